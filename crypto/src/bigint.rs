@@ -0,0 +1,197 @@
+use std::cmp::Ordering;
+
+const LIMBS: usize = 4;
+pub const WORDS: usize = 8;
+
+/// Unsigned 256-bit integer stored as little-endian 64-bit limbs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; LIMBS]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; LIMBS]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+
+    pub const fn from_limbs(limbs: [u64; LIMBS]) -> U256 {
+        U256(limbs)
+    }
+
+    /// Parses a big-endian hex string of at most 64 digits, panicking on invalid input.
+    /// Meant for constants.
+    pub const fn from_be_hex(hex: &str) -> U256 {
+        let bytes = hex.as_bytes();
+        assert!(bytes.len() <= 2 * LIMBS * 8, "Hex string is too long");
+        let mut limbs = [0; LIMBS];
+        let mut i = 0;
+        while i < bytes.len() {
+            let digit = match bytes[bytes.len() - 1 - i] {
+                b @ b'0'..=b'9' => b - b'0',
+                b @ b'a'..=b'f' => b - b'a' + 10,
+                b @ b'A'..=b'F' => b - b'A' + 10,
+                _ => panic!("Invalid hex digit"),
+            };
+            limbs[i / 16] |= (digit as u64) << ((i % 16) * 4);
+            i += 1;
+        }
+        U256(limbs)
+    }
+
+    /// Builds an integer from little-endian 32-bit words, the layout used in guest memory.
+    pub fn from_le_words(words: [u32; WORDS]) -> U256 {
+        let mut limbs = [0; LIMBS];
+        for (limb, pair) in limbs.iter_mut().zip(words.chunks_exact(2)) {
+            *limb = pair[0] as u64 | (pair[1] as u64) << 32;
+        }
+        U256(limbs)
+    }
+
+    pub fn to_le_words(&self) -> [u32; WORDS] {
+        let mut words = [0; WORDS];
+        for (pair, limb) in words.chunks_exact_mut(2).zip(self.0) {
+            pair[0] = limb as u32;
+            pair[1] = (limb >> 32) as u32;
+        }
+        words
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; LIMBS]
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        (self.0[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    pub fn overflowing_add(&self, other: &U256) -> (U256, bool) {
+        let mut result = [0; LIMBS];
+        let mut carry = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (sum, carry_a) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, carry_b) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = carry_a || carry_b;
+        }
+        (U256(result), carry)
+    }
+
+    pub fn overflowing_sub(&self, other: &U256) -> (U256, bool) {
+        let mut result = [0; LIMBS];
+        let mut borrow = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (diff, borrow_a) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, borrow_b) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = borrow_a || borrow_b;
+        }
+        (U256(result), borrow)
+    }
+
+    /// Full 512-bit product as little-endian limbs.
+    pub fn mul_wide(&self, other: &U256) -> [u64; 2 * LIMBS] {
+        let mut result = [0; 2 * LIMBS];
+        for i in 0..LIMBS {
+            let mut carry = 0u128;
+            for j in 0..LIMBS {
+                let acc = result[i + j] as u128 + self.0[i] as u128 * other.0[j] as u128 + carry;
+                result[i + j] = acc as u64;
+                carry = acc >> 64;
+            }
+            result[i + LIMBS] = carry as u64;
+        }
+        result
+    }
+
+    /// Low 256 bits of the product.
+    pub fn wrapping_mul(&self, other: &U256) -> U256 {
+        let wide = self.mul_wide(other);
+        U256([wide[0], wide[1], wide[2], wide[3]])
+    }
+
+    /// Reduces a 512-bit value modulo a non-zero `modulus` by binary long division.
+    fn reduce_wide(wide: &[u64; 2 * LIMBS], modulus: &U256) -> U256 {
+        debug_assert!(!modulus.is_zero());
+        let mut remainder = U256::ZERO;
+        for i in (0..2 * LIMBS * 64).rev() {
+            let bit = (wide[i / 64] >> (i % 64)) & 1;
+            let overflow = remainder.0[LIMBS - 1] >> 63 == 1;
+            let mut shifted = [0; LIMBS];
+            let mut carry = bit;
+            for (j, limb) in shifted.iter_mut().enumerate() {
+                *limb = (remainder.0[j] << 1) | carry;
+                carry = remainder.0[j] >> 63;
+            }
+            let shifted = U256(shifted);
+            // When the shift overflowed, the true value is `shifted + 2^256`, which is
+            // larger than the modulus and the wrapping subtraction yields the right result.
+            remainder = if overflow || shifted >= *modulus {
+                shifted.overflowing_sub(modulus).0
+            } else {
+                shifted
+            };
+        }
+        remainder
+    }
+
+    /// `self mod modulus` for a non-zero `modulus`.
+    pub fn reduce(&self, modulus: &U256) -> U256 {
+        let mut wide = [0; 2 * LIMBS];
+        wide[..LIMBS].copy_from_slice(&self.0);
+        U256::reduce_wide(&wide, modulus)
+    }
+
+    /// `self * other mod modulus` for a non-zero `modulus`. Operands need not be reduced.
+    pub fn mul_mod(&self, other: &U256, modulus: &U256) -> U256 {
+        U256::reduce_wide(&self.mul_wide(other), modulus)
+    }
+
+    // Modular helpers below expect operands already reduced modulo `modulus`.
+
+    pub fn add_mod(&self, other: &U256, modulus: &U256) -> U256 {
+        let (sum, overflow) = self.overflowing_add(other);
+        if overflow || sum >= *modulus {
+            sum.overflowing_sub(modulus).0
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub_mod(&self, other: &U256, modulus: &U256) -> U256 {
+        let (diff, borrow) = self.overflowing_sub(other);
+        if borrow {
+            diff.overflowing_add(modulus).0
+        } else {
+            diff
+        }
+    }
+
+    pub fn pow_mod(&self, exponent: &U256, modulus: &U256) -> U256 {
+        let mut result = U256::ONE.reduce(modulus);
+        for i in (0..LIMBS * 64).rev() {
+            result = result.mul_mod(&result, modulus);
+            if exponent.bit(i) {
+                result = result.mul_mod(self, modulus);
+            }
+        }
+        result
+    }
+
+    /// Inverse modulo a prime through Fermat's little theorem. Zero has no inverse.
+    pub fn inv_mod_prime(&self, prime: &U256) -> Option<U256> {
+        if self.is_zero() {
+            return None;
+        }
+        let exponent = prime.overflowing_sub(&U256::from_limbs([2, 0, 0, 0])).0;
+        Some(self.pow_mod(&exponent, prime))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &U256) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &U256) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,41 @@
+use crate::bigint::{U256, WORDS};
+
+pub const POINT_WORDS: usize = 2 * WORDS;
+
+/// Curve point in affine coordinates. In guest memory it is laid out as `x` followed
+/// by `y`, each as eight little-endian words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AffinePoint {
+    pub x: U256,
+    pub y: U256,
+}
+
+impl AffinePoint {
+    pub fn from_le_words(words: [u32; POINT_WORDS]) -> AffinePoint {
+        let mut x = [0; WORDS];
+        let mut y = [0; WORDS];
+        x.copy_from_slice(&words[..WORDS]);
+        y.copy_from_slice(&words[WORDS..]);
+        AffinePoint {
+            x: U256::from_le_words(x),
+            y: U256::from_le_words(y),
+        }
+    }
+
+    pub fn to_le_words(&self) -> [u32; POINT_WORDS] {
+        let mut words = [0; POINT_WORDS];
+        words[..WORDS].copy_from_slice(&self.x.to_le_words());
+        words[WORDS..].copy_from_slice(&self.y.to_le_words());
+        words
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CurveError {
+    #[error("Coordinate is not reduced modulo the field prime")]
+    CoordinateOutOfRange,
+    #[error("Point is not on the curve")]
+    NotOnCurve,
+    #[error("Result is the point at infinity")]
+    PointAtInfinity,
+}
@@ -0,0 +1,77 @@
+use crate::{
+    bigint::U256,
+    curve::{AffinePoint, CurveError},
+};
+
+// edwards25519: -x^2 + y^2 = 1 + d * x^2 * y^2 over the prime 2^255 - 19.
+pub const PRIME: U256 =
+    U256::from_be_hex("7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED");
+// d = -121665 / 121666
+const D: U256 =
+    U256::from_be_hex("52036CEE2B6FFE738CC740797779E89800700A4D4141D8AB75EB4DCA135978A3");
+
+pub const IDENTITY: AffinePoint = AffinePoint {
+    x: U256::ZERO,
+    y: U256::ONE,
+};
+
+pub const GENERATOR: AffinePoint = AffinePoint {
+    x: U256::from_be_hex("216936D3CD6E53FEC0A4E231FDD6DC5C692CC7609525A7B2C9562D608F25D51A"),
+    y: U256::from_be_hex("6666666666666666666666666666666666666666666666666666666666666658"),
+};
+
+/// Checks that both coordinates are reduced and that the point satisfies the curve equation.
+pub fn validate(point: &AffinePoint) -> Result<(), CurveError> {
+    if point.x >= PRIME || point.y >= PRIME {
+        return Err(CurveError::CoordinateOutOfRange);
+    }
+    let x_squared = point.x.mul_mod(&point.x, &PRIME);
+    let y_squared = point.y.mul_mod(&point.y, &PRIME);
+    let lhs = y_squared.sub_mod(&x_squared, &PRIME);
+    let rhs = D
+        .mul_mod(&x_squared, &PRIME)
+        .mul_mod(&y_squared, &PRIME)
+        .add_mod(&U256::ONE, &PRIME);
+    if lhs != rhs {
+        return Err(CurveError::NotOnCurve);
+    }
+    Ok(())
+}
+
+/// Adds two points with the complete twisted Edwards addition law, which never fails
+/// for points on the curve.
+pub fn add(p: &AffinePoint, q: &AffinePoint) -> Result<AffinePoint, CurveError> {
+    validate(p)?;
+    validate(q)?;
+    Ok(add_unchecked(p, q))
+}
+
+pub fn double(p: &AffinePoint) -> Result<AffinePoint, CurveError> {
+    validate(p)?;
+    Ok(add_unchecked(p, p))
+}
+
+// x3 = (x1 * y2 + y1 * x2) / (1 + d * x1 * x2 * y1 * y2)
+// y3 = (y1 * y2 + x1 * x2) / (1 - d * x1 * x2 * y1 * y2)
+fn add_unchecked(p: &AffinePoint, q: &AffinePoint) -> AffinePoint {
+    let x1y2 = p.x.mul_mod(&q.y, &PRIME);
+    let y1x2 = p.y.mul_mod(&q.x, &PRIME);
+    let x1x2 = p.x.mul_mod(&q.x, &PRIME);
+    let y1y2 = p.y.mul_mod(&q.y, &PRIME);
+    let dxy = D.mul_mod(&x1x2, &PRIME).mul_mod(&y1y2, &PRIME);
+    let x_denominator = U256::ONE.add_mod(&dxy, &PRIME);
+    let y_denominator = U256::ONE.sub_mod(&dxy, &PRIME);
+    let x = x1y2
+        .add_mod(&y1x2, &PRIME)
+        .mul_mod(&inverse(&x_denominator), &PRIME);
+    let y = y1y2
+        .add_mod(&x1x2, &PRIME)
+        .mul_mod(&inverse(&y_denominator), &PRIME);
+    AffinePoint { x, y }
+}
+
+fn inverse(value: &U256) -> U256 {
+    value
+        .inv_mod_prime(&PRIME)
+        .expect("Denominators of the complete addition law are non-zero")
+}
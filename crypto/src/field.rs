@@ -0,0 +1,131 @@
+use std::{
+    fmt::Display,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+// BabyBear prime: 2^31 - 2^27 + 1
+pub const MODULUS: u32 = 0x7800_0001;
+
+/// Element of the BabyBear prime field, always kept in canonical form (`< MODULUS`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FieldElement(u32);
+
+impl FieldElement {
+    pub const ZERO: FieldElement = FieldElement(0);
+    pub const ONE: FieldElement = FieldElement(1);
+
+    /// Builds an element from any `u32`, reducing it modulo the prime.
+    pub const fn new(value: u32) -> FieldElement {
+        FieldElement(value % MODULUS)
+    }
+
+    /// Builds an element only if `value` is already in canonical form.
+    pub const fn from_canonical(value: u32) -> Option<FieldElement> {
+        if value < MODULUS {
+            Some(FieldElement(value))
+        } else {
+            None
+        }
+    }
+
+    pub const fn value(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn add(self, other: FieldElement) -> FieldElement {
+        let sum = self.0 as u64 + other.0 as u64;
+        FieldElement((sum % MODULUS as u64) as u32)
+    }
+
+    pub const fn sub(self, other: FieldElement) -> FieldElement {
+        let diff = self.0 as u64 + MODULUS as u64 - other.0 as u64;
+        FieldElement((diff % MODULUS as u64) as u32)
+    }
+
+    pub const fn mul(self, other: FieldElement) -> FieldElement {
+        let product = self.0 as u64 * other.0 as u64;
+        FieldElement((product % MODULUS as u64) as u32)
+    }
+
+    pub const fn neg(self) -> FieldElement {
+        FieldElement::ZERO.sub(self)
+    }
+
+    pub const fn pow(self, mut exponent: u64) -> FieldElement {
+        let mut base = self;
+        let mut result = FieldElement::ONE;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(base);
+            }
+            base = base.mul(base);
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// Multiplicative inverse through Fermat's little theorem. Zero has no inverse.
+    pub const fn inv(self) -> Option<FieldElement> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.pow(MODULUS as u64 - 2))
+        }
+    }
+}
+
+impl Display for FieldElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Add for FieldElement {
+    type Output = FieldElement;
+
+    fn add(self, rhs: FieldElement) -> FieldElement {
+        FieldElement::add(self, rhs)
+    }
+}
+
+impl AddAssign for FieldElement {
+    fn add_assign(&mut self, rhs: FieldElement) {
+        *self = FieldElement::add(*self, rhs);
+    }
+}
+
+impl Sub for FieldElement {
+    type Output = FieldElement;
+
+    fn sub(self, rhs: FieldElement) -> FieldElement {
+        FieldElement::sub(self, rhs)
+    }
+}
+
+impl SubAssign for FieldElement {
+    fn sub_assign(&mut self, rhs: FieldElement) {
+        *self = FieldElement::sub(*self, rhs);
+    }
+}
+
+impl Mul for FieldElement {
+    type Output = FieldElement;
+
+    fn mul(self, rhs: FieldElement) -> FieldElement {
+        FieldElement::mul(self, rhs)
+    }
+}
+
+impl MulAssign for FieldElement {
+    fn mul_assign(&mut self, rhs: FieldElement) {
+        *self = FieldElement::mul(*self, rhs);
+    }
+}
+
+impl Neg for FieldElement {
+    type Output = FieldElement;
+
+    fn neg(self) -> FieldElement {
+        FieldElement::neg(self)
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bigint;
+pub mod curve;
+pub mod edwards25519;
+pub mod field;
+pub mod poseidon2;
+pub mod secp256k1;
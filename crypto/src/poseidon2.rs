@@ -0,0 +1,227 @@
+use crate::field::FieldElement;
+
+// Poseidon2 over BabyBear with a width-16 state and x^7 S-box.
+// The same permutation is exposed to guests as a syscall and used by the prover,
+// so any change here changes both the VM semantics and the proof system.
+pub const WIDTH: usize = 16;
+const FULL_ROUNDS: usize = 8;
+const HALF_FULL_ROUNDS: usize = FULL_ROUNDS / 2;
+const PARTIAL_ROUNDS: usize = 13;
+
+// Diagonal of the internal matrix `1 + diag(D)`:
+// [-2, 1, 2, 1/2, 3, 4, -1/2, -3, -4, 1/2^8, 1/4, 1/8, 1/2^27, -1/2^8, -1/16, -1/2^27]
+const INTERNAL_DIAGONAL: [FieldElement; WIDTH] = {
+    let two = FieldElement::new(2);
+    let half = inverse_of_power_of_two(1);
+    [
+        two.neg(),
+        FieldElement::ONE,
+        two,
+        half,
+        FieldElement::new(3),
+        FieldElement::new(4),
+        half.neg(),
+        FieldElement::new(3).neg(),
+        FieldElement::new(4).neg(),
+        inverse_of_power_of_two(8),
+        inverse_of_power_of_two(2),
+        inverse_of_power_of_two(3),
+        inverse_of_power_of_two(27),
+        inverse_of_power_of_two(8).neg(),
+        inverse_of_power_of_two(4).neg(),
+        inverse_of_power_of_two(27).neg(),
+    ]
+};
+
+const fn inverse_of_power_of_two(exponent: u64) -> FieldElement {
+    match FieldElement::new(2).pow(exponent).inv() {
+        Some(inverse) => inverse,
+        None => panic!("Powers of two are invertible"),
+    }
+}
+
+// Round constants come from the Grain LFSR of the Poseidon2 parameter scripts, seeded with
+// the field, the S-box and the round numbers, in round order: a constant per element for
+// the full rounds and a single one for the partial rounds.
+struct RoundConstants {
+    external: [[FieldElement; WIDTH]; FULL_ROUNDS],
+    internal: [FieldElement; PARTIAL_ROUNDS],
+}
+
+const ROUND_CONSTANTS: RoundConstants = {
+    let mut grain = Grain::new();
+    let mut external = [[FieldElement::ZERO; WIDTH]; FULL_ROUNDS];
+    let mut internal = [FieldElement::ZERO; PARTIAL_ROUNDS];
+    let mut round = 0;
+    while round < FULL_ROUNDS + PARTIAL_ROUNDS {
+        if round < HALF_FULL_ROUNDS || round >= HALF_FULL_ROUNDS + PARTIAL_ROUNDS {
+            let full_round = if round < HALF_FULL_ROUNDS {
+                round
+            } else {
+                round - PARTIAL_ROUNDS
+            };
+            let mut i = 0;
+            while i < WIDTH {
+                external[full_round][i] = grain.field_element();
+                i += 1;
+            }
+        } else {
+            internal[round - HALF_FULL_ROUNDS] = grain.field_element();
+        }
+        round += 1;
+    }
+    RoundConstants { external, internal }
+};
+
+// Bits needed for an element of BabyBear
+const FIELD_BITS: u32 = 31;
+
+// 80 bit LFSR, bit `i` of the state is the `i`-th bit of the sequence
+struct Grain(u128);
+
+impl Grain {
+    const fn new() -> Grain {
+        const PRIME_FIELD: u128 = 1;
+        const POWER_SBOX: u128 = 0;
+        let fields = [
+            (PRIME_FIELD, 2),
+            (POWER_SBOX, 4),
+            (FIELD_BITS as u128, 12),
+            (WIDTH as u128, 12),
+            (FULL_ROUNDS as u128, 10),
+            (PARTIAL_ROUNDS as u128, 10),
+            ((1 << 30) - 1, 30),
+        ];
+        let mut state = 0;
+        let mut position = 0;
+        let mut f = 0;
+        while f < fields.len() {
+            let (value, bits) = fields[f];
+            let mut bit = 0;
+            while bit < bits {
+                state |= ((value >> (bits - 1 - bit)) & 1) << position;
+                position += 1;
+                bit += 1;
+            }
+            f += 1;
+        }
+        let mut grain = Grain(state);
+        let mut i = 0;
+        while i < 160 {
+            grain.next_bit();
+            i += 1;
+        }
+        grain
+    }
+
+    const fn next_bit(&mut self) -> u128 {
+        let state = self.0;
+        let bit = (state >> 62 ^ state >> 51 ^ state >> 38 ^ state >> 23 ^ state >> 13 ^ state) & 1;
+        self.0 = state >> 1 | bit << 79;
+        bit
+    }
+
+    // Bits come in pairs, and the second one is kept only when the first one is set
+    const fn output_bit(&mut self) -> u128 {
+        loop {
+            if self.next_bit() == 1 {
+                return self.next_bit();
+            }
+            self.next_bit();
+        }
+    }
+
+    // Most significant bit first, rejecting values that are not canonical
+    const fn field_element(&mut self) -> FieldElement {
+        loop {
+            let mut value = 0;
+            let mut i = 0;
+            while i < FIELD_BITS {
+                value = value << 1 | self.output_bit() as u32;
+                i += 1;
+            }
+            if let Some(element) = FieldElement::from_canonical(value) {
+                return element;
+            }
+        }
+    }
+}
+
+/// Applies the Poseidon2 permutation in place.
+pub fn permute(state: &mut [FieldElement; WIDTH]) {
+    external_linear_layer(state);
+    for round_constants in &ROUND_CONSTANTS.external[..HALF_FULL_ROUNDS] {
+        full_round(state, round_constants);
+    }
+    for round_constant in ROUND_CONSTANTS.internal {
+        state[0] += round_constant;
+        state[0] = sbox(state[0]);
+        internal_linear_layer(state);
+    }
+    for round_constants in &ROUND_CONSTANTS.external[HALF_FULL_ROUNDS..] {
+        full_round(state, round_constants);
+    }
+}
+
+fn full_round(state: &mut [FieldElement; WIDTH], round_constants: &[FieldElement; WIDTH]) {
+    for (element, constant) in state.iter_mut().zip(round_constants) {
+        *element = sbox(*element + *constant);
+    }
+    external_linear_layer(state);
+}
+
+fn sbox(x: FieldElement) -> FieldElement {
+    let x2 = x * x;
+    let x3 = x2 * x;
+    let x6 = x3 * x3;
+    x6 * x
+}
+
+// M_E = circ(2 * M4, M4, M4, M4), applied as M4 on each chunk followed by
+// adding the column-wise sum of all chunks.
+fn external_linear_layer(state: &mut [FieldElement; WIDTH]) {
+    for chunk in state.chunks_exact_mut(4) {
+        apply_m4(chunk);
+    }
+    let mut sums = [FieldElement::ZERO; 4];
+    for chunk in state.chunks_exact(4) {
+        for (sum, element) in sums.iter_mut().zip(chunk) {
+            *sum += *element;
+        }
+    }
+    for chunk in state.chunks_exact_mut(4) {
+        for (element, sum) in chunk.iter_mut().zip(sums) {
+            *element += sum;
+        }
+    }
+}
+
+// M4 from the Poseidon2 paper:
+// | 5 7 1 3 |
+// | 4 6 1 1 |
+// | 1 3 5 7 |
+// | 1 1 4 6 |
+fn apply_m4(x: &mut [FieldElement]) {
+    let t0 = x[0] + x[1];
+    let t1 = x[2] + x[3];
+    let t2 = x[1] + x[1] + t1;
+    let t3 = x[3] + x[3] + t0;
+    let t4 = t1 + t1 + t1 + t1 + t3;
+    let t5 = t0 + t0 + t0 + t0 + t2;
+    let t6 = t3 + t5;
+    let t7 = t2 + t4;
+    x[0] = t6;
+    x[1] = t5;
+    x[2] = t7;
+    x[3] = t4;
+}
+
+// M_I = 1 + diag(D): every element gets the sum of the state plus itself scaled by D.
+fn internal_linear_layer(state: &mut [FieldElement; WIDTH]) {
+    let sum = state
+        .iter()
+        .fold(FieldElement::ZERO, |acc, element| acc + *element);
+    for (element, diagonal) in state.iter_mut().zip(INTERNAL_DIAGONAL) {
+        *element = *element * diagonal + sum;
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    bigint::U256,
+    curve::{AffinePoint, CurveError},
+};
+
+// secp256k1: y^2 = x^3 + 7 over the prime 2^256 - 2^32 - 977.
+pub const PRIME: U256 =
+    U256::from_be_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F");
+const B: U256 = U256::from_limbs([7, 0, 0, 0]);
+
+pub const GENERATOR: AffinePoint = AffinePoint {
+    x: U256::from_be_hex("79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+    y: U256::from_be_hex("483ADA7726A3C4655DA4FBFC0E1108A8FD17B448A68554199C47D08FFB10D4B8"),
+};
+
+/// Checks that both coordinates are reduced and that the point satisfies the curve equation.
+pub fn validate(point: &AffinePoint) -> Result<(), CurveError> {
+    if point.x >= PRIME || point.y >= PRIME {
+        return Err(CurveError::CoordinateOutOfRange);
+    }
+    let y_squared = point.y.mul_mod(&point.y, &PRIME);
+    let x_cubed = point.x.mul_mod(&point.x, &PRIME).mul_mod(&point.x, &PRIME);
+    if y_squared != x_cubed.add_mod(&B, &PRIME) {
+        return Err(CurveError::NotOnCurve);
+    }
+    Ok(())
+}
+
+/// Adds two points. Adding a point to itself doubles it; adding a point to its
+/// negation has no affine result and is reported as an error.
+pub fn add(p: &AffinePoint, q: &AffinePoint) -> Result<AffinePoint, CurveError> {
+    validate(p)?;
+    validate(q)?;
+    if p.x == q.x {
+        return if p.y == q.y {
+            double_unchecked(p)
+        } else {
+            Err(CurveError::PointAtInfinity)
+        };
+    }
+    // lambda = (y2 - y1) / (x2 - x1)
+    let numerator = q.y.sub_mod(&p.y, &PRIME);
+    let denominator = q.x.sub_mod(&p.x, &PRIME);
+    let lambda = numerator.mul_mod(&inverse(&denominator), &PRIME);
+    Ok(from_slope(p, &q.x, &lambda))
+}
+
+pub fn double(p: &AffinePoint) -> Result<AffinePoint, CurveError> {
+    validate(p)?;
+    double_unchecked(p)
+}
+
+fn double_unchecked(p: &AffinePoint) -> Result<AffinePoint, CurveError> {
+    if p.y.is_zero() {
+        return Err(CurveError::PointAtInfinity);
+    }
+    // lambda = 3 * x^2 / (2 * y)
+    let x_squared = p.x.mul_mod(&p.x, &PRIME);
+    let numerator = x_squared
+        .add_mod(&x_squared, &PRIME)
+        .add_mod(&x_squared, &PRIME);
+    let denominator = p.y.add_mod(&p.y, &PRIME);
+    let lambda = numerator.mul_mod(&inverse(&denominator), &PRIME);
+    Ok(from_slope(p, &p.x, &lambda))
+}
+
+// x3 = lambda^2 - x1 - x2, y3 = lambda * (x1 - x3) - y1
+fn from_slope(p: &AffinePoint, other_x: &U256, lambda: &U256) -> AffinePoint {
+    let x = lambda
+        .mul_mod(lambda, &PRIME)
+        .sub_mod(&p.x, &PRIME)
+        .sub_mod(other_x, &PRIME);
+    let y = lambda
+        .mul_mod(&p.x.sub_mod(&x, &PRIME), &PRIME)
+        .sub_mod(&p.y, &PRIME);
+    AffinePoint { x, y }
+}
+
+fn inverse(value: &U256) -> U256 {
+    value
+        .inv_mod_prime(&PRIME)
+        .expect("Denominators of the group law are non-zero")
+}
@@ -0,0 +1,36 @@
+use crypto::bigint::U256;
+
+fn x() -> U256 {
+    U256::from_be_hex("D23F0824128B2F330C5C7FD0A6A3A4506513270E269E0D37F2A74DE452E6B438")
+}
+
+fn y() -> U256 {
+    U256::from_be_hex("36F675CC81E74EF5E8E25D940ED904759531985D5D9DC9F81818E811892F902B")
+}
+
+#[test]
+fn test_mul_mod_with_unreduced_operands() {
+    let modulus =
+        U256::from_be_hex("8D116ECE1738F7D93D9C172411E20B8F6B0D549B6F03675A1600A35A099950D9");
+    let expected =
+        U256::from_be_hex("2E5051F9769DBC09D9CB0C2248C48DA273B4BC1711AB5BA7BBA78465E08E3C55");
+    assert_eq!(x().mul_mod(&y(), &modulus), expected);
+}
+
+#[test]
+fn test_wrapping_mul() {
+    let expected =
+        U256::from_be_hex("65F99D1EE00DB3DC2AE0851BD5090F341BD44E608453D25B1517EA80C067C568");
+    assert_eq!(x().wrapping_mul(&y()), expected);
+}
+
+#[test]
+fn test_mul_mod_by_one_is_zero() {
+    assert_eq!(x().mul_mod(&y(), &U256::ONE), U256::ZERO);
+}
+
+#[test]
+fn test_le_words_round_trip() {
+    assert_eq!(U256::from_le_words(x().to_le_words()), x());
+    assert_eq!(x().to_le_words()[0], 0x52e6b438);
+}
@@ -0,0 +1,118 @@
+use crypto::{
+    bigint::U256,
+    curve::{AffinePoint, CurveError},
+    edwards25519, secp256k1,
+};
+
+fn point(x: &str, y: &str) -> AffinePoint {
+    AffinePoint {
+        x: U256::from_be_hex(x),
+        y: U256::from_be_hex(y),
+    }
+}
+
+fn secp256k1_two_g() -> AffinePoint {
+    point(
+        "C6047F9441ED7D6D3045406E95C07CD85C778E4B8CEF3CA7ABAC09B95C709EE5",
+        "1AE168FEA63DC339A3C58419466CEAEEF7F632653266D0E1236431A950CFE52A",
+    )
+}
+
+fn secp256k1_three_g() -> AffinePoint {
+    point(
+        "F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+        "388F7B0F632DE8140FE337E62A37F3566500A99934C2231B6CB9FD7584B8E672",
+    )
+}
+
+fn edwards25519_two_g() -> AffinePoint {
+    point(
+        "36AB384C9F5A046C3D043B7D1833E7AC080D8E4515D7A45F83C5A14E2843CE0E",
+        "2260CDF3092329C21DA25EE8C9A21F5697390F51643851560E5F46AE6AF8A3C9",
+    )
+}
+
+fn edwards25519_three_g() -> AffinePoint {
+    point(
+        "67AE9C4A22928F491FF4AE743EDAC83A6343981981624886AC62485FD3F8E25C",
+        "1267B1D177EE69ABA126A18E60269EF79F16EC176724030402C3684878F5B4D4",
+    )
+}
+
+#[test]
+fn test_secp256k1_double() {
+    let result = secp256k1::double(&secp256k1::GENERATOR).unwrap();
+    assert_eq!(result, secp256k1_two_g());
+}
+
+#[test]
+fn test_secp256k1_add() {
+    let result = secp256k1::add(&secp256k1::GENERATOR, &secp256k1_two_g()).unwrap();
+    assert_eq!(result, secp256k1_three_g());
+}
+
+#[test]
+fn test_secp256k1_add_same_point_doubles() {
+    let result = secp256k1::add(&secp256k1::GENERATOR, &secp256k1::GENERATOR).unwrap();
+    assert_eq!(result, secp256k1_two_g());
+}
+
+#[test]
+fn test_secp256k1_add_negation_is_infinity() {
+    let g = secp256k1::GENERATOR;
+    let negated = AffinePoint {
+        x: g.x,
+        y: secp256k1::PRIME.overflowing_sub(&g.y).0,
+    };
+    assert_eq!(
+        secp256k1::add(&g, &negated),
+        Err(CurveError::PointAtInfinity)
+    );
+}
+
+#[test]
+fn test_secp256k1_invalid_points() {
+    let mut off_curve = secp256k1::GENERATOR;
+    off_curve.y = off_curve.y.add_mod(&U256::ONE, &secp256k1::PRIME);
+    assert_eq!(secp256k1::double(&off_curve), Err(CurveError::NotOnCurve));
+
+    let mut out_of_range = secp256k1::GENERATOR;
+    out_of_range.x = secp256k1::PRIME;
+    assert_eq!(
+        secp256k1::add(&secp256k1::GENERATOR, &out_of_range),
+        Err(CurveError::CoordinateOutOfRange)
+    );
+}
+
+#[test]
+fn test_edwards25519_double() {
+    let result = edwards25519::double(&edwards25519::GENERATOR).unwrap();
+    assert_eq!(result, edwards25519_two_g());
+}
+
+#[test]
+fn test_edwards25519_add() {
+    let result = edwards25519::add(&edwards25519_two_g(), &edwards25519::GENERATOR).unwrap();
+    assert_eq!(result, edwards25519_three_g());
+}
+
+#[test]
+fn test_edwards25519_add_identity() {
+    let result = edwards25519::add(&edwards25519::GENERATOR, &edwards25519::IDENTITY).unwrap();
+    assert_eq!(result, edwards25519::GENERATOR);
+}
+
+#[test]
+fn test_edwards25519_invalid_point() {
+    let off_curve = point("1", "1");
+    assert_eq!(
+        edwards25519::double(&off_curve),
+        Err(CurveError::NotOnCurve)
+    );
+}
+
+#[test]
+fn test_point_words_round_trip() {
+    let g = secp256k1::GENERATOR;
+    assert_eq!(AffinePoint::from_le_words(g.to_le_words()), g);
+}
@@ -0,0 +1,45 @@
+use crypto::{
+    field::{FieldElement, MODULUS},
+    poseidon2::{WIDTH, permute},
+};
+
+fn state_from(values: [u32; WIDTH]) -> [FieldElement; WIDTH] {
+    values.map(FieldElement::new)
+}
+
+#[test]
+fn test_permutation_known_answer() {
+    // Regression vector for the permutation of [0, 1, ..., 15]
+    let mut state = state_from(std::array::from_fn(|i| i as u32));
+    permute(&mut state);
+    let expected = [
+        703824558, 661597802, 1125127041, 972584437, 874705857, 558540447, 1673783368, 447354557,
+        406581445, 865045115, 453544240, 1793488887, 1236810461, 438848728, 944774266, 1379823082,
+    ];
+    assert_eq!(state, state_from(expected));
+}
+
+#[test]
+fn test_permutation_of_zero_state_is_not_zero() {
+    let mut state = [FieldElement::ZERO; WIDTH];
+    permute(&mut state);
+    assert!(state.iter().all(|element| *element != FieldElement::ZERO));
+}
+
+#[test]
+fn test_permutation_diffuses_single_element_change() {
+    let mut a = [FieldElement::ZERO; WIDTH];
+    let mut b = [FieldElement::ZERO; WIDTH];
+    b[WIDTH - 1] = FieldElement::ONE;
+    permute(&mut a);
+    permute(&mut b);
+    assert!(a.iter().zip(&b).all(|(x, y)| x != y));
+}
+
+#[test]
+fn test_field_inverse() {
+    let x = FieldElement::new(MODULUS - 5);
+    assert_eq!(x * x.inv().unwrap(), FieldElement::ONE);
+    assert_eq!(FieldElement::ZERO.inv(), None);
+    assert_eq!(FieldElement::from_canonical(MODULUS), None);
+}
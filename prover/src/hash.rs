@@ -0,0 +1,44 @@
+// Hashing for the commitments of the prover. It is built on the same Poseidon2 permutation
+// the VM exposes to guests, so guest programs can recompute the digests.
+
+use crypto::{
+    field::FieldElement,
+    poseidon2::{WIDTH, permute},
+};
+
+pub const DIGEST_ELEMENTS: usize = 8;
+// The rest of the state is the capacity
+const RATE: usize = 8;
+
+pub type Digest = [FieldElement; DIGEST_ELEMENTS];
+
+/// Sponge hash of `elements`, absorbing `RATE` elements per permutation. The length goes into
+/// the capacity, so inputs that only differ by trailing zeros hash differently.
+pub fn hash(elements: &[FieldElement]) -> Digest {
+    let mut state = [FieldElement::ZERO; WIDTH];
+    state[RATE] = FieldElement::new(elements.len() as u32);
+    for chunk in elements.chunks(RATE) {
+        for (element, input) in state.iter_mut().zip(chunk) {
+            *element += *input;
+        }
+        permute(&mut state);
+    }
+    if elements.is_empty() {
+        permute(&mut state);
+    }
+    truncate(&state)
+}
+
+/// Two-to-one compression for the nodes of Merkle trees: the permutation of both digests,
+/// truncated.
+pub fn compress(left: &Digest, right: &Digest) -> Digest {
+    let mut state = [FieldElement::ZERO; WIDTH];
+    state[..DIGEST_ELEMENTS].copy_from_slice(left);
+    state[DIGEST_ELEMENTS..].copy_from_slice(right);
+    permute(&mut state);
+    truncate(&state)
+}
+
+fn truncate(state: &[FieldElement; WIDTH]) -> Digest {
+    std::array::from_fn(|i| state[i])
+}
@@ -0,0 +1,29 @@
+use crypto::{
+    field::FieldElement,
+    poseidon2::{WIDTH, permute},
+};
+use prover::hash::{Digest, compress, hash};
+
+fn elements(values: &[u32]) -> Vec<FieldElement> {
+    values.iter().copied().map(FieldElement::new).collect()
+}
+
+#[test]
+fn test_compress_is_the_truncated_permutation() {
+    let left: Digest = std::array::from_fn(|i| FieldElement::new(i as u32));
+    let right: Digest = std::array::from_fn(|i| FieldElement::new(100 + i as u32));
+    let mut state: [FieldElement; WIDTH] =
+        std::array::from_fn(|i| if i < 8 { left[i] } else { right[i - 8] });
+    permute(&mut state);
+    assert_eq!(compress(&left, &right)[..], state[..8]);
+    assert_ne!(compress(&left, &right), compress(&right, &left));
+}
+
+#[test]
+fn test_hash_depends_on_length() {
+    assert_ne!(hash(&[]), hash(&elements(&[0])));
+    assert_ne!(hash(&elements(&[1, 2, 3])), hash(&elements(&[1, 2, 3, 0])));
+    // A whole number of chunks and one more
+    let long = elements(&(0..17).collect::<Vec<_>>());
+    assert_ne!(hash(&long[..16]), hash(&long));
+}
@@ -0,0 +1,31 @@
+// Helpers for the VM specific instructions in the custom-0 and custom-1 opcode spaces.
+// They do the same work as the matching syscalls in a single instruction, and only exist
+// when building for the VM.
+
+/// Applies the Poseidon2 permutation to `state` in place.
+/// Every word must be a canonical BabyBear element, otherwise the VM stops with an error.
+pub fn poseidon2_permute(state: &mut [u32; 16]) {
+    unsafe {
+        // custom-0, funct3 = 0, funct7 = 0
+        core::arch::asm!(
+            ".insn r 0x0b, 0, 0, x0, {state}, x0",
+            state = in(reg) state.as_mut_ptr(),
+        );
+    }
+}
+
+/// Sets `x` to `x + y` modulo 2^256 for little-endian 256-bit integers and returns the
+/// carry out.
+pub fn uint256_add(x: &mut [u32; 8], y: &[u32; 8]) -> bool {
+    unsafe {
+        let carry: u32;
+        // custom-1, funct3 = 0, funct7 = 0
+        core::arch::asm!(
+            ".insn r 0x2b, 0, 0, {carry}, {x}, {y}",
+            carry = out(reg) carry,
+            x = in(reg) x.as_mut_ptr(),
+            y = in(reg) y.as_ptr(),
+        );
+        carry != 0
+    }
+}
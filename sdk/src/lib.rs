@@ -0,0 +1,5 @@
+#![no_std]
+
+#[cfg(target_arch = "riscv32")]
+pub mod custom;
+pub mod syscalls;
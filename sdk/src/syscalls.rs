@@ -0,0 +1,62 @@
+// Syscall numbers, passed in a7. They must match the ones in `vm::vm::syscalls`.
+// The wrappers below only exist when building for the VM.
+pub const POSEIDON2_PERMUTE: u32 = 0x01;
+pub const SECP256K1_ADD: u32 = 0x02;
+pub const SECP256K1_DOUBLE: u32 = 0x03;
+pub const ED25519_ADD: u32 = 0x04;
+pub const ED25519_DOUBLE: u32 = 0x05;
+pub const UINT256_MUL_MOD: u32 = 0x06;
+
+/// Affine point as sixteen little-endian words: `x` followed by `y`.
+pub type AffinePoint = [u32; 16];
+
+/// Applies the Poseidon2 permutation to `state` in place.
+/// Every word must be a canonical BabyBear element, otherwise the VM stops with an error.
+#[cfg(target_arch = "riscv32")]
+pub fn poseidon2_permute(state: &mut [u32; 16]) {
+    syscall(POSEIDON2_PERMUTE, state.as_mut_ptr(), core::ptr::null());
+}
+
+/// Sets `p` to `p + q` on secp256k1.
+/// Both points must be on the curve and `q` must not be the negation of `p`.
+#[cfg(target_arch = "riscv32")]
+pub fn secp256k1_add(p: &mut AffinePoint, q: &AffinePoint) {
+    syscall(SECP256K1_ADD, p.as_mut_ptr(), q.as_ptr());
+}
+
+/// Sets `p` to `2p` on secp256k1.
+#[cfg(target_arch = "riscv32")]
+pub fn secp256k1_double(p: &mut AffinePoint) {
+    syscall(SECP256K1_DOUBLE, p.as_mut_ptr(), core::ptr::null());
+}
+
+/// Sets `p` to `p + q` on edwards25519. Both points must be on the curve.
+#[cfg(target_arch = "riscv32")]
+pub fn ed25519_add(p: &mut AffinePoint, q: &AffinePoint) {
+    syscall(ED25519_ADD, p.as_mut_ptr(), q.as_ptr());
+}
+
+/// Sets `p` to `2p` on edwards25519.
+#[cfg(target_arch = "riscv32")]
+pub fn ed25519_double(p: &mut AffinePoint) {
+    syscall(ED25519_DOUBLE, p.as_mut_ptr(), core::ptr::null());
+}
+
+/// Sets `x` to `x * y mod m` for little-endian 256-bit integers, where
+/// `y_and_modulus` holds `y` followed by `m`. A zero `m` stands for 2^256.
+#[cfg(target_arch = "riscv32")]
+pub fn uint256_mul_mod(x: &mut [u32; 8], y_and_modulus: &[u32; 16]) {
+    syscall(UINT256_MUL_MOD, x.as_mut_ptr(), y_and_modulus.as_ptr());
+}
+
+#[cfg(target_arch = "riscv32")]
+fn syscall(number: u32, first_argument: *mut u32, second_argument: *const u32) {
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            in("a7") number,
+            in("a0") first_argument,
+            in("a1") second_argument,
+        );
+    }
+}
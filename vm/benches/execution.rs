@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use vm::{assembler::assemble, vm::execution::Executor};
+
+// Iterative Fibonacci, five instructions per iteration
+const FIBONACCI: &str = "
+main:
+    li a0, 0
+    li a1, 1
+    li t0, 1000000
+loop:
+    add t1, a0, a1
+    mv a0, a1
+    mv a1, t1
+    addi t0, t0, -1
+    bnez t0, loop
+    ret
+";
+const RUNS: usize = 5;
+
+fn step(executor: &mut Executor) {
+    while !executor.is_halted() {
+        executor.step().unwrap();
+    }
+}
+
+fn run_blocks(executor: &mut Executor) {
+    executor.run().unwrap();
+}
+
+// Fastest of several runs, to filter out noise
+fn measure(make_executor: impl Fn() -> Executor, execute: fn(&mut Executor)) -> (Duration, u64) {
+    (0..RUNS)
+        .map(|_| {
+            let mut executor = make_executor();
+            let start = Instant::now();
+            execute(&mut executor);
+            (start.elapsed(), executor.cycles)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let program = assemble(FIBONACCI).unwrap();
+    let new_executor = || Executor::new(program.image.clone(), program.entry_point);
+    let (uncached, steps) = measure(|| new_executor().without_decode_cache(), step);
+    let (cached, _) = measure(new_executor, step);
+    let (blocks, _) = measure(new_executor, run_blocks);
+    #[allow(unused_mut)]
+    let mut modes = vec![
+        ("decode every step", uncached),
+        ("decode cache", cached),
+        ("basic blocks", blocks),
+    ];
+    #[cfg(feature = "jit")]
+    {
+        use vm::vm::execution::JIT_HOT_THRESHOLD;
+        let (jit, _) = measure(|| new_executor().with_jit(JIT_HOT_THRESHOLD), run_blocks);
+        modes.push(("jit", jit));
+    }
+    println!("{steps} steps");
+    for (mode, time) in modes {
+        println!(
+            "{mode:<18} {time:>12.3?} {:>6.1} ns/step {:>6.2}x",
+            time.as_nanos() as f64 / steps as f64,
+            uncached.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vm::elf::{Elf, LoadLimits};
+
+// Small enough that the fuzzer reaches the limit instead of running out of memory
+const LIMITS: LoadLimits = LoadLimits {
+    max_segments: 16,
+    max_image_size: 1 << 20,
+};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(elf) = Elf::load_with_limits(data, LIMITS) {
+        assert!(elf.image.len() as u32 * 4 <= LIMITS.max_image_size);
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use libfuzzer_sys::fuzz_target;
+use vm::vm::execution::Executor;
+
+const ENTRY_POINT: u32 = 0x10000;
+const MAX_STEPS: usize = 10_000;
+
+// Runs the input as code until it halts, fails or runs out of steps
+fuzz_target!(|words: Vec<u32>| {
+    let image: BTreeMap<u32, u32> = (ENTRY_POINT..).step_by(4).zip(words).collect();
+    let mut executor = Executor::new(image, ENTRY_POINT);
+    for _ in 0..MAX_STEPS {
+        if executor.is_halted() || executor.step().is_err() {
+            break;
+        }
+    }
+});
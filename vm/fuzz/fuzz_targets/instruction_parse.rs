@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vm::vm::instructions::Instruction;
+
+fuzz_target!(|word: u32| {
+    if let Ok(instruction) = Instruction::parse(word) {
+        // Unused fields must be zero, so every valid word has a single encoding
+        assert_eq!(instruction.try_encode().unwrap(), word);
+        let _ = instruction.to_string();
+    }
+});
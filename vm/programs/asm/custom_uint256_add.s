@@ -0,0 +1,19 @@
+	.attribute	5, "rv32i2p1_m2p0_zmmul1p0"
+.Lfunc_end0:
+	.globl	main
+main:
+	la	a1, x
+	la	a2, y
+	.insn	r	0x2b, 0, 0, a3, a1, a2
+	lw	a0, 0(a1)
+	add	a0, a0, a3
+	jalr	zero, 0(ra)
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+
+	.data
+	.p2align	2
+x:
+	.word	0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff
+y:
+	.word	0x00000002, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000
@@ -0,0 +1,21 @@
+	.attribute	5, "rv32i2p1_m2p0_zmmul1p0"
+.Lfunc_end0:
+	.globl	main
+main:
+	la	a0, p
+	la	a1, q
+	addi	a7, zero, 4
+	ecall
+	lw	a0, 0(a0)
+	jalr	zero, 0(ra)
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+
+	.data
+	.p2align	2
+p:
+	.word	0x2843ce0e, 0x83c5a14e, 0x15d7a45f, 0x080d8e45, 0x1833e7ac, 0x3d043b7d, 0x9f5a046c, 0x36ab384c
+	.word	0x6af8a3c9, 0x0e5f46ae, 0x64385156, 0x97390f51, 0xc9a21f56, 0x1da25ee8, 0x092329c2, 0x2260cdf3
+q:
+	.word	0x8f25d51a, 0xc9562d60, 0x9525a7b2, 0x692cc760, 0xfdd6dc5c, 0xc0a4e231, 0xcd6e53fe, 0x216936d3
+	.word	0x66666658, 0x66666666, 0x66666666, 0x66666666, 0x66666666, 0x66666666, 0x66666666, 0x66666666
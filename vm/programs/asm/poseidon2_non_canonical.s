@@ -0,0 +1,15 @@
+	.attribute	5, "rv32i2p1_m2p0_zmmul1p0"
+.Lfunc_end0:
+	.globl	main
+main:
+	la	a0, state
+	addi	a7, zero, 1
+	ecall
+	jalr	zero, 0(ra)
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+
+	.data
+	.p2align	2
+state:
+	.word	0, 1, 2, 3, 4, 5, 6, 0x78000001, 8, 9, 10, 11, 12, 13, 14, 15
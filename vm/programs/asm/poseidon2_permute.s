@@ -0,0 +1,16 @@
+	.attribute	5, "rv32i2p1_m2p0_zmmul1p0"
+.Lfunc_end0:
+	.globl	main
+main:
+	la	a0, state
+	addi	a7, zero, 1
+	ecall
+	lw	a0, 0(a0)
+	jalr	zero, 0(ra)
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+
+	.data
+	.p2align	2
+state:
+	.word	0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
@@ -0,0 +1,21 @@
+	.attribute	5, "rv32i2p1_m2p0_zmmul1p0"
+.Lfunc_end0:
+	.globl	main
+main:
+	la	a0, x
+	la	a1, y
+	addi	a7, zero, 6
+	ecall
+	lw	a0, 0(a0)
+	jalr	zero, 0(ra)
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+
+	.data
+	.p2align	2
+x:
+	.word	0x52e6b438, 0xf2a74de4, 0x269e0d37, 0x6513270e, 0xa6a3a450, 0x0c5c7fd0, 0x128b2f33, 0xd23f0824
+y:
+	.word	0x892f902b, 0x1818e811, 0x5d9dc9f8, 0x9531985d, 0x0ed90475, 0xe8e25d94, 0x81e74ef5, 0x36f675cc
+m:
+	.word	0x099950d9, 0x1600a35a, 0x6f03675a, 0x6b0d549b, 0x11e20b8f, 0x3d9c1724, 0x1738f7d9, 0x8d116ece
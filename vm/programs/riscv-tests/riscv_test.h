@@ -0,0 +1,37 @@
+// Test environment for riscv-tests style programs running on the VM.
+// There are no traps or privilege modes, so tests report their outcome by writing
+// to `tohost` directly: 1 on success, (TESTNUM << 1) | 1 on failure.
+
+#define TESTNUM gp
+
+#define RVTEST_RV32U
+
+#define RVTEST_CODE_BEGIN                                               \
+        .text;                                                          \
+        .globl _start;                                                  \
+_start:                                                                 \
+        li TESTNUM, 0;
+
+#define RVTEST_CODE_END
+
+#define RVTEST_PASS                                                     \
+        li TESTNUM, 1;                                                  \
+write_tohost:                                                           \
+        la t5, tohost;                                                  \
+        sw TESTNUM, 0(t5);                                              \
+1:      j 1b;
+
+#define RVTEST_FAIL                                                     \
+        sll TESTNUM, TESTNUM, 1;                                        \
+        or TESTNUM, TESTNUM, 1;                                         \
+        j write_tohost;
+
+#define RVTEST_DATA_BEGIN                                               \
+        .pushsection .tohost,"aw",@progbits;                            \
+        .align 6; .globl tohost; tohost: .word 0, 0;                    \
+        .align 6; .globl fromhost; fromhost: .word 0, 0;                \
+        .popsection;                                                    \
+        .data;                                                          \
+        .align 4;
+
+#define RVTEST_DATA_END
@@ -0,0 +1,897 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    elf::{Elf, WORD_SIZE},
+    vm::{
+        instructions::{ArithOp, Comparison, EncodeError, Instruction, LoadStoreWidth},
+        registers::register_index,
+    },
+};
+
+// Programs are laid out like a linked executable: code first, data on the following page
+pub const TEXT_START: u32 = 0x0001_0000;
+const DATA_ALIGNMENT: u32 = 0x1000;
+const ENTRY_SYMBOL: &str = "main";
+
+const NOP: Instruction = Instruction::ArithImm {
+    dst: 0,
+    src: 0,
+    imm: 0,
+    op: ArithOp::Add,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssemblerError {
+    #[error("Line {line}: {error}")]
+    Line { line: usize, error: LineError },
+    #[error("No `{ENTRY_SYMBOL}` symbol to use as entry point")]
+    MissingEntryPoint,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LineError {
+    #[error("Unknown instruction: {0}")]
+    UnknownInstruction(String),
+    #[error("Unknown directive: {0}")]
+    UnknownDirective(String),
+    #[error("Invalid operands for {0}")]
+    InvalidOperands(String),
+    #[error("Invalid register: {0}")]
+    InvalidRegister(String),
+    #[error("Invalid expression: {0}")]
+    InvalidExpression(String),
+    #[error("Undefined symbol: {0}")]
+    UndefinedSymbol(String),
+    #[error("Symbol defined more than once: {0}")]
+    DuplicateSymbol(String),
+    #[error("Value {0} does not fit")]
+    ValueOutOfRange(i64),
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Text,
+    Data,
+}
+
+enum StatementKind<'a> {
+    Instruction {
+        mnemonic: &'a str,
+        operands: Vec<&'a str>,
+    },
+    // `.insn r opcode, func3, func7, rd, rs1, rs2`
+    Insn {
+        operands: Vec<&'a str>,
+    },
+    Data {
+        width: u32,
+        values: Vec<&'a str>,
+    },
+    Bytes(Vec<u8>),
+    Padding(u32),
+}
+
+struct Statement<'a> {
+    line: usize,
+    section: Section,
+    kind: StatementKind<'a>,
+}
+
+/// Assembles RISC-V source in the syntax of `programs/asm` into a program image.
+///
+/// Supports labels, ABI register names, the common pseudo-instructions and the
+/// section and data directives emitted by compilers. Execution starts at `main`.
+pub fn assemble(source: &str) -> Result<Elf, AssemblerError> {
+    let mut assembler = Assembler::default();
+    for (index, line) in source.lines().enumerate() {
+        assembler.line = index + 1;
+        assembler
+            .parse_line(line)
+            .map_err(|error| AssemblerError::Line {
+                line: index + 1,
+                error,
+            })?;
+    }
+    assembler.finish()
+}
+
+/// Loads a program given on the command line: `.s` files are assembled, anything else is
+/// loaded as an ELF file.
+pub fn load_program(path: &str) -> Result<Elf, String> {
+    if path.ends_with(".s") {
+        let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        return assemble(&source).map_err(|error| error.to_string());
+    }
+    let input = std::fs::read(path).map_err(|error| error.to_string())?;
+    Elf::load(&input).map_err(|error| error.to_string())
+}
+
+struct Assembler<'a> {
+    statements: Vec<Statement<'a>>,
+    labels: HashMap<&'a str, (Section, u32)>,
+    section: Section,
+    sizes: [u32; 2],
+    line: usize,
+}
+
+impl Default for Assembler<'_> {
+    fn default() -> Self {
+        Assembler {
+            statements: Vec::new(),
+            labels: HashMap::new(),
+            section: Section::Text,
+            sizes: [0; 2],
+            line: 0,
+        }
+    }
+}
+
+impl<'a> Assembler<'a> {
+    fn size(&mut self) -> &mut u32 {
+        &mut self.sizes[self.section as usize]
+    }
+
+    fn push(&mut self, kind: StatementKind<'a>, size: u32) {
+        self.statements.push(Statement {
+            line: self.line,
+            section: self.section,
+            kind,
+        });
+        *self.size() += size;
+    }
+
+    fn parse_line(&mut self, line: &'a str) -> Result<(), LineError> {
+        let mut rest = strip_comment(line).trim();
+        while let Some((label, after)) = split_label(rest) {
+            let offset = *self.size();
+            if self.labels.insert(label, (self.section, offset)).is_some() {
+                return Err(LineError::DuplicateSymbol(label.to_string()));
+            }
+            rest = after.trim_start();
+        }
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let (mnemonic, operands) = rest
+            .split_once(char::is_whitespace)
+            .map(|(mnemonic, operands)| (mnemonic, operands.trim()))
+            .unwrap_or((rest, ""));
+        if mnemonic.starts_with('.') {
+            self.parse_directive(mnemonic, operands)?;
+        } else {
+            let operands = split_operands(operands);
+            let count = expand(mnemonic, &operands, 0, &|_| Some(0))?.len() as u32;
+            self.push(
+                StatementKind::Instruction { mnemonic, operands },
+                count * WORD_SIZE,
+            );
+        }
+        Ok(())
+    }
+
+    fn parse_directive(&mut self, directive: &'a str, operands: &'a str) -> Result<(), LineError> {
+        match directive {
+            ".text" => self.section = Section::Text,
+            ".data" | ".bss" | ".rodata" | ".sdata" | ".sbss" => self.section = Section::Data,
+            ".section" => {
+                let name = split_operands(operands).first().copied().unwrap_or("");
+                self.section = if name.starts_with(".text") {
+                    Section::Text
+                } else {
+                    Section::Data
+                };
+            }
+            ".globl" | ".global" | ".local" | ".weak" | ".attribute" | ".file" | ".size"
+            | ".type" | ".ident" | ".option" | ".addrsig" => {}
+            ".p2align" | ".align" | ".balign" => {
+                let operands = split_operands(operands);
+                let value = operands
+                    .first()
+                    .ok_or_else(|| LineError::InvalidOperands(directive.to_string()))
+                    .and_then(|value| parse_number(value))?;
+                let alignment = match directive {
+                    ".balign" => value,
+                    _ if (0..32).contains(&value) => 1 << value,
+                    _ => return Err(LineError::ValueOutOfRange(value)),
+                };
+                if alignment <= 0 || alignment > DATA_ALIGNMENT as i64 {
+                    return Err(LineError::ValueOutOfRange(alignment));
+                }
+                let alignment = alignment as u32;
+                let padding = self.size().next_multiple_of(alignment) - *self.size();
+                self.push(StatementKind::Padding(padding), padding);
+            }
+            ".word" | ".4byte" | ".long" | ".half" | ".2byte" | ".short" | ".byte" => {
+                let width = match directive {
+                    ".byte" => 1,
+                    ".half" | ".2byte" | ".short" => 2,
+                    _ => 4,
+                };
+                let values = split_operands(operands);
+                let size = width * values.len() as u32;
+                self.push(StatementKind::Data { width, values }, size);
+            }
+            ".zero" | ".space" => {
+                let size = parse_number(operands)?;
+                let size = u32::try_from(size).map_err(|_| LineError::ValueOutOfRange(size))?;
+                self.push(StatementKind::Bytes(vec![0; size as usize]), size);
+            }
+            ".ascii" | ".asciz" | ".string" => {
+                let mut bytes = Vec::new();
+                for string in split_operands(operands) {
+                    bytes.extend(parse_string(string)?);
+                    if directive != ".ascii" {
+                        bytes.push(0);
+                    }
+                }
+                let size = bytes.len() as u32;
+                self.push(StatementKind::Bytes(bytes), size);
+            }
+            ".insn" => {
+                let operands = match operands.split_once(char::is_whitespace) {
+                    Some(("r", operands)) => split_operands(operands.trim()),
+                    _ => return Err(LineError::InvalidOperands(directive.to_string())),
+                };
+                if operands.len() != 6 {
+                    return Err(LineError::InvalidOperands(directive.to_string()));
+                }
+                self.push(StatementKind::Insn { operands }, WORD_SIZE);
+            }
+            _ => return Err(LineError::UnknownDirective(directive.to_string())),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Elf, AssemblerError> {
+        let text_size = self.sizes[Section::Text as usize];
+        let data_start = (TEXT_START + text_size).next_multiple_of(DATA_ALIGNMENT);
+        let section_start = |section| match section {
+            Section::Text => TEXT_START,
+            Section::Data => data_start,
+        };
+        let symbols = |name: &str| {
+            self.labels
+                .get(name)
+                .map(|(section, offset)| (section_start(*section) + offset) as i64)
+        };
+        let mut contents = [Vec::new(), Vec::new()];
+        for statement in &self.statements {
+            let bytes = &mut contents[statement.section as usize];
+            let pc = section_start(statement.section) + bytes.len() as u32;
+            statement
+                .emit(pc, &symbols, bytes)
+                .map_err(|error| AssemblerError::Line {
+                    line: statement.line,
+                    error,
+                })?;
+        }
+
+        let entry_point = symbols(ENTRY_SYMBOL).ok_or(AssemblerError::MissingEntryPoint)? as u32;
+        let mut image = BTreeMap::new();
+        for section in [Section::Text, Section::Data] {
+            let start = section_start(section);
+            for (i, chunk) in contents[section as usize]
+                .chunks(WORD_SIZE as usize)
+                .enumerate()
+            {
+                let word = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |word, (j, byte)| word | (*byte as u32) << (j * 8));
+                image.insert(start + i as u32 * WORD_SIZE, word);
+            }
+        }
+        let mut symbol_table = BTreeMap::new();
+        let mut labels: Vec<_> = self.labels.keys().collect();
+        labels.sort();
+        for label in labels.into_iter().filter(|label| !label.starts_with(".L")) {
+            if let Some(addr) = symbols(label) {
+                symbol_table.entry(addr as u32).or_insert(label.to_string());
+            }
+        }
+        let code = TEXT_START..TEXT_START + text_size;
+        Ok(Elf {
+            entry_point,
+            image,
+            executable_ranges: vec![code],
+            symbols: symbol_table,
+        })
+    }
+}
+
+impl Statement<'_> {
+    fn emit(
+        &self,
+        pc: u32,
+        symbols: &dyn Fn(&str) -> Option<i64>,
+        bytes: &mut Vec<u8>,
+    ) -> Result<(), LineError> {
+        match &self.kind {
+            StatementKind::Instruction { mnemonic, operands } => {
+                for instruction in expand(mnemonic, operands, pc, symbols)? {
+                    bytes.extend(instruction.try_encode()?.to_le_bytes());
+                }
+            }
+            StatementKind::Insn { operands } => {
+                let field = |index: usize, limit: i64| {
+                    let value = parse_number(operands[index])?;
+                    if (0..limit).contains(&value) {
+                        Ok(value as u32)
+                    } else {
+                        Err(LineError::ValueOutOfRange(value))
+                    }
+                };
+                let word = field(2, 1 << 7)? << 25
+                    | parse_register(operands[5])? << 20
+                    | parse_register(operands[4])? << 15
+                    | field(1, 1 << 3)? << 12
+                    | parse_register(operands[3])? << 7
+                    | field(0, 1 << 7)?;
+                bytes.extend(word.to_le_bytes());
+            }
+            StatementKind::Data { width, values } => {
+                for value in values {
+                    let value = evaluate(value, symbols)?;
+                    let bits = width * 8;
+                    if value < -(1 << (bits - 1)) || value >= 1 << bits {
+                        return Err(LineError::ValueOutOfRange(value));
+                    }
+                    bytes.extend(&value.to_le_bytes()[..*width as usize]);
+                }
+            }
+            StatementKind::Bytes(data) => bytes.extend(data),
+            StatementKind::Padding(size) => {
+                // Code is padded with nops so that it stays decodable
+                if self.section == Section::Text && size % WORD_SIZE == 0 {
+                    let nop = NOP.encode().to_le_bytes();
+                    for _ in 0..size / WORD_SIZE {
+                        bytes.extend(nop);
+                    }
+                } else {
+                    bytes.extend(vec![0; *size as usize]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Expands a possibly pseudo instruction placed at `pc` into the instructions it stands for.
+// The number of instructions only depends on the source, so that the first pass can lay
+// out the program before symbols are known.
+fn expand(
+    mnemonic: &str,
+    operands: &[&str],
+    pc: u32,
+    symbols: &dyn Fn(&str) -> Option<i64>,
+) -> Result<Vec<Instruction>, LineError> {
+    let invalid = || LineError::InvalidOperands(mnemonic.to_string());
+    let reg = |index: usize| {
+        operands
+            .get(index)
+            .ok_or_else(invalid)
+            .and_then(|s| parse_register(s))
+    };
+    let imm = |index: usize| {
+        operands
+            .get(index)
+            .ok_or_else(invalid)
+            .and_then(|expr| evaluate(expr, symbols))
+            .and_then(to_i32)
+    };
+    let target = |index: usize| {
+        operands
+            .get(index)
+            .ok_or_else(invalid)
+            .and_then(|expr| branch_offset(expr, pc, symbols))
+    };
+    let memory = |index: usize| {
+        let operand = operands.get(index).ok_or_else(invalid)?;
+        let (offset, base) = operand
+            .strip_suffix(')')
+            .and_then(|operand| operand.rsplit_once('('))
+            .ok_or_else(invalid)?;
+        let offset = match offset.trim() {
+            "" => 0,
+            offset => to_i32(evaluate(offset, symbols)?)?,
+        };
+        Ok::<_, LineError>((offset, parse_register(base)?))
+    };
+    let expect = |count: usize| {
+        if operands.len() == count {
+            Ok(())
+        } else {
+            Err(invalid())
+        }
+    };
+
+    if let Some(op) = arith_op(mnemonic) {
+        expect(3)?;
+        return Ok(vec![Instruction::Arith {
+            dst: reg(0)?,
+            src1: reg(1)?,
+            src2: reg(2)?,
+            op,
+        }]);
+    }
+    if let Some(op) = arith_imm_op(mnemonic) {
+        expect(3)?;
+        return Ok(vec![Instruction::ArithImm {
+            dst: reg(0)?,
+            src: reg(1)?,
+            imm: imm(2)?,
+            op,
+        }]);
+    }
+    if let Some(width) = load_width(mnemonic) {
+        expect(2)?;
+        let (offset, base) = memory(1)?;
+        return Ok(vec![Instruction::Load {
+            dst: reg(0)?,
+            offset,
+            base,
+            width,
+        }]);
+    }
+    if let Some(width) = store_width(mnemonic) {
+        expect(2)?;
+        let (offset, base) = memory(1)?;
+        return Ok(vec![Instruction::Store {
+            src: reg(0)?,
+            offset,
+            base,
+            width,
+        }]);
+    }
+    if let Some((cond, swap)) = branch_condition(mnemonic) {
+        expect(3)?;
+        let (src1, src2) = if swap {
+            (reg(1)?, reg(0)?)
+        } else {
+            (reg(0)?, reg(1)?)
+        };
+        return Ok(vec![Instruction::Branch {
+            src1,
+            src2,
+            cond,
+            offset: target(2)?,
+        }]);
+    }
+    // Branches against zero: `beqz rs, target` and friends
+    if let Some((cond, swap)) = mnemonic.strip_suffix('z').and_then(branch_zero_condition) {
+        expect(2)?;
+        let (src1, src2) = if swap { (0, reg(0)?) } else { (reg(0)?, 0) };
+        return Ok(vec![Instruction::Branch {
+            src1,
+            src2,
+            cond,
+            offset: target(1)?,
+        }]);
+    }
+
+    let instructions = match (mnemonic, operands.len()) {
+        ("nop", 0) => vec![NOP],
+        ("ecall", 0) => vec![Instruction::EnvironmentCall],
+        ("ebreak", 0) => vec![Instruction::EnvironmentBreak],
+        ("lui", 2) => vec![Instruction::LoadUpperImm {
+            dst: reg(0)?,
+            imm: upper_immediate(imm(1)?)?,
+        }],
+        ("auipc", 2) => vec![Instruction::AddUpperImmToPc {
+            dst: reg(0)?,
+            imm: upper_immediate(imm(1)?)?,
+        }],
+        ("jal", 1) => vec![Instruction::JumpAndLink {
+            dst: 1,
+            offset: target(0)?,
+        }],
+        ("jal", 2) => vec![Instruction::JumpAndLink {
+            dst: reg(0)?,
+            offset: target(1)?,
+        }],
+        ("j", 1) => vec![Instruction::JumpAndLink {
+            dst: 0,
+            offset: target(0)?,
+        }],
+        ("jalr", 1) => vec![Instruction::JumpAndLinkRegister {
+            base: reg(0)?,
+            dst: 1,
+            offset: 0,
+        }],
+        ("jalr", 2) if operands[1].ends_with(')') => {
+            let (offset, base) = memory(1)?;
+            vec![Instruction::JumpAndLinkRegister {
+                base,
+                dst: reg(0)?,
+                offset,
+            }]
+        }
+        ("jalr", 3) => vec![Instruction::JumpAndLinkRegister {
+            base: reg(1)?,
+            dst: reg(0)?,
+            offset: imm(2)?,
+        }],
+        ("jr", 1) => vec![Instruction::JumpAndLinkRegister {
+            base: reg(0)?,
+            dst: 0,
+            offset: 0,
+        }],
+        ("ret", 0) => vec![Instruction::JumpAndLinkRegister {
+            base: 1,
+            dst: 0,
+            offset: 0,
+        }],
+        ("mv", 2) => vec![Instruction::ArithImm {
+            dst: reg(0)?,
+            src: reg(1)?,
+            imm: 0,
+            op: ArithOp::Add,
+        }],
+        ("not", 2) => vec![Instruction::ArithImm {
+            dst: reg(0)?,
+            src: reg(1)?,
+            imm: -1,
+            op: ArithOp::Xor,
+        }],
+        ("seqz", 2) => vec![Instruction::ArithImm {
+            dst: reg(0)?,
+            src: reg(1)?,
+            imm: 1,
+            op: ArithOp::SetLessThanU,
+        }],
+        ("neg" | "snez" | "sgtz", 2) => {
+            let op = match mnemonic {
+                "neg" => ArithOp::Sub,
+                "snez" => ArithOp::SetLessThanU,
+                _ => ArithOp::SetLessThan,
+            };
+            vec![Instruction::Arith {
+                dst: reg(0)?,
+                src1: 0,
+                src2: reg(1)?,
+                op,
+            }]
+        }
+        ("sltz", 2) => vec![Instruction::Arith {
+            dst: reg(0)?,
+            src1: reg(1)?,
+            src2: 0,
+            op: ArithOp::SetLessThan,
+        }],
+        ("li", 2) => {
+            let dst = reg(0)?;
+            // Values given by symbols always take two instructions, their size must not
+            // depend on the layout
+            let symbolic = evaluate(operands[1], &|_| None).is_err();
+            let value = imm(1)?;
+            if !symbolic && (-2048..2048).contains(&value) {
+                vec![Instruction::ArithImm {
+                    dst,
+                    src: 0,
+                    imm: value,
+                    op: ArithOp::Add,
+                }]
+            } else {
+                let (upper, lower) = split_immediate(value);
+                let mut instructions = vec![Instruction::LoadUpperImm { dst, imm: upper }];
+                if symbolic || lower != 0 {
+                    instructions.push(Instruction::ArithImm {
+                        dst,
+                        src: dst,
+                        imm: lower,
+                        op: ArithOp::Add,
+                    });
+                }
+                instructions
+            }
+        }
+        ("la", 2) => {
+            let dst = reg(0)?;
+            let offset = to_i32(evaluate(operands[1], symbols)? - pc as i64)?;
+            let (upper, lower) = split_immediate(offset);
+            vec![
+                Instruction::AddUpperImmToPc { dst, imm: upper },
+                Instruction::ArithImm {
+                    dst,
+                    src: dst,
+                    imm: lower,
+                    op: ArithOp::Add,
+                },
+            ]
+        }
+        ("call" | "tail", 1) => {
+            // `call` links through ra, `tail` jumps through t1 without linking
+            let (scratch, dst) = if mnemonic == "call" { (1, 1) } else { (6, 0) };
+            let offset = to_i32(evaluate(operands[0], symbols)? - pc as i64)?;
+            let (upper, lower) = split_immediate(offset);
+            vec![
+                Instruction::AddUpperImmToPc {
+                    dst: scratch,
+                    imm: upper,
+                },
+                Instruction::JumpAndLinkRegister {
+                    base: scratch,
+                    dst,
+                    offset: lower,
+                },
+            ]
+        }
+        _ if is_mnemonic(mnemonic) => return Err(invalid()),
+        _ => return Err(LineError::UnknownInstruction(mnemonic.to_string())),
+    };
+    Ok(instructions)
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    [
+        "nop", "ecall", "ebreak", "lui", "auipc", "jal", "j", "jalr", "jr", "ret", "mv", "not",
+        "seqz", "neg", "snez", "sgtz", "sltz", "li", "la", "call", "tail",
+    ]
+    .contains(&mnemonic)
+}
+
+fn arith_op(mnemonic: &str) -> Option<ArithOp> {
+    Some(match mnemonic {
+        "add" => ArithOp::Add,
+        "sub" => ArithOp::Sub,
+        "xor" => ArithOp::Xor,
+        "or" => ArithOp::Or,
+        "and" => ArithOp::And,
+        "sll" => ArithOp::ShiftLeftLogical,
+        "srl" => ArithOp::ShiftRightLogical,
+        "sra" => ArithOp::ShiftRightArith,
+        "slt" => ArithOp::SetLessThan,
+        "sltu" => ArithOp::SetLessThanU,
+        "mul" => ArithOp::Mul,
+        "mulh" => ArithOp::MulHigh,
+        "mulhsu" => ArithOp::MulHighSU,
+        "mulhu" => ArithOp::MulHighU,
+        "div" => ArithOp::Div,
+        "divu" => ArithOp::DivU,
+        "rem" => ArithOp::Rem,
+        "remu" => ArithOp::RemU,
+        _ => return None,
+    })
+}
+
+fn arith_imm_op(mnemonic: &str) -> Option<ArithOp> {
+    match mnemonic {
+        "sltiu" => Some(ArithOp::SetLessThanU),
+        _ => arith_op(mnemonic.strip_suffix('i')?).filter(ArithOp::has_immediate_form),
+    }
+}
+
+fn load_width(mnemonic: &str) -> Option<LoadStoreWidth> {
+    Some(match mnemonic.strip_prefix('l')? {
+        "bu" => LoadStoreWidth::ByteUnsigned,
+        "hu" => LoadStoreWidth::HalfUnsigned,
+        suffix => return width(suffix),
+    })
+}
+
+fn store_width(mnemonic: &str) -> Option<LoadStoreWidth> {
+    width(mnemonic.strip_prefix('s')?)
+}
+
+fn width(suffix: &str) -> Option<LoadStoreWidth> {
+    Some(match suffix {
+        "b" => LoadStoreWidth::Byte,
+        "h" => LoadStoreWidth::Half,
+        "w" => LoadStoreWidth::Word,
+        _ => return None,
+    })
+}
+
+// Condition of a branch mnemonic and whether its operands are swapped
+fn branch_condition(mnemonic: &str) -> Option<(Comparison, bool)> {
+    Some(match mnemonic {
+        "beq" => (Comparison::Equal, false),
+        "bne" => (Comparison::NotEqual, false),
+        "blt" => (Comparison::LessThan, false),
+        "bge" => (Comparison::GreaterOrEqual, false),
+        "bltu" => (Comparison::LessThanUnsigned, false),
+        "bgeu" => (Comparison::GreaterOrEqualUnsigned, false),
+        "bgt" => (Comparison::LessThan, true),
+        "ble" => (Comparison::GreaterOrEqual, true),
+        "bgtu" => (Comparison::LessThanUnsigned, true),
+        "bleu" => (Comparison::GreaterOrEqualUnsigned, true),
+        _ => return None,
+    })
+}
+
+// Same for branches against zero, where swapping puts zero first
+fn branch_zero_condition(mnemonic: &str) -> Option<(Comparison, bool)> {
+    Some(match mnemonic {
+        "beq" => (Comparison::Equal, false),
+        "bne" => (Comparison::NotEqual, false),
+        "blt" => (Comparison::LessThan, false),
+        "bge" => (Comparison::GreaterOrEqual, false),
+        "bgt" => (Comparison::LessThan, true),
+        "ble" => (Comparison::GreaterOrEqual, true),
+        _ => return None,
+    })
+}
+
+fn parse_register(name: &str) -> Result<u32, LineError> {
+    let name = name.trim();
+    register_index(name).ok_or_else(|| LineError::InvalidRegister(name.to_string()))
+}
+
+// Jump and branch targets given as plain numbers are offsets from the pc, like in llvm-mc
+fn branch_offset(
+    expr: &str,
+    pc: u32,
+    symbols: &dyn Fn(&str) -> Option<i64>,
+) -> Result<i32, LineError> {
+    match evaluate(expr, &|_| None) {
+        Ok(offset) => to_i32(offset),
+        Err(_) => to_i32(evaluate(expr, symbols)? - pc as i64),
+    }
+}
+
+// Splits a value into the upper part for lui/auipc and the signed lower 12 bits
+fn split_immediate(value: i32) -> (u32, i32) {
+    let upper = (value as u32).wrapping_add(0x800) & 0xffff_f000;
+    (upper, value.wrapping_sub(upper as i32))
+}
+
+// lui and auipc take the 20 upper bits as operand
+fn upper_immediate(value: i32) -> Result<u32, LineError> {
+    if (-(1 << 19)..1 << 20).contains(&value) {
+        Ok((value as u32) << 12)
+    } else {
+        Err(LineError::ValueOutOfRange(value as i64))
+    }
+}
+
+// Accepts any value representable in 32 bits, so that `0xffffffff` stands for -1
+fn to_i32(value: i64) -> Result<i32, LineError> {
+    if (i32::MIN as i64..=u32::MAX as i64).contains(&value) {
+        Ok(value as u32 as i32)
+    } else {
+        Err(LineError::ValueOutOfRange(value))
+    }
+}
+
+// Sums and differences of numbers and symbols, optionally wrapped in `%hi` or `%lo`
+fn evaluate(expr: &str, symbols: &dyn Fn(&str) -> Option<i64>) -> Result<i64, LineError> {
+    let expr = expr.trim();
+    let invalid = || LineError::InvalidExpression(expr.to_string());
+    for (modifier, lower) in [("%hi(", false), ("%lo(", true)] {
+        if let Some(inner) = expr.strip_prefix(modifier) {
+            let inner = inner.strip_suffix(')').ok_or_else(invalid)?;
+            let (upper, low) = split_immediate(to_i32(evaluate(inner, symbols)?)?);
+            return Ok(if lower {
+                low as i64
+            } else {
+                (upper >> 12) as i64
+            });
+        }
+    }
+    let mut total: i64 = 0;
+    let mut rest = expr;
+    let mut negative = false;
+    if let Some(after) = rest.strip_prefix('-') {
+        negative = true;
+        rest = after;
+    }
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = rest[..end].trim();
+        if term.is_empty() {
+            return Err(invalid());
+        }
+        let value = match parse_number(term) {
+            Ok(value) => value,
+            Err(_) if is_symbol(term) => {
+                symbols(term).ok_or_else(|| LineError::UndefinedSymbol(term.to_string()))?
+            }
+            Err(_) => return Err(invalid()),
+        };
+        total = if negative {
+            total.checked_sub(value)
+        } else {
+            total.checked_add(value)
+        }
+        .ok_or_else(invalid)?;
+        let Some(operator) = rest[end..].chars().next() else {
+            return Ok(total);
+        };
+        negative = operator == '-';
+        rest = &rest[end + 1..];
+    }
+}
+
+fn parse_number(text: &str) -> Result<i64, LineError> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        digits.parse()
+    }
+    .map_err(|_| LineError::InvalidExpression(text.to_string()))?;
+    Ok(if negative { -value } else { value })
+}
+
+fn parse_string(text: &str) -> Result<Vec<u8>, LineError> {
+    let invalid = || LineError::InvalidExpression(text.to_string());
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next().ok_or_else(invalid)? {
+                'n' => '\n',
+                't' => '\t',
+                '0' => '\0',
+                c @ ('\\' | '"') => c,
+                _ => return Err(invalid()),
+            },
+            c => c,
+        };
+        let mut buffer = [0; 4];
+        bytes.extend(c.encode_utf8(&mut buffer).as_bytes());
+    }
+    Ok(bytes)
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    is_symbol(label).then_some((label, rest))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Splits on commas outside of strings
+fn split_operands(operands: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut in_string = false;
+    let mut start = 0;
+    for (i, c) in operands.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                result.push(operands[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = operands[start..].trim();
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+    result
+}
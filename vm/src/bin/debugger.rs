@@ -0,0 +1,61 @@
+// Interactive debugger for guest programs, see `vm::debugger` for the commands. With the
+// `debug-info` feature, addresses are shown with their function and source line.
+//
+// Usage: debugger <elf file or assembly source>
+
+use std::{
+    io::{BufRead, Write},
+    process::ExitCode,
+};
+
+#[cfg(feature = "debug-info")]
+use vm::debug_info::load_debug_info;
+use vm::{assembler::load_program, debugger::Debugger};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [path] = args.as_slice() else {
+        eprintln!("Usage: debugger <elf file or assembly source>");
+        return ExitCode::FAILURE;
+    };
+    let program = match load_program(path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "{path}: entry point 0x{:08x}, type help for the commands",
+        program.entry_point
+    );
+    let debugger = Debugger::new(program);
+    #[cfg(feature = "debug-info")]
+    let debugger = match load_debug_info(path) {
+        Ok(Some(debug_info)) => debugger.with_debug_info(debug_info),
+        Ok(None) => debugger,
+        Err(error) => {
+            eprintln!("{path}: no debug information: {error}");
+            debugger
+        }
+    };
+    let mut debugger = debugger;
+    let mut stdout = std::io::stdout().lock();
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        let _ = write!(stdout, "(debugger) ").and_then(|()| stdout.flush());
+        // The session ends at the end of the input too
+        let Some(Ok(line)) = lines.next() else {
+            let _ = writeln!(stdout);
+            return ExitCode::SUCCESS;
+        };
+        match debugger.execute(&line, &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => return ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// Converts a binary trace file to CSV or JSON lines, one line per record. With the `debug-info`
+// feature and the ELF file the trace is of, steps get a `source` field with the function and
+// source line of their pc.
+//
+// Usage: dump-trace <trace file> [csv|jsonl] [elf file]
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    process::ExitCode,
+};
+
+use vm::vm::trace_file::{Record, TraceFileError, TraceReader};
+
+const CSV_HEADER: &str = "record,cycle,pc,instruction,rs1_value,rs2_value,rd_value,timestamp,addr,value,old_value,is_write";
+
+// The `source` field of a step from its pc
+type Describe = Box<dyn Fn(u32) -> String>;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, format, describe): (_, _, Option<Describe>) = match args.as_slice() {
+        [path] => (path, Some(Format::Csv), None),
+        [path, format] => (path, parse_format(format), None),
+        #[cfg(feature = "debug-info")]
+        [path, format, program] => match describer(program) {
+            Ok(describe) => (path, parse_format(format), Some(describe)),
+            Err(error) => {
+                eprintln!("{program}: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => return usage(),
+    };
+    let Some(format) = format else {
+        return usage();
+    };
+    match dump(path, format, describe) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: dump-trace <trace file> [csv|jsonl] [elf file, with debug-info]");
+    ExitCode::FAILURE
+}
+
+fn parse_format(format: &str) -> Option<Format> {
+    match format {
+        "csv" => Some(Format::Csv),
+        "jsonl" => Some(Format::JsonLines),
+        _ => None,
+    }
+}
+
+// The function and source line of a pc, from the debug information of `program`
+#[cfg(feature = "debug-info")]
+fn describer(program: &str) -> Result<Describe, String> {
+    let debug_info = vm::debug_info::load_debug_info(program)?.unwrap_or_default();
+    Ok(Box::new(move |pc| {
+        debug_info.describe(pc).unwrap_or_default()
+    }))
+}
+
+// Steps have no `source` field without `describe`
+fn dump(path: &str, format: Format, describe: Option<Describe>) -> Result<(), TraceFileError> {
+    let reader = TraceReader::new(BufReader::new(File::open(path)?))?;
+    let mut output = BufWriter::new(std::io::stdout().lock());
+    if let Format::Csv = format {
+        let source = if describe.is_some() { ",source" } else { "" };
+        writeln!(output, "{CSV_HEADER}{source}")?;
+    }
+    // Quotes are doubled inside a quoted CSV field
+    let source = |pc, format| match (&describe, format) {
+        (Some(describe), Format::Csv) => format!(",\"{}\"", describe(pc).replace('"', "\"\"")),
+        (Some(describe), Format::JsonLines) => {
+            format!(",\"source\":\"{}\"", json_escape(&describe(pc)))
+        }
+        (None, _) => String::new(),
+    };
+    let mut cycle = 0;
+    for record in reader {
+        let record = record?;
+        match (record, format) {
+            (Record::Step(step), Format::Csv) => writeln!(
+                output,
+                "step,{cycle},0x{:08x},\"{}\",0x{:08x},0x{:08x},0x{:08x},,,,,{}",
+                step.pc,
+                step.instruction,
+                step.rs1_value,
+                step.rs2_value,
+                step.rd_value,
+                source(step.pc, format)
+            )?,
+            (Record::Step(step), Format::JsonLines) => writeln!(
+                output,
+                "{{\"record\":\"step\",\"cycle\":{cycle},\"pc\":{},\"instruction\":\"{}\",\
+                 \"rs1_value\":{},\"rs2_value\":{},\"rd_value\":{}{}}}",
+                step.pc,
+                step.instruction,
+                step.rs1_value,
+                step.rs2_value,
+                step.rd_value,
+                source(step.pc, format)
+            )?,
+            (Record::MemoryAccess(access), Format::Csv) => writeln!(
+                output,
+                "memory,{},,,,,,{},0x{:08x},0x{:08x},0x{:08x},{}{}",
+                access.cycle,
+                access.timestamp,
+                access.addr,
+                access.value,
+                access.old_value,
+                access.is_write,
+                if describe.is_some() { "," } else { "" }
+            )?,
+            (Record::MemoryAccess(access), Format::JsonLines) => writeln!(
+                output,
+                "{{\"record\":\"memory\",\"cycle\":{},\"timestamp\":{},\"addr\":{},\"value\":{},\
+                 \"old_value\":{},\"is_write\":{}}}",
+                access.cycle,
+                access.timestamp,
+                access.addr,
+                access.value,
+                access.old_value,
+                access.is_write
+            )?,
+        }
+        if let Record::Step(_) = record {
+            cycle += 1;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+// Source paths may hold quotes, backslashes or control characters
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
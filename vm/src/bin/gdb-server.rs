@@ -0,0 +1,55 @@
+// GDB remote protocol server for guest programs, see `vm::gdb`. Listens on 127.0.0.1 on the
+// given port, 1234 by default, or talks over stdin and stdout for `target remote | ...`.
+//
+// Usage: gdb-server <elf file or assembly source> [port or stdio]
+
+use std::{net::TcpListener, process::ExitCode};
+
+use vm::{assembler::load_program, gdb::GdbStub, vm::execution::Executor};
+
+const DEFAULT_PORT: &str = "1234";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, connection) = match args.as_slice() {
+        [path] => (path, DEFAULT_PORT),
+        [path, connection] => (path, connection.as_str()),
+        _ => {
+            eprintln!("Usage: gdb-server <elf file or assembly source> [port or stdio]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let program = match load_program(path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut stub = GdbStub::new(Executor::new(program.image, program.entry_point));
+    let result = match connection {
+        "stdio" => stub.serve(std::io::stdin(), std::io::stdout().lock()),
+        port => serve_tcp(&mut stub, port),
+    };
+    if let Err(error) = result {
+        eprintln!("{error}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+// Serves the first connection
+fn serve_tcp(stub: &mut GdbStub, port: &str) -> std::io::Result<()> {
+    let port: u16 = port.parse().map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid port {port}"),
+        )
+    })?;
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let addr = listener.local_addr()?;
+    eprintln!("Listening on {addr}, connect with: target remote {addr}");
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    stub.serve(stream.try_clone()?, &stream)
+}
@@ -0,0 +1,92 @@
+// Runs a guest program and reports where its cycles went, see `vm::vm::profiler`. Prints the
+// functions with the most cycles, 20 by default, the call paths in the folded format of
+// flamegraph tools, e.g. `profile program.elf folded | flamegraph.pl > profile.svg`, or the
+// instruction mix, see `vm::vm::statistics`. With the `debug-info` feature, functions are named
+// by the debug information of the program. An error is reported with the calls it happened in.
+//
+// Usage: profile <elf file or assembly source> [top [count]|folded|mix]
+
+use std::process::ExitCode;
+
+use vm::{
+    assembler::load_program,
+    vm::{
+        execution::Executor,
+        profiler::{Profiler, function_name},
+    },
+};
+#[cfg(feature = "debug-info")]
+use vm::{debug_info::load_debug_info, vm::profiler::function_name_with_debug_info};
+
+const DEFAULT_TOP: usize = 20;
+
+enum Report {
+    Top(usize),
+    Folded,
+    Mix,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, report) = match args.as_slice() {
+        [path] => (path, Report::Top(DEFAULT_TOP)),
+        [path, report] if report == "top" => (path, Report::Top(DEFAULT_TOP)),
+        [path, report, count] if report == "top" => match count.parse() {
+            Ok(count) => (path, Report::Top(count)),
+            Err(_) => return usage(),
+        },
+        [path, report] if report == "folded" => (path, Report::Folded),
+        [path, report] if report == "mix" => (path, Report::Mix),
+        _ => return usage(),
+    };
+    let program = match load_program(path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut executor = Executor::new(program.image.clone(), program.entry_point).with_statistics();
+    let mut profiler = Profiler::new(program.entry_point);
+    // What ran before an error is still worth seeing
+    let result = executor.run_with(&mut profiler);
+    #[cfg(feature = "debug-info")]
+    let debug_info = match load_debug_info(path) {
+        Ok(debug_info) => debug_info,
+        Err(error) => {
+            eprintln!("{path}: no debug information: {error}");
+            None
+        }
+    };
+    let name = |addr| {
+        #[cfg(feature = "debug-info")]
+        if let Some(debug_info) = &debug_info {
+            return function_name_with_debug_info(&program, debug_info, addr);
+        }
+        function_name(&program, addr)
+    };
+    match report {
+        Report::Top(count) => print!("{}", profiler.top(count, name)),
+        Report::Folded => print!("{}", profiler.folded(name)),
+        Report::Mix => {
+            if let Some(mix) = executor.statistics() {
+                print!("{mix}");
+            }
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            for function in profiler.call_stack() {
+                eprintln!("    in {}", name(function));
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: profile <elf file or assembly source> [top [count]|folded|mix]");
+    ExitCode::FAILURE
+}
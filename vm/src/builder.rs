@@ -0,0 +1,123 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    elf::{Elf, WORD_SIZE},
+    vm::instructions::{EncodeError, Instruction},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("Block address 0x{0:08x} is unaligned")]
+    UnalignedAddress(u32),
+    // Or, for code, reaches the end of memory, see `ProgramBuilder::code`
+    #[error("Block at 0x{0:08x} runs past the end of memory")]
+    AddressOverflow(u32),
+    #[error("Address 0x{0:08x} is written by more than one block")]
+    Overlap(u32),
+    #[error("Instruction at 0x{addr:08x} cannot be encoded: {error}")]
+    Encode { addr: u32, error: EncodeError },
+    #[error("No entry point was set")]
+    MissingEntryPoint,
+    #[error("Entry point 0x{0:08x} is not in a code block")]
+    InvalidEntryPoint(u32),
+}
+
+enum Block {
+    Code(Vec<Instruction>),
+    Data(Vec<u32>),
+}
+
+/// Builds a program image in memory from instructions and data blocks placed at chosen
+/// addresses, producing the same structure as `Elf::load`.
+#[derive(Default)]
+pub struct ProgramBuilder {
+    blocks: Vec<(u32, Block)>,
+    symbols: BTreeMap<u32, String>,
+    entry_point: Option<u32>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> ProgramBuilder {
+        ProgramBuilder::default()
+    }
+
+    /// Places `instructions` starting at `addr`. Code ranges are exclusive at the end, like
+    /// `Elf::executable_ranges`, so code must end below 2^32: a block whose last instruction is
+    /// at 0xfffffffc fails with `AddressOverflow`. Data has no such limit.
+    pub fn code(mut self, addr: u32, instructions: &[Instruction]) -> ProgramBuilder {
+        self.blocks.push((addr, Block::Code(instructions.to_vec())));
+        self
+    }
+
+    /// Places `words` starting at `addr`.
+    pub fn data(mut self, addr: u32, words: &[u32]) -> ProgramBuilder {
+        self.blocks.push((addr, Block::Data(words.to_vec())));
+        self
+    }
+
+    /// Names `addr`, for the disassembler and symbolic lookups.
+    pub fn symbol(mut self, addr: u32, name: &str) -> ProgramBuilder {
+        self.symbols.insert(addr, name.to_string());
+        self
+    }
+
+    pub fn entry_point(mut self, addr: u32) -> ProgramBuilder {
+        self.entry_point = Some(addr);
+        self
+    }
+
+    pub fn build(self) -> Result<Elf, BuildError> {
+        let mut image = BTreeMap::new();
+        let mut executable_ranges: Vec<Range<u32>> = Vec::new();
+        for (start, block) in self.blocks {
+            if !start.is_multiple_of(WORD_SIZE) {
+                return Err(BuildError::UnalignedAddress(start));
+            }
+            let words = match block {
+                Block::Code(instructions) => {
+                    let end = u32::try_from(instructions.len())
+                        .ok()
+                        .and_then(|len| len.checked_mul(WORD_SIZE))
+                        .and_then(|size| start.checked_add(size))
+                        .ok_or(BuildError::AddressOverflow(start))?;
+                    let words = instructions
+                        .iter()
+                        .zip((start..end).step_by(WORD_SIZE as usize))
+                        .map(|(instruction, addr)| {
+                            instruction
+                                .try_encode()
+                                .map_err(|error| BuildError::Encode { addr, error })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    executable_ranges.push(start..end);
+                    words
+                }
+                Block::Data(words) => words,
+            };
+            for (i, word) in words.into_iter().enumerate() {
+                let addr = (i as u32)
+                    .checked_mul(WORD_SIZE)
+                    .and_then(|offset| start.checked_add(offset))
+                    .ok_or(BuildError::AddressOverflow(start))?;
+                if image.insert(addr, word).is_some() {
+                    return Err(BuildError::Overlap(addr));
+                }
+            }
+        }
+        let entry_point = self.entry_point.ok_or(BuildError::MissingEntryPoint)?;
+        if !entry_point.is_multiple_of(WORD_SIZE)
+            || !executable_ranges
+                .iter()
+                .any(|range| range.contains(&entry_point))
+        {
+            return Err(BuildError::InvalidEntryPoint(entry_point));
+        }
+        executable_ranges.sort_by_key(|range| range.start);
+        Ok(Elf {
+            entry_point,
+            image,
+            executable_ranges,
+            symbols: self.symbols,
+        })
+    }
+}
@@ -0,0 +1,212 @@
+// Function names and source lines of a program, from the ELF symbol table and the DWARF line
+// tables, so that addresses can be shown as the source names them. Needs the `debug-info`
+// feature.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use elf::{ElfBytes, abi::STT_FUNC, endian::LittleEndian};
+use gimli::{Dwarf, EndianSlice, SectionId};
+use hashbrown::HashMap;
+
+type Section<'a> = EndianSlice<'a, gimli::LittleEndian>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DebugInfoError {
+    #[error(transparent)]
+    Elf(#[from] elf::ParseError),
+    #[error("Invalid DWARF: {0}")]
+    Dwarf(#[from] gimli::Error),
+    #[error("Section {0} is compressed")]
+    CompressedSection(&'static str),
+}
+
+/// A line of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+    pub file: &'a str,
+    pub line: u32,
+}
+
+impl Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug)]
+struct Function {
+    // Symbols of size 0 extend to the next function
+    end: Option<u32>,
+    name: String,
+}
+
+#[derive(Default, Debug)]
+pub struct DebugInfo {
+    // By start address, names demangled
+    functions: BTreeMap<u32, Function>,
+    // Rows of the line tables by address, as an index into `files` and a line. `None` marks
+    // the end of a sequence.
+    lines: BTreeMap<u32, Option<(usize, u32)>>,
+    files: Vec<String>,
+}
+
+impl DebugInfo {
+    /// Reads the function symbols and line tables of an ELF file. Either may be missing, a
+    /// stripped file has no debug information at all.
+    pub fn load(input: &[u8]) -> Result<DebugInfo, DebugInfoError> {
+        let elf = ElfBytes::<LittleEndian>::minimal_parse(input)?;
+        let mut debug_info = DebugInfo::default();
+        debug_info.load_functions(&elf)?;
+        debug_info.load_lines(&elf)?;
+        Ok(debug_info)
+    }
+
+    /// The function `addr` is in, and the offset of `addr` into it.
+    pub fn function(&self, addr: u32) -> Option<(&str, u32)> {
+        let (start, function) = self.functions.range(..=addr).next_back()?;
+        function
+            .end
+            .is_none_or(|end| addr < end)
+            .then(|| (function.name.as_str(), addr - start))
+    }
+
+    /// The source line the instruction at `addr` was compiled from.
+    pub fn location(&self, addr: u32) -> Option<SourceLocation<'_>> {
+        let (_, row) = self.lines.range(..=addr).next_back()?;
+        // Line 0 is code that no line is responsible for
+        let (file, line) = row.filter(|(_, line)| *line != 0)?;
+        Some(SourceLocation {
+            file: &self.files[file],
+            line,
+        })
+    }
+
+    /// `<function>` or `<function+0xoffset>`, like `disassembler::symbolize` with the
+    /// demangled function names.
+    pub fn symbolize(&self, addr: u32) -> Option<String> {
+        self.function(addr).map(|(name, offset)| match offset {
+            0 => format!("<{name}>"),
+            offset => format!("<{name}+0x{offset:x}>"),
+        })
+    }
+
+    /// `<function+0xoffset> at file:line`, or as much of it as is known.
+    pub fn describe(&self, addr: u32) -> Option<String> {
+        match (self.symbolize(addr), self.location(addr)) {
+            (Some(function), Some(location)) => Some(format!("{function} at {location}")),
+            (Some(function), None) => Some(function),
+            (None, Some(location)) => Some(format!("at {location}")),
+            (None, None) => None,
+        }
+    }
+
+    fn load_functions(&mut self, elf: &ElfBytes<LittleEndian>) -> Result<(), DebugInfoError> {
+        let Some((symbol_table, string_table)) = elf.symbol_table()? else {
+            return Ok(());
+        };
+        for symbol in symbol_table.iter() {
+            if symbol.st_symtype() != STT_FUNC || symbol.st_name == 0 {
+                continue;
+            }
+            let (Ok(start), Ok(size)) = (
+                u32::try_from(symbol.st_value),
+                u32::try_from(symbol.st_size),
+            ) else {
+                continue;
+            };
+            let name = string_table.get(symbol.st_name as usize)?;
+            self.functions.insert(
+                start,
+                Function {
+                    end: (size != 0).then(|| start.saturating_add(size)),
+                    name: format!("{:#}", rustc_demangle::demangle(name)),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn load_lines(&mut self, elf: &ElfBytes<LittleEndian>) -> Result<(), DebugInfoError> {
+        let dwarf = Dwarf::load(|id| section(elf, id))?;
+        let mut files = HashMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                let Ok(addr) = u32::try_from(row.address()) else {
+                    continue;
+                };
+                // A sequence may start where another ends
+                if row.end_sequence() {
+                    self.lines.entry(addr).or_insert(None);
+                    continue;
+                }
+                let Some(file) = row.file(header) else {
+                    continue;
+                };
+                // Relative to the file's directory, which may be relative to the unit's.
+                // Directory 0 is the unit's.
+                let mut path = dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy()
+                    .into_owned();
+                if let Some(directory) = file.directory(header) {
+                    path = join(
+                        &dwarf.attr_string(&unit, directory)?.to_string_lossy(),
+                        path,
+                    );
+                }
+                if let Some(directory) = unit.comp_dir
+                    && file.directory_index() != 0
+                {
+                    path = join(&directory.to_string_lossy(), path);
+                }
+                let file = *files.entry(path).or_insert_with_key(|path| {
+                    self.files.push(path.clone());
+                    self.files.len() - 1
+                });
+                let line = row.line().map_or(0, |line| line.get() as u32);
+                self.lines.insert(addr, Some((file, line)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The debug information of a program given on the command line, see
+/// `assembler::load_program`. Assembly sources have none.
+pub fn load_debug_info(path: &str) -> Result<Option<DebugInfo>, String> {
+    if path.ends_with(".s") {
+        return Ok(None);
+    }
+    let input = std::fs::read(path).map_err(|error| error.to_string())?;
+    DebugInfo::load(&input)
+        .map(Some)
+        .map_err(|error| error.to_string())
+}
+
+// Missing sections read as empty
+fn section<'a>(
+    elf: &ElfBytes<'a, LittleEndian>,
+    id: SectionId,
+) -> Result<Section<'a>, DebugInfoError> {
+    let data = match elf.section_header_by_name(id.name())? {
+        Some(header) => match elf.section_data(&header)? {
+            (data, None) => data,
+            (_, Some(_)) => return Err(DebugInfoError::CompressedSection(id.name())),
+        },
+        None => &[],
+    };
+    Ok(EndianSlice::new(data, gimli::LittleEndian))
+}
+
+fn join(directory: &str, path: String) -> String {
+    if path.starts_with('/') || directory.is_empty() {
+        return path;
+    }
+    format!("{}/{path}", directory.trim_end_matches('/'))
+}
@@ -0,0 +1,540 @@
+// Commands of the interactive debugger, `src/bin/debugger.rs`. Each line is one command,
+// whose output is written to the given writer, so sessions can be scripted and tested.
+
+use std::io::Write;
+
+#[cfg(feature = "debug-info")]
+use crate::debug_info::DebugInfo;
+use crate::{
+    elf::{Elf, WORD_SIZE},
+    vm::{
+        disassembler::symbolize,
+        execution::{ExecutionError, Executor, Watch},
+        history::{History, SNAPSHOT_INTERVAL},
+        instructions::Instruction,
+        observer::UninitializedReads,
+        registers::{A0, A1, register_index},
+    },
+};
+
+// Name, alias, arguments and description of every command, for help and usage errors
+const COMMANDS: [(&str, &str, &str, &str); 18] = [
+    (
+        "break",
+        "b",
+        "<location>",
+        "Stop before the instruction at an address or symbol",
+    ),
+    ("delete", "", "<location>", "Remove a breakpoint"),
+    ("breakpoints", "", "", "List the breakpoints"),
+    (
+        "watch",
+        "",
+        "<addr> [read|write|access]",
+        "Stop after the word at addr is written, or as given",
+    ),
+    ("unwatch", "", "<addr>", "Remove a watchpoint"),
+    ("watchpoints", "", "", "List the watchpoints"),
+    (
+        "step",
+        "s",
+        "[count]",
+        "Execute count instructions, 1 by default",
+    ),
+    (
+        "continue",
+        "c",
+        "",
+        "Run until a breakpoint, an ebreak or the end",
+    ),
+    (
+        "reverse-step",
+        "rs",
+        "[count]",
+        "Go back count instructions, 1 by default",
+    ),
+    (
+        "reverse-continue",
+        "rc",
+        "",
+        "Go back to the last breakpoint or the start",
+    ),
+    (
+        "last-write",
+        "",
+        "<addr>",
+        "Show the instruction that last wrote the word at addr",
+    ),
+    (
+        "registers",
+        "regs",
+        "",
+        "Show pc, the cycle count and all registers",
+    ),
+    (
+        "register",
+        "reg",
+        "<name> [value]",
+        "Show or set a register or pc",
+    ),
+    (
+        "memory",
+        "mem",
+        "<addr> [count]",
+        "Show count words, 4 by default",
+    ),
+    ("write", "", "<addr> <value>", "Set the word at addr"),
+    (
+        "disassemble",
+        "disas",
+        "[location] [count]",
+        "Show instructions around location or pc",
+    ),
+    (
+        "help",
+        "h",
+        "",
+        "Show this list, an empty line repeats the last command",
+    ),
+    ("quit", "q", "", "Exit"),
+];
+
+// Instructions shown by `disassemble` without a count
+const DISASSEMBLY_LINES: u32 = 9;
+const MEMORY_WORDS: u32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("Unknown command {0}, try help")]
+    UnknownCommand(String),
+    #[error("Usage: {0}")]
+    Usage(String),
+    #[error("{0} is neither an address nor a symbol")]
+    InvalidLocation(String),
+    #[error("Invalid value {0}")]
+    InvalidValue(String),
+    #[error("Unknown register {0}")]
+    UnknownRegister(String),
+    #[error("No breakpoint at 0x{0:08x}")]
+    NoBreakpoint(u32),
+    #[error("No watchpoint at 0x{0:08x}")]
+    NoWatchpoint(u32),
+    #[error("Watchpoints cover whole words, 0x{0:08x} is not aligned")]
+    UnalignedWatchpoint(u32),
+    #[error("The program has returned")]
+    Halted,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub struct Debugger {
+    pub executor: Executor,
+    // Everything executed, to go back in time
+    history: History,
+    program: Elf,
+    #[cfg(feature = "debug-info")]
+    debug_info: Option<DebugInfo>,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new(program: Elf) -> Debugger {
+        let executor = Executor::new(program.image.clone(), program.entry_point);
+        Debugger {
+            history: History::new(&executor, SNAPSHOT_INTERVAL),
+            executor,
+            program,
+            #[cfg(feature = "debug-info")]
+            debug_info: None,
+            last_command: String::new(),
+        }
+    }
+
+    /// Names functions as `debug_info` does and shows the source line of addresses.
+    #[cfg(feature = "debug-info")]
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Debugger {
+        self.debug_info = Some(debug_info);
+        self
+    }
+
+    /// Runs the command on `line`. Returns `false` once the user asked to quit.
+    pub fn execute(&mut self, line: &str, output: &mut impl Write) -> std::io::Result<bool> {
+        let line = match line.trim() {
+            "" => std::mem::take(&mut self.last_command),
+            line => line.to_string(),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words.as_slice() {
+            [] => Ok(()),
+            ["quit" | "q"] => return Ok(false),
+            ["help" | "h"] => help(output).map_err(CommandError::from),
+            arguments => self.command(arguments, output),
+        };
+        match result {
+            Ok(()) => {}
+            Err(CommandError::Io(error)) => return Err(error),
+            Err(error) => writeln!(output, "error: {error}")?,
+        }
+        self.last_command = line;
+        Ok(true)
+    }
+
+    fn command(&mut self, words: &[&str], output: &mut impl Write) -> Result<(), CommandError> {
+        match words {
+            ["break" | "b", location] => {
+                let addr = self.location(location)?;
+                self.executor.breakpoints.insert(addr);
+                writeln!(output, "Breakpoint set at {}", self.describe(addr))?;
+            }
+            ["delete", location] => {
+                let addr = self.location(location)?;
+                if !self.executor.breakpoints.remove(&addr) {
+                    return Err(CommandError::NoBreakpoint(addr));
+                }
+            }
+            ["breakpoints"] => {
+                for addr in &self.executor.breakpoints {
+                    writeln!(output, "{}", self.describe(*addr))?;
+                }
+            }
+            ["watch", addr] => self.watch(addr, Watch::Write, output)?,
+            ["watch", addr, "read"] => self.watch(addr, Watch::Read, output)?,
+            ["watch", addr, "write"] => self.watch(addr, Watch::Write, output)?,
+            ["watch", addr, "access"] => self.watch(addr, Watch::Access, output)?,
+            ["unwatch", addr] => {
+                let addr = self.location(addr)?;
+                if self.executor.watchpoints.remove(&addr).is_none() {
+                    return Err(CommandError::NoWatchpoint(addr));
+                }
+            }
+            ["watchpoints"] => {
+                for (addr, watch) in &self.executor.watchpoints {
+                    writeln!(output, "{} {watch}", self.describe(*addr))?;
+                }
+            }
+            ["step" | "s"] => self.step(1, output)?,
+            ["step" | "s", count] => self.step(parse_value(count)?, output)?,
+            ["continue" | "c"] => self.resume(output)?,
+            ["registers" | "regs"] => {
+                writeln!(
+                    output,
+                    "pc  0x{:08x}  cycles {}",
+                    self.executor.pc, self.executor.cycles
+                )?;
+                write!(output, "{}", self.executor.registers)?;
+            }
+            ["register" | "reg", name] => {
+                let value = match *name {
+                    "pc" => self.executor.pc,
+                    name => self.executor.registers.read(register(name)?),
+                };
+                writeln!(output, "{name} = 0x{value:08x} ({})", value as i32)?;
+            }
+            ["register" | "reg", name, value] => {
+                let value = parse_value(value)?;
+                match *name {
+                    "pc" => self.executor.set_pc(value),
+                    name => self.executor.registers.write(register(name)?, value),
+                }
+                self.history.snapshot(&self.executor);
+            }
+            ["memory" | "mem", addr] => self.memory(self.location(addr)?, MEMORY_WORDS, output)?,
+            ["memory" | "mem", addr, count] => {
+                self.memory(self.location(addr)?, parse_count(count)?, output)?
+            }
+            ["write", addr, value] => {
+                let addr = self.location(addr)?;
+                self.executor.memory.write_word(addr, parse_value(value)?);
+                self.history.snapshot(&self.executor);
+            }
+            ["reverse-step" | "rs"] => self.reverse_step(1, output)?,
+            ["reverse-step" | "rs", count] => self.reverse_step(parse_value(count)?, output)?,
+            ["reverse-continue" | "rc"] => self.reverse_continue(output)?,
+            ["last-write", addr] => self.last_write(self.location(addr)?, output)?,
+            ["disassemble" | "disas"] => {
+                self.disassemble(self.executor.pc, DISASSEMBLY_LINES, output)?
+            }
+            ["disassemble" | "disas", location] => {
+                self.disassemble(self.location(location)?, DISASSEMBLY_LINES, output)?
+            }
+            ["disassemble" | "disas", location, count] => {
+                self.disassemble(self.location(location)?, parse_count(count)?, output)?
+            }
+            [command, ..] => return Err(usage(command)),
+            [] => {}
+        }
+        Ok(())
+    }
+
+    fn watch(
+        &mut self,
+        addr: &str,
+        watch: Watch,
+        output: &mut impl Write,
+    ) -> Result<(), CommandError> {
+        let addr = self.location(addr)?;
+        if !addr.is_multiple_of(WORD_SIZE) {
+            return Err(CommandError::UnalignedWatchpoint(addr));
+        }
+        self.executor.watchpoints.insert(addr, watch);
+        writeln!(output, "Watchpoint set on {} {watch}", self.describe(addr))?;
+        Ok(())
+    }
+
+    // Reads of uninitialized memory are reported as the program runs
+    fn step(&mut self, count: u32, output: &mut impl Write) -> Result<(), CommandError> {
+        if self.executor.is_halted() {
+            return Err(CommandError::Halted);
+        }
+        let mut reads = UninitializedReads::default();
+        let mut result = Ok(());
+        for _ in 0..count {
+            result = self.history.step(&mut self.executor, &mut reads);
+            if result.is_err() || self.executor.is_halted() {
+                break;
+            }
+        }
+        self.warn(&reads, output)?;
+        self.report(result, output)
+    }
+
+    fn resume(&mut self, output: &mut impl Write) -> Result<(), CommandError> {
+        if self.executor.is_halted() {
+            return Err(CommandError::Halted);
+        }
+        let mut reads = UninitializedReads::default();
+        let result = self.history.run(&mut self.executor, &mut reads);
+        self.warn(&reads, output)?;
+        // Without a cycle limit, `run` only stops early at breakpoints
+        if result.is_ok() && !self.executor.is_halted() {
+            writeln!(output, "Breakpoint at {}", self.describe(self.executor.pc))?;
+        }
+        self.report(result, output)
+    }
+
+    fn reverse_step(&mut self, count: u32, output: &mut impl Write) -> std::io::Result<()> {
+        let cycle = self.executor.cycles.saturating_sub(count as u64);
+        self.history.rewind(&mut self.executor, cycle);
+        self.report_history_start(output)?;
+        self.disassemble_line(self.executor.pc, output)
+    }
+
+    fn reverse_continue(&mut self, output: &mut impl Write) -> std::io::Result<()> {
+        let breakpoints = &self.executor.breakpoints;
+        match self
+            .history
+            .last_visit(self.executor.cycles, |pc| breakpoints.contains(&pc))
+        {
+            Some(cycle) => {
+                self.history.rewind(&mut self.executor, cycle);
+                writeln!(output, "Breakpoint at {}", self.describe(self.executor.pc))?;
+            }
+            None => {
+                self.history.rewind(&mut self.executor, 0);
+                self.report_history_start(output)?;
+            }
+        }
+        self.disassemble_line(self.executor.pc, output)
+    }
+
+    fn report_history_start(&self, output: &mut impl Write) -> std::io::Result<()> {
+        if self.executor.cycles == self.history.first_cycle() {
+            writeln!(output, "Reached the start of the history")?;
+        }
+        Ok(())
+    }
+
+    fn last_write(&self, addr: u32, output: &mut impl Write) -> std::io::Result<()> {
+        let Some(write) = self.history.last_write(addr) else {
+            return writeln!(output, "{} was not written", self.describe(addr));
+        };
+        writeln!(
+            output,
+            "{} = 0x{:08x} (was 0x{:08x}), written at cycle {} by {}: {}",
+            self.describe(addr),
+            write.value,
+            write.old_value,
+            write.cycle,
+            self.describe(write.pc),
+            write.instruction
+        )
+    }
+
+    fn warn(&self, reads: &UninitializedReads, output: &mut impl Write) -> std::io::Result<()> {
+        for read in &reads.reads {
+            writeln!(
+                output,
+                "warning: read of uninitialized 0x{:08x} at {}",
+                read.addr,
+                self.describe(read.pc)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn report(
+        &self,
+        result: Result<(), ExecutionError>,
+        output: &mut impl Write,
+    ) -> Result<(), CommandError> {
+        match result {
+            Ok(()) if self.executor.is_halted() => {
+                let registers = &self.executor.registers;
+                writeln!(
+                    output,
+                    "Program returned a0 = 0x{:08x}, a1 = 0x{:08x} after {} cycles",
+                    registers.read(A0),
+                    registers.read(A1),
+                    self.executor.cycles
+                )?;
+                return Ok(());
+            }
+            Ok(()) => {}
+            Err(ExecutionError::Breakpoint(pc)) => {
+                writeln!(output, "ebreak at {}", self.describe(pc))?
+            }
+            Err(ExecutionError::Watchpoint { pc, addr, access }) => writeln!(
+                output,
+                "Watchpoint: {access} of {} at {}",
+                self.describe(addr),
+                self.describe(pc)
+            )?,
+            Err(error) => writeln!(output, "error: {error}")?,
+        }
+        self.disassemble_line(self.executor.pc, output)?;
+        Ok(())
+    }
+
+    fn memory(&self, addr: u32, count: u32, output: &mut impl Write) -> std::io::Result<()> {
+        for row in 0..count.div_ceil(MEMORY_WORDS) {
+            let row_addr = addr.wrapping_add(row * MEMORY_WORDS * 4);
+            write!(output, "0x{row_addr:08x}:")?;
+            for i in 0..MEMORY_WORDS.min(count - row * MEMORY_WORDS) {
+                let word_addr = row_addr.wrapping_add(4 * i);
+                let word = self.executor.memory.read_word(word_addr).unwrap_or(0);
+                write!(output, " 0x{word:08x}")?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    // `count` instructions centered on `addr`
+    fn disassemble(&self, addr: u32, count: u32, output: &mut impl Write) -> std::io::Result<()> {
+        let start = addr.saturating_sub(4 * (count / 2));
+        for i in 0..count {
+            self.disassemble_line(start.wrapping_add(4 * i), output)?;
+        }
+        Ok(())
+    }
+
+    // `=>` marks the pc and `*` breakpoints
+    fn disassemble_line(&self, addr: u32, output: &mut impl Write) -> std::io::Result<()> {
+        let Some(word) = self.executor.memory.read_word(addr) else {
+            return Ok(());
+        };
+        let marker = match (
+            addr == self.executor.pc,
+            self.executor.breakpoints.contains(&addr),
+        ) {
+            (true, true) => "=>*",
+            (true, false) => "=> ",
+            (false, true) => "  *",
+            (false, false) => "   ",
+        };
+        let text = match Instruction::parse(word) {
+            Ok(instruction) => match instruction
+                .jump_target(addr)
+                .and_then(|target| self.symbol(target))
+            {
+                Some(target) => format!("{instruction} {target}"),
+                None => instruction.to_string(),
+            },
+            Err(_) => format!(".word 0x{word:08x}"),
+        };
+        writeln!(output, "{marker} {}: {text}", self.describe(addr))
+    }
+
+    // An address, with the symbol it is in if there is one, and its source line if known
+    fn describe(&self, addr: u32) -> String {
+        #[cfg(feature = "debug-info")]
+        if let Some(description) = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.describe(addr))
+        {
+            return format!("0x{addr:08x} {description}");
+        }
+        match symbolize(&self.program, addr) {
+            Some(symbol) => format!("0x{addr:08x} {symbol}"),
+            None => format!("0x{addr:08x}"),
+        }
+    }
+
+    fn symbol(&self, addr: u32) -> Option<String> {
+        #[cfg(feature = "debug-info")]
+        if let Some(symbol) = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.symbolize(addr))
+        {
+            return Some(symbol);
+        }
+        symbolize(&self.program, addr)
+    }
+
+    fn location(&self, location: &str) -> Result<u32, CommandError> {
+        self.program
+            .symbol_address(location)
+            .or_else(|| parse_value(location).ok())
+            .ok_or_else(|| CommandError::InvalidLocation(location.to_string()))
+    }
+}
+
+// Hexadecimal with a 0x prefix, or decimal, possibly negative
+fn parse_value(value: &str) -> Result<u32, CommandError> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value
+            .parse::<u32>()
+            .ok()
+            .or_else(|| value.parse::<i32>().ok().map(|value| value as u32)),
+    };
+    parsed.ok_or_else(|| CommandError::InvalidValue(value.to_string()))
+}
+
+// A number of words, which must fit in the address space
+fn parse_count(value: &str) -> Result<u32, CommandError> {
+    let count = parse_value(value)?;
+    match count.checked_mul(WORD_SIZE) {
+        Some(_) => Ok(count),
+        None => Err(CommandError::InvalidValue(value.to_string())),
+    }
+}
+
+fn register(name: &str) -> Result<u32, CommandError> {
+    register_index(name).ok_or_else(|| CommandError::UnknownRegister(name.to_string()))
+}
+
+fn help(output: &mut impl Write) -> std::io::Result<()> {
+    for (name, alias, arguments, description) in COMMANDS {
+        let command = format!("{name} {arguments}");
+        let alias = match alias {
+            "" => String::new(),
+            alias => format!(" ({alias})"),
+        };
+        writeln!(output, "{command:<32} {description}{alias}")?;
+    }
+    Ok(())
+}
+
+// The error for a known command with the wrong arguments, or an unknown one
+fn usage(command: &str) -> CommandError {
+    match COMMANDS
+        .iter()
+        .find(|(name, alias, ..)| command == *name || command == *alias)
+    {
+        Some((name, _, arguments, _)) => CommandError::Usage(format!("{name} {arguments}")),
+        None => CommandError::UnknownCommand(command.to_string()),
+    }
+}
@@ -1,8 +1,14 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
 
 use elf::{
     ElfBytes,
-    abi::{EM_RISCV, ET_EXEC, PT_LOAD},
+    abi::{
+        EM_RISCV, ET_EXEC, PF_X, PT_LOAD, SHF_ALLOC, SHF_EXECINSTR, SHN_ABS, SHN_UNDEF, STT_FUNC,
+        STT_NOTYPE,
+    },
     endian::LittleEndian,
     file::Class,
 };
@@ -11,10 +17,36 @@ pub struct Elf {
     pub entry_point: u32,
 
     pub image: BTreeMap<u32, u32>,
+
+    // Address ranges holding code
+    pub executable_ranges: Vec<Range<u32>>,
+
+    // Function and label symbols from `.symtab`, by address
+    pub symbols: BTreeMap<u32, String>,
 }
 pub(crate) const WORD_SIZE: u32 = 4;
 pub const MAX_MEMORY_SIZE: u32 = u32::MAX;
 pub const MAX_SEGMENTS: usize = 256;
+// Every word of the image is a map entry, so this bounds the memory used by loading
+pub const MAX_IMAGE_SIZE: u32 = 64 * 1024 * 1024;
+
+/// Bounds on the resources an ELF may claim, so that hostile inputs fail to load instead of
+/// exhausting memory.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadLimits {
+    pub max_segments: usize,
+    // Total bytes of all loadable segments, including zero-filled ones
+    pub max_image_size: u32,
+}
+
+impl Default for LoadLimits {
+    fn default() -> LoadLimits {
+        LoadLimits {
+            max_segments: MAX_SEGMENTS,
+            max_image_size: MAX_IMAGE_SIZE,
+        }
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum ElfError {
@@ -46,11 +78,18 @@ pub enum ElfError {
     AddrTooLarge,
     #[error("Segment offset is invalid")]
     InvalidOffset,
+    #[error("Image size exceeds the limit of {0} bytes")]
+    ImageTooLarge(u32),
 }
 
 impl Elf {
     pub fn load(input: &[u8]) -> Result<Elf, ElfError> {
+        Elf::load_with_limits(input, LoadLimits::default())
+    }
+
+    pub fn load_with_limits(input: &[u8], limits: LoadLimits) -> Result<Elf, ElfError> {
         let mut image: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut executable_segments = Vec::new();
         let elf = ElfBytes::<LittleEndian>::minimal_parse(input)?;
         if elf.ehdr.class != Class::ELF32 {
             return Err(ElfError::Not32Bit);
@@ -70,9 +109,10 @@ impl Elf {
             return Err(ElfError::InvalidEntryPoint);
         }
         let segments = elf.segments().ok_or(ElfError::NoSegments)?;
-        if segments.len() > MAX_SEGMENTS {
+        if segments.len() > limits.max_segments {
             return Err(ElfError::TooManySegments);
         }
+        let mut image_size: u32 = 0;
         for segment in segments.iter().filter(|segment| segment.p_type == PT_LOAD) {
             let file_size: u32 = segment
                 .p_filesz
@@ -93,22 +133,116 @@ impl Elf {
                 .p_offset
                 .try_into()
                 .map_err(|_| ElfError::OffsetTooLarge)?;
+            image_size = image_size
+                .checked_add(mem_size)
+                .filter(|size| *size <= limits.max_image_size)
+                .ok_or(ElfError::ImageTooLarge(limits.max_image_size))?;
+            let loaded_size = file_size.min(mem_size);
+            let data = offset
+                .checked_add(loaded_size)
+                .and_then(|end| input.get(offset as usize..end as usize))
+                .ok_or(ElfError::InvalidOffset)?;
+            if segment.p_flags & PF_X != 0 {
+                let end = vaddr.checked_add(mem_size).ok_or(ElfError::AddrTooLarge)?;
+                executable_segments.push(vaddr..end);
+            }
             for i in (0..mem_size).step_by(WORD_SIZE as usize) {
                 let addr = vaddr.checked_add(i).ok_or(ElfError::AddrTooLarge)?;
-                if i >= file_size {
-                    image.insert(addr, 0);
-                } else {
-                    let mut word = 0;
-                    let len = (file_size - i).min(WORD_SIZE);
-                    for j in 0..len {
-                        let offset = (offset + i + j) as usize;
-                        let byte = input.get(offset).ok_or(ElfError::InvalidOffset)?;
-                        word |= (*byte as u32) << (j * 8);
-                    }
-                    image.insert(addr, word);
+                let mut word = 0;
+                for (j, byte) in data
+                    .iter()
+                    .skip(i as usize)
+                    .take(WORD_SIZE as usize)
+                    .enumerate()
+                {
+                    word |= (*byte as u32) << (j * 8);
                 }
+                image.insert(addr, word);
             }
         }
-        Ok(Self { entry_point, image })
+        // Executable segments may also map the ELF headers, so prefer the executable
+        // sections when the section headers are available
+        let executable_sections = load_executable_sections(&elf)?;
+        let executable_ranges = if executable_sections.is_empty() {
+            executable_segments
+        } else {
+            executable_sections
+        };
+        let symbols = load_symbols(&elf);
+        Ok(Self {
+            entry_point,
+            image,
+            executable_ranges,
+            symbols,
+        })
+    }
+
+    /// Address of the symbol called `name`, if the ELF defines one.
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol)| *symbol == name)
+            .map(|(addr, _)| *addr)
+    }
+}
+
+fn load_executable_sections(elf: &ElfBytes<LittleEndian>) -> Result<Vec<Range<u32>>, ElfError> {
+    let mut ranges = Vec::new();
+    let Some(section_headers) = elf.section_headers() else {
+        return Ok(ranges);
+    };
+    let flags = SHF_ALLOC as u64 | SHF_EXECINSTR as u64;
+    for section in section_headers.iter() {
+        if section.sh_flags & flags != flags || section.sh_size == 0 {
+            continue;
+        }
+        let start: u32 = section
+            .sh_addr
+            .try_into()
+            .map_err(|_| ElfError::AddrTooLarge)?;
+        let end = section
+            .sh_addr
+            .checked_add(section.sh_size)
+            .and_then(|end| u32::try_from(end).ok())
+            .ok_or(ElfError::AddrTooLarge)?;
+        ranges.push(start..end);
+    }
+    Ok(ranges)
+}
+
+// Symbols only name addresses, so a malformed symbol table leaves them out instead of failing
+// the load
+fn load_symbols(elf: &ElfBytes<LittleEndian>) -> BTreeMap<u32, String> {
+    let mut symbols = BTreeMap::new();
+    let mut function_addresses = BTreeSet::new();
+    let Ok(Some((symbol_table, string_table))) = elf.symbol_table() else {
+        return symbols;
+    };
+    for symbol in symbol_table.iter() {
+        let symbol_type = symbol.st_symtype();
+        if (symbol_type != STT_FUNC && symbol_type != STT_NOTYPE)
+            || symbol.st_name == 0
+            || symbol.st_shndx == SHN_UNDEF
+            || symbol.st_shndx == SHN_ABS
+        {
+            continue;
+        }
+        let Ok(name) = string_table.get(symbol.st_name as usize) else {
+            continue;
+        };
+        // Skip RISC-V mapping symbols ($x, $d) and assembler local labels
+        if name.starts_with('$') || name.starts_with(".L") {
+            continue;
+        }
+        let Ok(addr) = u32::try_from(symbol.st_value) else {
+            continue;
+        };
+        // Function symbols win over plain labels at the same address
+        if symbol_type == STT_FUNC && function_addresses.insert(addr) {
+            symbols.insert(addr, name.to_string());
+        } else {
+            symbols.entry(addr).or_insert_with(|| name.to_string());
+        }
     }
+    symbols
 }
@@ -0,0 +1,453 @@
+// A GDB remote serial protocol stub, so guest programs can be debugged with gdb or an IDE
+// built on it. `src/bin/gdb-server.rs` serves it over TCP, `target remote :1234`, or stdio,
+// `target remote | gdb-server <program> stdio`.
+//
+// Registers and memory can be read and written, breakpoints and watchpoints set, and the
+// program stepped and continued. A running program is stopped by an interrupt, what gdb sends
+// on Ctrl-C.
+
+use std::{
+    io::{BufReader, Read, Write},
+    sync::mpsc::{Receiver, channel},
+};
+
+use crate::vm::{
+    execution::{ExecutionError, Executor, Watch},
+    observer::Silent,
+    registers::{A0, NUM_REGISTERS, REGISTER_NAMES},
+};
+
+// Largest packet gdb may send, in the hexadecimal gdb expects
+const PACKET_SIZE: usize = 0x1000;
+// gdb numbers pc after x0..x31
+const PC_REGISTER: usize = NUM_REGISTERS;
+const MALFORMED: &str = "E01";
+// Sent on its own, outside packets
+const INTERRUPT: u8 = 0x03;
+// Cycles a continued program runs between checks for an interrupt
+const INTERRUPT_INTERVAL: u64 = 0x10_0000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+pub struct GdbStub {
+    pub executor: Executor,
+}
+
+impl GdbStub {
+    pub fn new(executor: Executor) -> GdbStub {
+        GdbStub { executor }
+    }
+
+    /// Answers packets read from `input` until gdb detaches, kills the program or closes the
+    /// connection. `input` is read on a thread of its own, so a running program can be
+    /// interrupted. That thread stays blocked on `input` if it is still open at the end.
+    pub fn serve(
+        &mut self,
+        input: impl Read + Send + 'static,
+        mut output: impl Write,
+    ) -> std::io::Result<()> {
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for byte in BufReader::new(input).bytes() {
+                let error = byte.is_err();
+                if sender.send(byte).is_err() || error {
+                    break;
+                }
+            }
+        });
+        let mut last_response = Vec::new();
+        while let Some(packet) = read_packet(&receiver, &mut output, &last_response)? {
+            let Some(response) = self.respond(&packet, || interrupted(&receiver)) else {
+                break;
+            };
+            last_response = frame(&response);
+            output.write_all(&last_response)?;
+            output.flush()?;
+            if packet.starts_with('D') {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to the data of one packet, `None` if the session ends without one.
+    /// Unsupported packets get an empty response, as the protocol requires.
+    pub fn handle(&mut self, packet: &str) -> Option<String> {
+        self.respond(packet, || false)
+    }
+
+    // `interrupted` tells whether gdb asked to stop a continued program
+    fn respond(&mut self, packet: &str, interrupted: impl FnMut() -> bool) -> Option<String> {
+        let mut chars = packet.chars();
+        let command = chars.next();
+        let arguments = chars.as_str();
+        let response = match command {
+            Some('?') => self.stop_reply(SIGTRAP),
+            Some('g') => (0..=PC_REGISTER)
+                .map(|register| hex_word(self.read_register(register)))
+                .collect(),
+            Some('G') => self.write_registers(arguments),
+            Some('p') => usize::from_str_radix(arguments, 16)
+                .ok()
+                .filter(|register| *register <= PC_REGISTER)
+                .map_or(MALFORMED.to_string(), |register| {
+                    hex_word(self.read_register(register))
+                }),
+            Some('P') => self.write_register(arguments),
+            Some('m') => self.read_memory(arguments),
+            Some('M') => self.write_memory(arguments),
+            Some(kind @ ('Z' | 'z')) => self.breakpoint(arguments, kind == 'Z'),
+            Some(kind @ ('s' | 'c')) => {
+                if !arguments.is_empty() {
+                    match parse_hex(arguments) {
+                        Some(pc) => self.executor.set_pc(pc),
+                        None => return Some(MALFORMED.to_string()),
+                    }
+                }
+                self.resume(kind == 's', interrupted)
+            }
+            // There is a single thread
+            Some('H' | 'T') => "OK".to_string(),
+            Some('D') => "OK".to_string(),
+            Some('k') => return None,
+            Some('q') => query(arguments),
+            _ => String::new(),
+        };
+        Some(response)
+    }
+
+    fn resume(&mut self, single_step: bool, interrupted: impl FnMut() -> bool) -> String {
+        if self.executor.is_halted() {
+            return self.stop_reply(SIGTRAP);
+        }
+        let result = if single_step {
+            self.executor.step().map(|()| SIGTRAP)
+        } else {
+            self.run(interrupted)
+        };
+        let signal = match result {
+            Ok(signal) => signal,
+            Err(ExecutionError::Breakpoint(_)) => SIGTRAP,
+            // The watchpoint that stopped the program, as it was set
+            Err(ExecutionError::Watchpoint { addr, .. }) => {
+                let kind = match self.executor.watchpoints.get(&addr) {
+                    Some(Watch::Read) => "rwatch",
+                    Some(Watch::Access) => "awatch",
+                    _ => "watch",
+                };
+                return format!("T{SIGTRAP:02x}{kind}:{addr:x};");
+            }
+            Err(ExecutionError::InvalidPc(_)) => SIGSEGV,
+            Err(_) => SIGILL,
+        };
+        self.stop_reply(signal)
+    }
+
+    // Runs in chunks of `INTERRUPT_INTERVAL` cycles, checking for an interrupt after each.
+    // The signal is SIGINT if the program was interrupted.
+    fn run(&mut self, mut interrupted: impl FnMut() -> bool) -> Result<u8, ExecutionError> {
+        let executor = &mut self.executor;
+        let cycle_limit = executor.cycle_limit;
+        let result = loop {
+            let chunk_end = executor
+                .cycles
+                .saturating_add(INTERRUPT_INTERVAL)
+                .min(cycle_limit);
+            executor.cycle_limit = chunk_end;
+            let result = executor.run();
+            if result.is_err()
+                || executor.is_halted()
+                || executor.cycles != chunk_end
+                || chunk_end == cycle_limit
+            {
+                break result.map(|()| SIGTRAP);
+            }
+            // Running again would execute the instruction at the breakpoint
+            if executor.breakpoints.contains(&executor.pc) {
+                break Ok(SIGTRAP);
+            }
+            if interrupted() {
+                break Ok(SIGINT);
+            }
+        };
+        executor.cycle_limit = cycle_limit;
+        result
+    }
+
+    // A halted program exited with the low byte of a0 as its status
+    fn stop_reply(&self, signal: u8) -> String {
+        if self.executor.is_halted() {
+            format!("W{:02x}", self.executor.registers.read(A0) as u8)
+        } else {
+            format!("S{signal:02x}")
+        }
+    }
+
+    fn read_register(&self, register: usize) -> u32 {
+        match register {
+            PC_REGISTER => self.executor.pc,
+            register => self.executor.registers.read(register as u32),
+        }
+    }
+
+    fn set_register(&mut self, register: usize, value: u32) {
+        match register {
+            PC_REGISTER => self.executor.set_pc(value),
+            register => self.executor.registers.write(register as u32, value),
+        }
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> String {
+        if arguments.len() != 8 * (PC_REGISTER + 1) || !arguments.is_ascii() {
+            return MALFORMED.to_string();
+        }
+        let Some(values) = (0..=PC_REGISTER)
+            .map(|register| parse_hex_word(&arguments[8 * register..8 * register + 8]))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return MALFORMED.to_string();
+        };
+        for (register, value) in values.into_iter().enumerate() {
+            self.set_register(register, value);
+        }
+        "OK".to_string()
+    }
+
+    fn write_register(&mut self, arguments: &str) -> String {
+        let Some((register, value)) = arguments.split_once('=') else {
+            return MALFORMED.to_string();
+        };
+        match (usize::from_str_radix(register, 16), parse_hex_word(value)) {
+            (Ok(register), Some(value)) if register <= PC_REGISTER => {
+                self.set_register(register, value);
+                "OK".to_string()
+            }
+            _ => MALFORMED.to_string(),
+        }
+    }
+
+    // Memory is addressed by word, so bytes are read from and written into aligned words.
+    // Bytes the program never wrote read as zero.
+    fn read_memory(&self, arguments: &str) -> String {
+        let Some((addr, length)) = parse_range(arguments) else {
+            return MALFORMED.to_string();
+        };
+        (0..length.min(PACKET_SIZE as u32 / 2))
+            .map(|i| {
+                let addr = addr.wrapping_add(i);
+                let word = self.executor.memory.read_word(addr & !3).unwrap_or(0);
+                format!("{:02x}", (word >> (8 * (addr & 3))) as u8)
+            })
+            .collect()
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> String {
+        let Some((range, data)) = arguments.split_once(':') else {
+            return MALFORMED.to_string();
+        };
+        let bytes = parse_bytes(data);
+        let (Some((addr, length)), Some(bytes)) = (parse_range(range), bytes) else {
+            return MALFORMED.to_string();
+        };
+        if bytes.len() != length as usize {
+            return MALFORMED.to_string();
+        }
+        let memory = &mut self.executor.memory;
+        for (i, byte) in bytes.into_iter().enumerate() {
+            let addr = addr.wrapping_add(i as u32);
+            let value = (byte as u32) << (8 * (addr & 3));
+            memory.store(addr & !3, value, 1 << (addr & 3), &mut Silent);
+        }
+        "OK".to_string()
+    }
+
+    // Software and hardware breakpoints are the same here. Watchpoints cover every word in
+    // their range.
+    fn breakpoint(&mut self, arguments: &str, insert: bool) -> String {
+        let mut fields = arguments.split(',');
+        let (Some(kind), Some(addr), Some(length)) = (
+            fields.next(),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            return MALFORMED.to_string();
+        };
+        let watch = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.executor.breakpoints.insert(addr);
+                } else {
+                    self.executor.breakpoints.remove(&addr);
+                }
+                return "OK".to_string();
+            }
+            "2" => Watch::Write,
+            "3" => Watch::Read,
+            "4" => Watch::Access,
+            _ => return String::new(),
+        };
+        let end = addr.saturating_add(length.max(1));
+        for word in (addr & !3..end).step_by(4) {
+            if insert {
+                self.executor.watchpoints.insert(word, watch);
+            } else {
+                self.executor.watchpoints.remove(&word);
+            }
+        }
+        "OK".to_string()
+    }
+}
+
+fn query(query: &str) -> String {
+    if query.starts_with("Supported") {
+        return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+");
+    }
+    // The program is loaded, not started by gdb, so quitting detaches
+    if query == "Attached" {
+        return "1".to_string();
+    }
+    let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") else {
+        return String::new();
+    };
+    let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| {
+        Some((
+            usize::from_str_radix(offset, 16).ok()?,
+            usize::from_str_radix(length, 16).ok()?,
+        ))
+    }) else {
+        return MALFORMED.to_string();
+    };
+    // `m` is followed by more chunks, `l` is the last one
+    let description = target_description();
+    let chunk = description.get(offset..).unwrap_or_default();
+    match chunk.get(..length) {
+        Some(chunk) if chunk.len() < description.len() - offset => format!("m{chunk}"),
+        _ => format!("l{chunk}"),
+    }
+}
+
+// Tells gdb the target is RV32, whatever architecture it was built for by default
+fn target_description() -> String {
+    let mut description = String::from(
+        "<?xml version=\"1.0\"?>\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\">\
+         <architecture>riscv:rv32</architecture>\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">",
+    );
+    for (register, name) in REGISTER_NAMES.iter().enumerate() {
+        let kind = match *name {
+            "sp" | "gp" | "tp" => "data_ptr",
+            "ra" => "code_ptr",
+            _ => "int",
+        };
+        description.push_str(&format!(
+            "<reg name=\"{name}\" bitsize=\"32\" type=\"{kind}\" regnum=\"{register}\"/>"
+        ));
+    }
+    description.push_str(&format!(
+        "<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"{PC_REGISTER}\"/>\
+         </feature></target>"
+    ));
+    description
+}
+
+// The data of the next packet, which is acknowledged, or `None` at the end of the input.
+// Acknowledgements between packets are skipped, and so are interrupts, as the program is
+// stopped while packets are read. A `-` asks for the last response again.
+fn read_packet(
+    input: &Receiver<std::io::Result<u8>>,
+    output: &mut impl Write,
+    last_response: &[u8],
+) -> std::io::Result<Option<String>> {
+    loop {
+        match next_byte(input)? {
+            None => return Ok(None),
+            Some(b'$') => {}
+            Some(b'-') => {
+                output.write_all(last_response)?;
+                output.flush()?;
+                continue;
+            }
+            Some(_) => continue,
+        }
+        let mut data = Vec::new();
+        loop {
+            match next_byte(input)? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(byte) => data.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        for digit in &mut checksum {
+            match next_byte(input)? {
+                None => return Ok(None),
+                Some(byte) => *digit = byte,
+            }
+        }
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if expected == Some(checksum_of(&data)) {
+            output.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        output.write_all(b"-")?;
+        output.flush()?;
+    }
+}
+
+// `None` once the input ended
+fn next_byte(input: &Receiver<std::io::Result<u8>>) -> std::io::Result<Option<u8>> {
+    input.recv().ok().transpose()
+}
+
+// gdb sends nothing but interrupts while the program runs, so anything else is dropped
+fn interrupted(input: &Receiver<std::io::Result<u8>>) -> bool {
+    input.try_iter().any(|byte| matches!(byte, Ok(INTERRUPT)))
+}
+
+/// `$data#checksum`, the way packets are sent over the wire.
+pub fn frame(data: &str) -> Vec<u8> {
+    format!("${data}#{:02x}", checksum_of(data.as_bytes())).into_bytes()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+// Words are sent as their bytes in memory order
+fn hex_word(value: u32) -> String {
+    format!("{:08x}", value.swap_bytes())
+}
+
+fn parse_hex_word(hex: &str) -> Option<u32> {
+    (hex.len() == 8)
+        .then(|| parse_hex(hex))
+        .flatten()
+        .map(u32::swap_bytes)
+}
+
+fn parse_hex(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex, 16).ok()
+}
+
+// `addr,length`
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (addr, length) = range.split_once(',')?;
+    Some((parse_hex(addr)?, parse_hex(length)?))
+}
+
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -1,2 +1,8 @@
+pub mod assembler;
+pub mod builder;
+#[cfg(feature = "debug-info")]
+pub mod debug_info;
+pub mod debugger;
 pub mod elf;
+pub mod gdb;
 pub mod vm;
@@ -1,12 +1,22 @@
-use vm::{elf::Elf, vm::execution::run_program};
+use vm::{
+    elf::Elf,
+    vm::{
+        disassembler::disassemble,
+        execution::Executor,
+        registers::{A0, A1},
+    },
+};
 
 fn main() {
     println!("Reading elf");
     let elf_data = std::fs::read("./program_artifacts/asm/basic_program.elf").unwrap();
     let program = Elf::load(&elf_data).unwrap();
     println!("Program entry: 0x{:08x}", program.entry_point);
-    program.image.iter().for_each(|(addr, word)| {
-        println!("0x{addr:08x}: 0x{word:08x}");
-    });
-    run_program(program.image, program.entry_point);
+    print!("{}", disassemble(&program));
+    let mut executor = Executor::new(program.image, program.entry_point);
+    executor.run().unwrap();
+    let registers = &executor.registers;
+    println!("Final Register Values:\n{registers}");
+    let return_values = (registers.read(A0) as i32, registers.read(A1) as i32);
+    println!("Return Values: {return_values:?}");
 }
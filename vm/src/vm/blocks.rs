@@ -0,0 +1,75 @@
+use std::rc::Rc;
+
+use hashbrown::HashMap;
+
+use crate::{
+    elf::WORD_SIZE,
+    vm::{
+        execution::{ExecutionError, Memory},
+        instructions::Instruction,
+    },
+};
+
+// Bounds the work lost when a block has to be discovered again
+pub(crate) const MAX_BLOCK_LENGTH: usize = 64;
+
+/// Straight-line code starting at some pc: only its last instruction may change control flow
+/// or touch memory other than through plain stores.
+pub(crate) struct BasicBlock {
+    pub(crate) instructions: Vec<Instruction>,
+}
+
+// Blocks are decoded from the decode cache, so they are dropped whenever code is overwritten
+#[derive(Default)]
+pub(crate) struct BlockCache {
+    blocks: HashMap<u32, Rc<BasicBlock>>,
+    code_generation: u64,
+}
+
+impl BlockCache {
+    pub(crate) fn get(
+        &mut self,
+        pc: u32,
+        memory: &mut Memory,
+    ) -> Result<Rc<BasicBlock>, ExecutionError> {
+        if self.code_generation != memory.code_generation() {
+            self.blocks.clear();
+            self.code_generation = memory.code_generation();
+        }
+        if let Some(block) = self.blocks.get(&pc) {
+            return Ok(block.clone());
+        }
+        let block = Rc::new(discover(pc, memory)?);
+        self.blocks.insert(pc, block.clone());
+        Ok(block)
+    }
+}
+
+fn ends_block(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Branch { .. }
+            | Instruction::JumpAndLink { .. }
+            | Instruction::JumpAndLinkRegister { .. }
+            | Instruction::EnvironmentCall
+            | Instruction::EnvironmentBreak
+            | Instruction::Poseidon2Permute { .. }
+            | Instruction::Uint256Add { .. }
+    )
+}
+
+// A word that cannot be fetched ends the block before it, the error is then reported when
+// the block that starts there is discovered
+fn discover(start: u32, memory: &mut Memory) -> Result<BasicBlock, ExecutionError> {
+    let mut instructions = vec![memory.fetch(start)?];
+    let mut pc = start;
+    while instructions.len() < MAX_BLOCK_LENGTH
+        && !instructions.last().is_some_and(ends_block)
+        && let Some(next_pc) = pc.checked_add(WORD_SIZE)
+        && let Ok(instruction) = memory.fetch(next_pc)
+    {
+        instructions.push(instruction);
+        pc = next_pc;
+    }
+    Ok(BasicBlock { instructions })
+}
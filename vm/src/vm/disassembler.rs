@@ -0,0 +1,306 @@
+use std::fmt::{Display, Write};
+
+use crate::{
+    elf::{Elf, WORD_SIZE},
+    vm::{
+        instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+        registers::REGISTER_NAMES,
+    },
+};
+
+fn reg(register: &u32) -> &'static str {
+    REGISTER_NAMES[*register as usize]
+}
+
+fn arith_mnemonic(op: &ArithOp, immediate: bool) -> &'static str {
+    match (op, immediate) {
+        (ArithOp::Add, false) => "add",
+        (ArithOp::Add, true) => "addi",
+        // Operations without an immediate form only come from instructions built by hand,
+        // which cannot be encoded
+        (ArithOp::Sub, _) => "sub",
+        (ArithOp::Xor, false) => "xor",
+        (ArithOp::Xor, true) => "xori",
+        (ArithOp::Or, false) => "or",
+        (ArithOp::Or, true) => "ori",
+        (ArithOp::And, false) => "and",
+        (ArithOp::And, true) => "andi",
+        (ArithOp::ShiftLeftLogical, false) => "sll",
+        (ArithOp::ShiftLeftLogical, true) => "slli",
+        (ArithOp::ShiftRightLogical, false) => "srl",
+        (ArithOp::ShiftRightLogical, true) => "srli",
+        (ArithOp::ShiftRightArith, false) => "sra",
+        (ArithOp::ShiftRightArith, true) => "srai",
+        (ArithOp::SetLessThan, false) => "slt",
+        (ArithOp::SetLessThan, true) => "slti",
+        (ArithOp::SetLessThanU, false) => "sltu",
+        (ArithOp::SetLessThanU, true) => "sltiu",
+        (ArithOp::Mul, _) => "mul",
+        (ArithOp::MulHigh, _) => "mulh",
+        (ArithOp::MulHighSU, _) => "mulhsu",
+        (ArithOp::MulHighU, _) => "mulhu",
+        (ArithOp::Div, _) => "div",
+        (ArithOp::DivU, _) => "divu",
+        (ArithOp::Rem, _) => "rem",
+        (ArithOp::RemU, _) => "remu",
+    }
+}
+
+// Follows `l` or `s` in load and store mnemonics. Stores have no unsigned form, those
+// only come from instructions built by hand and cannot be encoded.
+fn width_suffix(width: &LoadStoreWidth) -> &'static str {
+    match width {
+        LoadStoreWidth::Byte => "b",
+        LoadStoreWidth::Half => "h",
+        LoadStoreWidth::Word => "w",
+        LoadStoreWidth::ByteUnsigned => "bu",
+        LoadStoreWidth::HalfUnsigned => "hu",
+    }
+}
+
+fn branch_mnemonic(cond: &Comparison) -> &'static str {
+    match cond {
+        Comparison::Equal => "beq",
+        Comparison::NotEqual => "bne",
+        Comparison::LessThan => "blt",
+        Comparison::GreaterOrEqual => "bge",
+        Comparison::LessThanUnsigned => "bltu",
+        Comparison::GreaterOrEqualUnsigned => "bgeu",
+    }
+}
+
+// Canonical RISC-V assembly with ABI register names, preferring the pseudo-instructions
+// used by objdump. Jump and branch targets are printed as offsets relative to the pc, the
+// listing shows them as addresses.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::ArithImm {
+                dst: 0,
+                src: 0,
+                imm: 0,
+                op: ArithOp::Add,
+            } => write!(f, "nop"),
+            Instruction::ArithImm {
+                dst,
+                src: 0,
+                imm,
+                op: ArithOp::Add,
+            } => write!(f, "li {}, {imm}", reg(dst)),
+            Instruction::ArithImm {
+                dst,
+                src,
+                imm: 0,
+                op: ArithOp::Add,
+            } => write!(f, "mv {}, {}", reg(dst), reg(src)),
+            Instruction::ArithImm {
+                dst,
+                src,
+                imm: -1,
+                op: ArithOp::Xor,
+            } => write!(f, "not {}, {}", reg(dst), reg(src)),
+            Instruction::ArithImm {
+                dst,
+                src,
+                imm: 1,
+                op: ArithOp::SetLessThanU,
+            } => write!(f, "seqz {}, {}", reg(dst), reg(src)),
+            Instruction::ArithImm { dst, src, imm, op } => {
+                let mnemonic = arith_mnemonic(op, true);
+                write!(f, "{mnemonic} {}, {}, {imm}", reg(dst), reg(src))
+            }
+            Instruction::Arith {
+                dst,
+                src1: 0,
+                src2,
+                op: ArithOp::Sub,
+            } => write!(f, "neg {}, {}", reg(dst), reg(src2)),
+            Instruction::Arith {
+                dst,
+                src1: 0,
+                src2,
+                op: ArithOp::SetLessThanU,
+            } => write!(f, "snez {}, {}", reg(dst), reg(src2)),
+            Instruction::Arith {
+                dst,
+                src1,
+                src2,
+                op,
+            } => {
+                let mnemonic = arith_mnemonic(op, false);
+                write!(f, "{mnemonic} {}, {}, {}", reg(dst), reg(src1), reg(src2))
+            }
+            Instruction::JumpAndLink { dst: 0, offset } => write!(f, "j {offset}"),
+            Instruction::JumpAndLink { dst: 1, offset } => write!(f, "jal {offset}"),
+            Instruction::JumpAndLink { dst, offset } => write!(f, "jal {}, {offset}", reg(dst)),
+            Instruction::JumpAndLinkRegister {
+                base: 1,
+                dst: 0,
+                offset: 0,
+            } => write!(f, "ret"),
+            Instruction::JumpAndLinkRegister {
+                base,
+                dst: 0,
+                offset: 0,
+            } => write!(f, "jr {}", reg(base)),
+            Instruction::JumpAndLinkRegister {
+                base,
+                dst: 1,
+                offset: 0,
+            } => write!(f, "jalr {}", reg(base)),
+            Instruction::JumpAndLinkRegister { base, dst, offset } => {
+                write!(f, "jalr {}, {offset}({})", reg(dst), reg(base))
+            }
+            Instruction::Store {
+                src,
+                offset,
+                base,
+                width,
+            } => {
+                let suffix = width_suffix(width);
+                write!(f, "s{suffix} {}, {offset}({})", reg(src), reg(base))
+            }
+            Instruction::Load {
+                dst,
+                offset,
+                base,
+                width,
+            } => {
+                let suffix = width_suffix(width);
+                write!(f, "l{suffix} {}, {offset}({})", reg(dst), reg(base))
+            }
+            Instruction::Branch {
+                src1,
+                src2: 0,
+                cond: Comparison::Equal,
+                offset,
+            } => write!(f, "beqz {}, {offset}", reg(src1)),
+            Instruction::Branch {
+                src1,
+                src2: 0,
+                cond: Comparison::NotEqual,
+                offset,
+            } => write!(f, "bnez {}, {offset}", reg(src1)),
+            Instruction::Branch {
+                src1,
+                src2,
+                cond,
+                offset,
+            } => {
+                let mnemonic = branch_mnemonic(cond);
+                write!(f, "{mnemonic} {}, {}, {offset}", reg(src1), reg(src2))
+            }
+            Instruction::LoadUpperImm { dst, imm } => {
+                write!(f, "lui {}, 0x{:x}", reg(dst), imm >> 12)
+            }
+            Instruction::AddUpperImmToPc { dst, imm } => {
+                write!(f, "auipc {}, 0x{:x}", reg(dst), imm >> 12)
+            }
+            Instruction::EnvironmentCall => write!(f, "ecall"),
+            Instruction::EnvironmentBreak => write!(f, "ebreak"),
+            Instruction::Poseidon2Permute { base } => {
+                write!(f, "poseidon2.permute {}", reg(base))
+            }
+            Instruction::Uint256Add {
+                dst,
+                x_base,
+                y_base,
+            } => write!(
+                f,
+                "uint256.add {}, {}, {}",
+                reg(dst),
+                reg(x_base),
+                reg(y_base)
+            ),
+        }
+    }
+}
+
+impl Instruction {
+    /// Destination of a pc-relative jump or branch placed at `pc`.
+    pub fn jump_target(&self, pc: u32) -> Option<u32> {
+        match self {
+            Instruction::JumpAndLink { offset, .. } | Instruction::Branch { offset, .. } => {
+                Some(pc.wrapping_add(*offset as u32))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Renders `addr` as `<symbol>` or `<symbol+0xoffset>` using the closest preceding symbol.
+pub fn symbolize(elf: &Elf, addr: u32) -> Option<String> {
+    let (symbol_addr, name) = elf.symbols.range(..=addr).next_back()?;
+    Some(match addr - symbol_addr {
+        0 => format!("<{name}>"),
+        offset => format!("<{name}+0x{offset:x}>"),
+    })
+}
+
+/// objdump-style listing of the executable segments of `elf`, with a header for every
+/// symbol and jump targets resolved to symbols. Words that do not decode are shown as data.
+pub fn disassemble(elf: &Elf) -> String {
+    listing(elf, |addr| symbolize(elf, addr), |_, _| {})
+}
+
+/// Like `disassemble`, with the source line above the instructions compiled from it and
+/// functions named as `debug_info` does.
+#[cfg(feature = "debug-info")]
+pub fn disassemble_with_source(elf: &Elf, debug_info: &crate::debug_info::DebugInfo) -> String {
+    let mut last_location = None;
+    let symbol = |addr| debug_info.symbolize(addr).or_else(|| symbolize(elf, addr));
+    listing(elf, symbol, |listing, addr| {
+        let location = debug_info.location(addr);
+        if let Some(location) = location
+            && last_location != Some(location)
+        {
+            let _ = writeln!(listing, "; {location}");
+        }
+        last_location = location;
+    })
+}
+
+// `symbol` renders addresses like `symbolize`, and `annotate` can add lines before the
+// instruction at each address
+fn listing(
+    elf: &Elf,
+    symbol: impl Fn(u32) -> Option<String>,
+    mut annotate: impl FnMut(&mut String, u32),
+) -> String {
+    let mut listing = String::new();
+    for range in &elf.executable_ranges {
+        for addr in range.clone().step_by(WORD_SIZE as usize) {
+            let Some(word) = elf.image.get(&addr) else {
+                continue;
+            };
+            if elf.symbols.contains_key(&addr)
+                && let Some(symbol) = symbol(addr)
+            {
+                let _ = writeln!(listing, "\n{addr:08x} {symbol}:");
+            }
+            annotate(&mut listing, addr);
+            let _ = match Instruction::parse(*word) {
+                Ok(instruction) => match instruction.jump_target(addr) {
+                    Some(target) => {
+                        // The offset is the last operand, replaced by the address like objdump
+                        let text = instruction.to_string();
+                        let without_offset = text
+                            .rsplit_once(' ')
+                            .map_or("", |(without_offset, _)| without_offset);
+                        let _ = write!(
+                            listing,
+                            "{addr:8x}:\t{word:08x}\t{without_offset} {target:x}"
+                        );
+                        match symbol(target) {
+                            Some(symbol) => writeln!(listing, " {symbol}"),
+                            None => writeln!(listing),
+                        }
+                    }
+                    None => writeln!(listing, "{addr:8x}:\t{word:08x}\t{instruction}"),
+                },
+                Err(_) => writeln!(listing, "{addr:8x}:\t{word:08x}\t.word 0x{word:08x}"),
+            };
+        }
+    }
+    listing
+}
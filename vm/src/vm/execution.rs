@@ -1,87 +1,615 @@
 use std::{
-    collections::BTreeMap,
-    fmt::{Debug, Display},
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
 };
 
-use crate::vm::instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth};
+use crypto::curve::CurveError;
+use hashbrown::HashMap;
 
-pub fn run_program(instruction_map: BTreeMap<u32, u32>, entrypoint: u32) -> (i32, i32) {
-    let mut memory = Memory::default();
-    load_program(instruction_map, &mut memory);
-    run_from_entrypoint(&mut memory, entrypoint)
+#[cfg(feature = "jit")]
+use crate::vm::jit::Jit;
+
+use crate::{
+    elf::WORD_SIZE,
+    vm::{
+        blocks::{BlockCache, MAX_BLOCK_LENGTH},
+        instructions::{ArithOp, DecodeError, Instruction, LoadStoreWidth},
+        observer::{Observer, Silent},
+        registers::{A0, A1, A7, Registers, SP},
+        statistics::InstructionMix,
+        syscalls::{handle_syscall, poseidon2_permute, uint256_add},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExecutionError {
+    #[error("Unknown syscall: {0}")]
+    UnknownSyscall(u32),
+    #[error("Syscall buffer at 0x{0:08x} is unaligned or out of bounds")]
+    InvalidSyscallBuffer(u32),
+    #[error("Value {0} is not a canonical field element")]
+    NonCanonicalFieldElement(u32),
+    #[error(transparent)]
+    Curve(#[from] CurveError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error("No instruction at 0x{0:08x}")]
+    InvalidPc(u32),
+    #[error("Unsupported instruction: {0}")]
+    UnsupportedInstruction(Instruction),
+    // Loads and stores must be aligned to their width, which the spec allows to require
+    #[error("Misaligned memory access at 0x{0:08x}")]
+    MisalignedAccess(u32),
+    // Like every error but a watchpoint, this leaves the pc at the failing instruction
+    #[error("ebreak at 0x{0:08x}")]
+    Breakpoint(u32),
+    // Raised after the instruction at `pc` ran, so its effects are kept
+    #[error("Watchpoint: {access} of 0x{addr:08x} at 0x{pc:08x}")]
+    Watchpoint { pc: u32, addr: u32, access: Watch },
+}
+
+/// The accesses to a word a watchpoint stops at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+impl Watch {
+    fn covers(self, is_write: bool) -> bool {
+        match self {
+            Watch::Read => !is_write,
+            Watch::Write => is_write,
+            Watch::Access => true,
+        }
+    }
+}
+
+impl Display for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Watch::Read => "read",
+            Watch::Write => "write",
+            Watch::Access => "access",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub fn run_program(
+    instruction_map: BTreeMap<u32, u32>,
+    entrypoint: u32,
+) -> Result<(i32, i32), ExecutionError> {
+    let mut executor = Executor::new(instruction_map, entrypoint);
+    executor.run()?;
+    let registers = &executor.registers;
+    Ok((registers.read(A0) as i32, registers.read(A1) as i32))
+}
+
+/// Like `run_program`, also returning the instruction mix of the run.
+pub fn run_program_with_statistics(
+    instruction_map: BTreeMap<u32, u32>,
+    entrypoint: u32,
+) -> Result<((i32, i32), InstructionMix), ExecutionError> {
+    let mut executor = Executor::new(instruction_map, entrypoint).with_statistics();
+    executor.run()?;
+    let registers = &executor.registers;
+    let result = (registers.read(A0) as i32, registers.read(A1) as i32);
+    Ok((result, executor.statistics.unwrap_or_default()))
+}
+
+// Translating a block costs about as much as interpreting it a few dozen times
+#[cfg(feature = "jit")]
+pub const JIT_HOT_THRESHOLD: u32 = 16;
+
+/// State of a running program, advanced one instruction at a time.
+pub struct Executor {
+    pub pc: u32,
+    pub registers: Registers,
+    pub memory: Memory,
+    // Instructions executed so far
+    pub cycles: u64,
+    // Addresses where `run` stops before executing the instruction
+    pub breakpoints: BTreeSet<u32>,
+    // Words where `run` and `step` stop after an instruction accessed them
+    pub watchpoints: BTreeMap<u32, Watch>,
+    // `run` stops once exactly this many instructions were executed
+    pub cycle_limit: u64,
+    // The ebreak the program stopped at, which resuming steps over
+    ebreak: Option<u32>,
+    statistics: Option<InstructionMix>,
+    blocks: BlockCache,
+    #[cfg(feature = "jit")]
+    jit: Option<Jit>,
+}
+
+impl Executor {
+    pub fn new(instruction_map: BTreeMap<u32, u32>, entrypoint: u32) -> Executor {
+        let mut registers = Registers::default();
+        // The top of memory, aligned to 16 bytes as the calling convention requires
+        registers.write(SP, 0xFFFFFFF0u32);
+        Executor {
+            pc: entrypoint,
+            registers,
+            memory: Memory::new(instruction_map),
+            cycles: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            cycle_limit: u64::MAX,
+            ebreak: None,
+            statistics: None,
+            blocks: BlockCache::default(),
+            #[cfg(feature = "jit")]
+            jit: None,
+        }
+    }
+
+    /// Translates basic blocks to native code in `run` once they ran `hot_threshold` times,
+    /// see `JIT_HOT_THRESHOLD`. Only x86-64 hosts are supported, elsewhere this has no effect,
+    /// and only part of RV32I is translated, the rest is interpreted.
+    #[cfg(feature = "jit")]
+    pub fn with_jit(mut self, hot_threshold: u32) -> Executor {
+        self.jit = Some(Jit::new(hot_threshold));
+        self
+    }
+
+    /// Number of blocks currently held as native code.
+    #[cfg(feature = "jit")]
+    pub fn translated_blocks(&self) -> usize {
+        self.jit.as_ref().map_or(0, Jit::translated_blocks)
+    }
+
+    /// Counts what the instructions do from now on, see `statistics`. Blocks are still run
+    /// whole, but interpreted rather than translated by the JIT, as native code can't count.
+    pub fn with_statistics(mut self) -> Executor {
+        self.statistics = Some(InstructionMix::default());
+        self
+    }
+
+    /// The instruction mix so far, if the executor was made `with_statistics`.
+    pub fn statistics(&self) -> Option<&InstructionMix> {
+        self.statistics.as_ref()
+    }
+
+    /// Decodes every instruction again each time it runs, for comparison with the cache.
+    pub fn without_decode_cache(mut self) -> Executor {
+        self.memory.decoded = None;
+        self
+    }
+
+    // Programs end by jumping to address zero, which is what returning from `main` does
+    pub fn is_halted(&self) -> bool {
+        self.pc == 0
+    }
+
+    /// Moves the program to `pc`, after which an ebreak it stopped at is no longer stepped
+    /// over.
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc;
+        self.ebreak = None;
+    }
+
+    pub fn step(&mut self) -> Result<(), ExecutionError> {
+        self.step_with(&mut Silent)
+    }
+
+    /// Executes one instruction, reporting what it does to `observer`. Stepping from an
+    /// ebreak the program stopped at only moves past it.
+    pub fn step_with<O: Observer>(&mut self, observer: &mut O) -> Result<(), ExecutionError> {
+        if self.skip_ebreak() {
+            return Ok(());
+        }
+        let result = match self.statistics.take() {
+            Some(mut statistics) => {
+                let result = self.step_watched(&mut (&mut *observer, &mut statistics));
+                self.statistics = Some(statistics);
+                result
+            }
+            None => self.step_watched(observer),
+        };
+        self.stopped(result)
+    }
+
+    fn step_watched<O: Observer>(&mut self, observer: &mut O) -> Result<(), ExecutionError> {
+        if self.watchpoints.is_empty() {
+            return self.execute(observer);
+        }
+        let pc = self.pc;
+        let watchpoints = std::mem::take(&mut self.watchpoints);
+        let mut watcher = Watcher {
+            observer,
+            watchpoints: &watchpoints,
+            hit: None,
+        };
+        let result = self.execute(&mut watcher);
+        let hit = watcher.hit;
+        self.watchpoints = watchpoints;
+        result?;
+        match hit {
+            Some((addr, access)) => Err(ExecutionError::Watchpoint { pc, addr, access }),
+            None => Ok(()),
+        }
+    }
+
+    fn execute<O: Observer>(&mut self, observer: &mut O) -> Result<(), ExecutionError> {
+        let pc = self.pc;
+        let instruction = self.memory.fetch(pc)?;
+        observer.before_instruction(pc, &instruction, &self.registers);
+        run_instruction(
+            &instruction,
+            &mut self.registers,
+            &mut self.pc,
+            &mut self.memory,
+            observer,
+        )?;
+        self.cycles += 1;
+        observer.after_instruction(pc, self.pc, &instruction, &self.registers);
+        if self.is_halted() {
+            observer.halt(&self.registers, self.cycles);
+        }
+        Ok(())
+    }
+
+    /// Runs until the program halts or reaches a breakpoint. Whole basic blocks are executed
+    /// at a time, unless breakpoints or watchpoints need to see every instruction. The
+    /// resulting state is the same as stepping. Resuming from an ebreak the program stopped at
+    /// steps over it.
+    pub fn run(&mut self) -> Result<(), ExecutionError> {
+        let single_step = !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || self.memory.decoded.is_none();
+        self.run_loop(&mut Silent, single_step)
+    }
+
+    /// Like `run`, but steps one instruction at a time to report each to `observer`.
+    pub fn run_with<O: Observer>(&mut self, observer: &mut O) -> Result<(), ExecutionError> {
+        self.run_loop(observer, true)
+    }
+
+    fn run_loop<O: Observer>(
+        &mut self,
+        observer: &mut O,
+        single_step: bool,
+    ) -> Result<(), ExecutionError> {
+        self.skip_ebreak();
+        // The instruction at the breakpoint we may be resuming from is executed
+        let mut resuming = true;
+        while !self.is_halted() && self.cycles < self.cycle_limit {
+            // Whole blocks could overshoot the limit
+            let near_limit = self.cycle_limit - self.cycles < MAX_BLOCK_LENGTH as u64;
+            if !single_step && !near_limit {
+                #[cfg(feature = "jit")]
+                if let Some(jit) = &mut self.jit
+                    && self.statistics.is_none()
+                    && jit.run(
+                        &mut self.pc,
+                        &mut self.cycles,
+                        &mut self.registers,
+                        &mut self.memory,
+                        &mut self.blocks,
+                    )
+                {
+                    continue;
+                }
+                let result = match self.statistics.take() {
+                    Some(mut statistics) => {
+                        let result = self.run_block(&mut (&mut *observer, &mut statistics));
+                        self.statistics = Some(statistics);
+                        result
+                    }
+                    None => self.run_block(observer),
+                };
+                self.stopped(result)?;
+                continue;
+            }
+            if !resuming && self.breakpoints.contains(&self.pc) {
+                break;
+            }
+            resuming = false;
+            self.step_with(observer)?;
+        }
+        Ok(())
+    }
+
+    // Steps over the ebreak the program stopped at, unless the pc was moved since
+    fn skip_ebreak(&mut self) -> bool {
+        let skip = self.ebreak.take() == Some(self.pc);
+        if skip {
+            self.pc = self.pc.wrapping_add(4);
+        }
+        skip
+    }
+
+    // Remembers an ebreak the program stopped at, for resuming to step over
+    fn stopped(&mut self, result: Result<(), ExecutionError>) -> Result<(), ExecutionError> {
+        if let Err(ExecutionError::Breakpoint(pc)) = result {
+            self.ebreak = Some(pc);
+        }
+        result
+    }
+
+    // Reports to `observer` like stepping does
+    fn run_block<O: Observer>(&mut self, observer: &mut O) -> Result<(), ExecutionError> {
+        let block = self.blocks.get(self.pc, &mut self.memory)?;
+        let code_generation = self.memory.code_generation;
+        let mut executed = 0;
+        let mut result = Ok(());
+        for instruction in &block.instructions {
+            let pc = self.pc;
+            observer.before_instruction(pc, instruction, &self.registers);
+            result = run_instruction(
+                instruction,
+                &mut self.registers,
+                &mut self.pc,
+                &mut self.memory,
+                observer,
+            );
+            if result.is_err() {
+                break;
+            }
+            executed += 1;
+            observer.after_instruction(pc, self.pc, instruction, &self.registers);
+            // A store into code makes the rest of the block stale
+            if self.memory.code_generation != code_generation {
+                break;
+            }
+        }
+        self.cycles += executed;
+        if self.is_halted() {
+            observer.halt(&self.registers, self.cycles);
+        }
+        result
+    }
+}
+
+// Passes everything on to `observer`, keeping the first access a watchpoint stops at
+struct Watcher<'a, O: Observer> {
+    observer: &'a mut O,
+    watchpoints: &'a BTreeMap<u32, Watch>,
+    hit: Option<(u32, Watch)>,
 }
 
-fn load_program(instruction_map: BTreeMap<u32, u32>, memory: &mut Memory) {
-    for (addr, instruction) in instruction_map {
-        memory.0.insert(addr, instruction);
+impl<O: Observer> Watcher<'_, O> {
+    fn access(&mut self, addr: u32, is_write: bool) {
+        if self.hit.is_none()
+            && let Some(watch) = self.watchpoints.get(&addr)
+            && watch.covers(is_write)
+        {
+            let access = if is_write { Watch::Write } else { Watch::Read };
+            self.hit = Some((addr, access));
+        }
     }
 }
 
-fn run_from_entrypoint(memory: &mut Memory, entrypoint: u32) -> (i32, i32) {
-    let mut pc = entrypoint;
-    let mut registers = Registers::default();
-    registers.0[2] = 0xFFFFFFFFu32; // 4GB
-    while pc != 0 {
-        let next_instruction = memory.0[&pc];
-        let instruction = Instruction::parse(next_instruction);
-        run_instruction(&instruction, &mut registers, &mut pc, memory);
+impl<O: Observer> Observer for Watcher<'_, O> {
+    fn before_instruction(&mut self, pc: u32, instruction: &Instruction, registers: &Registers) {
+        self.observer.before_instruction(pc, instruction, registers);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        next_pc: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) {
+        self.observer
+            .after_instruction(pc, next_pc, instruction, registers);
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        self.access(addr, false);
+        self.observer.memory_read(addr, value);
+    }
+
+    fn uninitialized_read(&mut self, addr: u32) {
+        self.observer.uninitialized_read(addr);
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        self.access(addr, true);
+        self.observer.memory_write(addr, old_value, value);
+    }
+
+    fn syscall(&mut self, number: u32, registers: &Registers) {
+        self.observer.syscall(number, registers);
+    }
+
+    fn halt(&mut self, registers: &Registers, cycles: u64) {
+        self.observer.halt(registers, cycles);
     }
-    println!("Final Register Values:\n {}", &registers);
-    let return_values = (registers.0[10] as i32, registers.0[11] as i32);
-    println!("Return Values: {return_values:?}");
-    return_values
 }
 
+// Every byte of a word, in the masks of written bytes
+const WHOLE_WORD: u8 = 0b1111;
+
 // Toy Memory, TODO: Make expandable memory
 #[derive(Default, Debug)]
-struct Memory(BTreeMap<u32, u32>);
+pub struct Memory {
+    words: BTreeMap<u32, u32>,
+    // Written bytes of the words that were only partly stored, a bit for each byte. Words
+    // missing here were written whole, if they are in `words`.
+    partial: HashMap<u32, u8>,
+    // Instructions decoded so far, by address. Writing a word drops its entry, so code that
+    // is overwritten is decoded again.
+    decoded: Option<HashMap<u32, Instruction>>,
+    // Counts writes over decoded instructions, so that copies of them can be dropped
+    code_generation: u64,
+}
 
-#[derive(Default, Debug)]
-struct Registers([u32; 32]);
-// Registers:
-// 0x zero
-// a0-ax function arguments: 0x10 -etc
-// 0x1 return address (ra)
-//
-impl Display for Registers {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Registers:")?;
-        writeln!(f, "Zero(zero): {}", self.0[0])?;
-        writeln!(f, "ReturnAddress(ra): {}", self.0[1])?;
-        writeln!(f, "StackPointer(sp): {}", self.0[2])?;
-        // Not used for now
-        // writeln!(f, "GlobalPointer(gp): {}", self.0[2])?;
-        // writeln!(f, "ThreadPointer(tp): {}", self.0[3])?;
-        let function_arguments = self.0[10..17]
-            .iter()
-            .enumerate()
-            .map(|(i, val)| match i {
-                i @ 0..=1 => format!("a{i} (return value {i}) : {val} "),
-                i => format!("a{i}: {val} "),
-            })
-            .collect::<Vec<_>>()
-            .concat();
-        writeln!(f, "FunctionArguments: {function_arguments}")?;
-        // TODO: Add other registers as we use them
+impl Memory {
+    fn new(words: BTreeMap<u32, u32>) -> Memory {
+        Memory {
+            words,
+            partial: HashMap::new(),
+            decoded: Some(HashMap::new()),
+            code_generation: 0,
+        }
+    }
+
+    pub fn read_word(&self, addr: u32) -> Option<u32> {
+        self.words.get(&addr).copied()
+    }
+
+    /// Writes `value` at `addr`, returning the previous word if there was one.
+    pub fn write_word(&mut self, addr: u32, value: u32) -> Option<u32> {
+        let old_value = self.words.insert(addr, value);
+        self.partial.remove(&addr);
+        if let Some(decoded) = &mut self.decoded
+            && decoded.remove(&addr).is_some()
+        {
+            self.code_generation += 1;
+        }
+        old_value
+    }
+
+    pub fn words(&self) -> &BTreeMap<u32, u32> {
+        &self.words
+    }
+
+    // Replaces every word, as going back to an earlier state does. The words count as written
+    // whole, and instructions decoded from the old words are dropped.
+    pub(crate) fn restore(&mut self, words: BTreeMap<u32, u32>) {
+        self.words = words;
+        self.partial.clear();
+        if let Some(decoded) = &mut self.decoded {
+            decoded.clear();
+        }
+        self.code_generation += 1;
+    }
+
+    pub(crate) fn code_generation(&self) -> u64 {
+        self.code_generation
+    }
+
+    pub(crate) fn fetch(&mut self, pc: u32) -> Result<Instruction, ExecutionError> {
+        if let Some(instruction) = self.decoded.as_ref().and_then(|decoded| decoded.get(&pc)) {
+            return Ok(*instruction);
+        }
+        let word = self
+            .read_word(pc)
+            .filter(|_| pc.is_multiple_of(WORD_SIZE))
+            .ok_or(ExecutionError::InvalidPc(pc))?;
+        let instruction = Instruction::parse(word)?;
+        if let Some(decoded) = &mut self.decoded {
+            decoded.insert(pc, instruction);
+        }
+        Ok(instruction)
+    }
+
+    // The bytes of the word at `addr` written so far
+    fn written_bytes(&self, addr: u32) -> u8 {
+        if self.words.contains_key(&addr) {
+            self.partial.get(&addr).copied().unwrap_or(WHOLE_WORD)
+        } else {
+            0
+        }
+    }
+
+    // A read by the program of the word at `addr`, of which it uses `bytes`. Bytes that were
+    // never written read as zero.
+    pub(crate) fn load<O: Observer>(&self, addr: u32, bytes: u8, observer: &mut O) -> u32 {
+        if self.written_bytes(addr) & bytes != bytes {
+            observer.uninitialized_read(addr);
+        }
+        let value = self.read_word(addr).unwrap_or(0);
+        observer.memory_read(addr, value);
+        value
+    }
+
+    // Writes `bytes` of `value` into the word at `addr`, keeping its other bytes
+    pub(crate) fn store<O: Observer>(
+        &mut self,
+        addr: u32,
+        value: u32,
+        bytes: u8,
+        observer: &mut O,
+    ) {
+        let mask = (0..WORD_SIZE)
+            .filter(|byte| bytes & (1 << byte) != 0)
+            .fold(0, |mask, byte| mask | 0xff << (8 * byte));
+        let written = self.written_bytes(addr) | bytes;
+        let old_value = self.read_word(addr).unwrap_or(0);
+        let value = (old_value & !mask) | (value & mask);
+        self.write_word(addr, value);
+        if written != WHOLE_WORD {
+            self.partial.insert(addr, written);
+        }
+        observer.memory_write(addr, old_value, value);
+    }
+
+    // Reads `N` consecutive words starting at `addr`, as syscalls do with guest buffers
+    pub(crate) fn read_words<const N: usize, O: Observer>(
+        &self,
+        addr: u32,
+        observer: &mut O,
+    ) -> Result<[u32; N], ExecutionError> {
+        let mut words = [0; N];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = self.load(buffer_word_addr(addr, i)?, WHOLE_WORD, observer);
+        }
+        Ok(words)
+    }
+
+    pub(crate) fn write_words<O: Observer>(
+        &mut self,
+        addr: u32,
+        words: &[u32],
+        observer: &mut O,
+    ) -> Result<(), ExecutionError> {
+        for (i, word) in words.iter().enumerate() {
+            self.store(buffer_word_addr(addr, i)?, *word, WHOLE_WORD, observer);
+        }
         Ok(())
     }
 }
 
-fn run_instruction(
+fn buffer_word_addr(addr: u32, index: usize) -> Result<u32, ExecutionError> {
+    if !addr.is_multiple_of(WORD_SIZE) {
+        return Err(ExecutionError::InvalidSyscallBuffer(addr));
+    }
+    (index as u32)
+        .checked_mul(WORD_SIZE)
+        .and_then(|offset| addr.checked_add(offset))
+        .ok_or(ExecutionError::InvalidSyscallBuffer(addr))
+}
+
+// The word holding an access of `width` at `addr`, the bit offset of the access in it and the
+// bytes it covers. Accesses never span two words, as they must be aligned, which is checked
+// before memory is touched.
+fn word_access(addr: u32, width: LoadStoreWidth) -> Result<(u32, u32, u8), ExecutionError> {
+    if !addr.is_multiple_of(width.size()) {
+        return Err(ExecutionError::MisalignedAccess(addr));
+    }
+    let offset = addr % WORD_SIZE;
+    let bytes = ((1 << width.size()) - 1) << offset;
+    Ok((addr - offset, 8 * offset, bytes))
+}
+
+fn run_instruction<O: Observer>(
     inst: &Instruction,
     registers: &mut Registers,
     pc: &mut u32,
     memory: &mut Memory,
-) {
-    println!("registers: {:?}", &registers);
-    println!("Executing instruction at 0x{:08x}: {:?}", pc.clone(), inst);
-    *pc += 4;
+    observer: &mut O,
+) -> Result<(), ExecutionError> {
+    // Only written back once the instruction succeeded, so errors leave the pc at it
+    let mut next_pc = pc.wrapping_add(4);
     match inst {
         Instruction::ArithImm { dst, src, imm, op } => {
-            let (a, b) = (registers.0[*src as usize] as i32, *imm);
+            let (a, b) = (registers.read(*src) as i32, *imm);
             let res = match op {
                 ArithOp::Add => a.wrapping_add(b),
-                ArithOp::Sub => panic!("SubImm not supported"),
+                ArithOp::Sub
+                | ArithOp::Mul
+                | ArithOp::MulHigh
+                | ArithOp::MulHighSU
+                | ArithOp::MulHighU
+                | ArithOp::Div
+                | ArithOp::DivU
+                | ArithOp::Rem
+                | ArithOp::RemU => return Err(ExecutionError::UnsupportedInstruction(*inst)),
                 ArithOp::Xor => a ^ b,
                 ArithOp::Or => a | b,
                 ArithOp::And => a & b,
@@ -91,21 +619,17 @@ fn run_instruction(
                 ArithOp::SetLessThan => (a < b) as i32,
                 ArithOp::SetLessThanU => ((a as u32) < (b as u32)) as i32,
             };
-            registers.0[*dst as usize] = res as u32;
+            registers.write(*dst, res as u32);
         }
         Instruction::JumpAndLinkRegister { dst, base, offset } => {
-            let new_pc = (registers.0[*base as usize] as i32 + offset) as u32;
-            if *dst != 0 {
-                registers.0[*dst as usize] = *pc;
-            }
-            *pc = new_pc;
+            // The lowest bit of the target is cleared, as the spec requires
+            let new_pc = registers.read(*base).wrapping_add(*offset as u32) & !1;
+            registers.write(*dst, next_pc);
+            next_pc = new_pc;
         }
         Instruction::JumpAndLink { dst, offset } => {
-            if *dst != 0 {
-                registers.0[*dst as usize] = *pc;
-            }
-            *pc -= 4;
-            *pc = (*pc as i32 + offset) as u32;
+            registers.write(*dst, next_pc);
+            next_pc = pc.wrapping_add(*offset as u32);
         }
         Instruction::Store {
             src,
@@ -113,15 +637,12 @@ fn run_instruction(
             base,
             width,
         } => {
-            let value = registers.0[*src as usize];
-            let value = match width {
-                LoadStoreWidth::Byte => todo!(),
-                LoadStoreWidth::Half => todo!(),
-                LoadStoreWidth::Word => value,
-            };
-            memory
-                .0
-                .insert(registers.0[*base as usize] + *offset, value);
+            if width.is_unsigned() {
+                return Err(ExecutionError::UnsupportedInstruction(*inst));
+            }
+            let addr = registers.read(*base).wrapping_add(*offset as u32);
+            let (word_addr, shift, bytes) = word_access(addr, *width)?;
+            memory.store(word_addr, registers.read(*src) << shift, bytes, observer);
         }
         Instruction::Load {
             dst,
@@ -129,13 +650,17 @@ fn run_instruction(
             base,
             width,
         } => {
-            let value = memory.0[&((registers.0[*base as usize] as i32 + *offset) as u32)];
+            let addr = registers.read(*base).wrapping_add(*offset as u32);
+            let (word_addr, shift, bytes) = word_access(addr, *width)?;
+            let value = memory.load(word_addr, bytes, observer) >> shift;
             let value = match width {
-                LoadStoreWidth::Byte => todo!(),
-                LoadStoreWidth::Half => todo!(),
+                LoadStoreWidth::Byte => value as i8 as u32,
+                LoadStoreWidth::Half => value as i16 as u32,
                 LoadStoreWidth::Word => value,
+                LoadStoreWidth::ByteUnsigned => value as u8 as u32,
+                LoadStoreWidth::HalfUnsigned => value as u16 as u32,
             };
-            registers.0[*dst as usize] = value;
+            registers.write(*dst, value);
         }
         Instruction::Branch {
             src1,
@@ -143,45 +668,68 @@ fn run_instruction(
             cond,
             offset,
         } => {
-            let (a, b) = (registers.0[*src1 as usize], registers.0[*src2 as usize]);
-            let cmp_result = match cond {
-                Comparison::Equal => a == b,
-                Comparison::NotEqual => a != b,
-                Comparison::LessThan => (a as i32) < (b as i32),
-                Comparison::GreaterOrEqual => (a as i32) >= (b as i32),
-                Comparison::LessThanUnsigned => a < b,
-                Comparison::GreaterOrEqualUnsigned => a >= b,
-            };
-            if cmp_result {
-                *pc -= 4;
-                *pc += offset
+            if cond.holds(registers.read(*src1), registers.read(*src2)) {
+                next_pc = pc.wrapping_add(*offset as u32);
             }
         }
-        Instruction::LoadUpperImm { dst, imm } => registers.0[*dst as usize] = *imm,
-        Instruction::AddUpperImmToPc { dst, imm } => registers.0[*dst as usize] = *pc - 4 + *imm,
+        Instruction::LoadUpperImm { dst, imm } => registers.write(*dst, *imm),
+        Instruction::AddUpperImmToPc { dst, imm } => registers.write(*dst, pc.wrapping_add(*imm)),
         Instruction::Arith {
             dst,
             src1,
             src2,
             op,
         } => {
-            let (a, b) = (
-                registers.0[*src1 as usize] as i32,
-                registers.0[*src2 as usize] as i32,
-            );
+            let (a, b) = (registers.read(*src1) as i32, registers.read(*src2) as i32);
+            // Only the low 5 bits of the shift amount are used
+            let shamt = (b & 0x1f) as u32;
             let res = match op {
                 ArithOp::Add => a.wrapping_add(b),
-                ArithOp::Sub => a - b,
+                ArithOp::Sub => a.wrapping_sub(b),
                 ArithOp::Xor => a ^ b,
                 ArithOp::Or => a | b,
                 ArithOp::And => a & b,
-                ArithOp::ShiftLeftLogical => a << b,
-                ArithOp::ShiftRightLogical => ((a as u32) >> (b as u32)) as i32,
-                ArithOp::ShiftRightArith => a >> b,
+                ArithOp::ShiftLeftLogical => a << shamt,
+                ArithOp::ShiftRightLogical => ((a as u32) >> shamt) as i32,
+                ArithOp::ShiftRightArith => a >> shamt,
                 ArithOp::SetLessThan => (a < b) as i32,
                 ArithOp::SetLessThanU => ((a as u32) < (b as u32)) as i32,
+                ArithOp::Mul => a.wrapping_mul(b),
+                ArithOp::MulHigh => ((a as i64 * b as i64) >> 32) as i32,
+                ArithOp::MulHighSU => ((a as i64 * b as u32 as i64) >> 32) as i32,
+                ArithOp::MulHighU => ((a as u32 as u64 * b as u32 as u64) >> 32) as i32,
+                // Division by zero and overflow give the results the spec defines, no trap
+                ArithOp::Div if b == 0 => -1,
+                ArithOp::Div => a.wrapping_div(b),
+                ArithOp::DivU => (a as u32).checked_div(b as u32).unwrap_or(u32::MAX) as i32,
+                ArithOp::Rem if b == 0 => a,
+                ArithOp::Rem => a.wrapping_rem(b),
+                ArithOp::RemU => (a as u32).checked_rem(b as u32).unwrap_or(a as u32) as i32,
             };
-            registers.0[*dst as usize] = res as u32;
+            registers.write(*dst, res as u32);
+        }
+        Instruction::EnvironmentBreak => return Err(ExecutionError::Breakpoint(*pc)),
+        Instruction::EnvironmentCall => {
+            observer.syscall(registers.read(A7), registers);
+            handle_syscall(registers, memory, observer)?
+        }
+        Instruction::Poseidon2Permute { base } => {
+            poseidon2_permute(registers.read(*base), memory, observer)?
+        }
+        Instruction::Uint256Add {
+            dst,
+            x_base,
+            y_base,
+        } => {
+            let carry = uint256_add(
+                registers.read(*x_base),
+                registers.read(*y_base),
+                memory,
+                observer,
+            )?;
+            registers.write(*dst, carry as u32);
         }
     }
+    *pc = next_pc;
+    Ok(())
 }
@@ -0,0 +1,199 @@
+// Time travel for the debugger. Running through a `History` records the execution trace and a
+// snapshot of the state every so often. An earlier state is rebuilt from the closest snapshot
+// before it by replaying the register and memory writes of the trace, without executing
+// anything again.
+
+use std::collections::BTreeMap;
+
+use crate::vm::{
+    execution::{ExecutionError, Executor},
+    instructions::Instruction,
+    observer::Observer,
+    registers::Registers,
+    trace::ExecutionTrace,
+};
+
+// Snapshots copy the whole memory, replaying this many steps takes a few milliseconds
+pub const SNAPSHOT_INTERVAL: u64 = 100_000;
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    cycle: u64,
+    pc: u32,
+    registers: Registers,
+    words: BTreeMap<u32, u32>,
+}
+
+/// The last write to a word, see `History::last_write`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastWrite {
+    // Cycle of the step that wrote, and its instruction
+    pub cycle: u64,
+    pub pc: u32,
+    pub instruction: Instruction,
+    pub value: u32,
+    pub old_value: u32,
+}
+
+/// The steps of an executor since the history was created, which it can be taken back to.
+pub struct History {
+    pub trace: ExecutionTrace,
+    // By cycle, the first one being where the history starts
+    snapshots: Vec<Snapshot>,
+    interval: u64,
+}
+
+impl History {
+    /// Starts recording at the current state of `executor`, with a snapshot every `interval`
+    /// steps, see `SNAPSHOT_INTERVAL`.
+    pub fn new(executor: &Executor, interval: u64) -> History {
+        let mut history = History {
+            trace: ExecutionTrace::default(),
+            snapshots: Vec::new(),
+            interval: interval.max(1),
+        };
+        history.snapshot(executor);
+        history
+    }
+
+    /// Cycle of the earliest state the executor can go back to.
+    pub fn first_cycle(&self) -> u64 {
+        self.snapshots[0].cycle
+    }
+
+    /// Keeps the current state of `executor` as a snapshot. Changes made to the state outside
+    /// of a step are only seen when going back if a snapshot was taken after them.
+    pub fn snapshot(&mut self, executor: &Executor) {
+        let snapshot = Snapshot {
+            cycle: executor.cycles,
+            pc: executor.pc,
+            registers: executor.registers.clone(),
+            words: executor.memory.words().clone(),
+        };
+        match self.snapshots.last_mut() {
+            Some(last) if last.cycle == snapshot.cycle => *last = snapshot,
+            _ => self.snapshots.push(snapshot),
+        }
+    }
+
+    /// Executes one instruction like `Executor::step_with`, recording it.
+    pub fn step<O: Observer>(
+        &mut self,
+        executor: &mut Executor,
+        observer: &mut O,
+    ) -> Result<(), ExecutionError> {
+        if executor.cycles >= self.next_snapshot() {
+            self.snapshot(executor);
+        }
+        executor.step_with(&mut (&mut self.trace, observer))
+    }
+
+    /// Runs like `Executor::run_with`, recording every step and stopping for snapshots on
+    /// the way.
+    pub fn run<O: Observer>(
+        &mut self,
+        executor: &mut Executor,
+        observer: &mut O,
+    ) -> Result<(), ExecutionError> {
+        if executor.cycles >= self.next_snapshot() {
+            self.snapshot(executor);
+        }
+        let cycle_limit = executor.cycle_limit;
+        let result = loop {
+            let next_snapshot = self.next_snapshot();
+            executor.cycle_limit = cycle_limit.min(next_snapshot);
+            let result = executor.run_with(&mut (&mut self.trace, &mut *observer));
+            if result.is_err() || executor.is_halted() || executor.cycles != next_snapshot {
+                break result;
+            }
+            self.snapshot(executor);
+            // Running again would execute the instruction at the breakpoint
+            if executor.breakpoints.contains(&executor.pc) {
+                break Ok(());
+            }
+        };
+        executor.cycle_limit = cycle_limit;
+        result
+    }
+
+    /// Takes `executor` back to its state before the step at `cycle`, or the first state of
+    /// the history if that is earlier. The steps after it are forgotten, running again
+    /// records them anew.
+    pub fn rewind(&mut self, executor: &mut Executor, cycle: u64) {
+        let cycle = cycle.max(self.first_cycle());
+        if cycle >= executor.cycles {
+            return;
+        }
+        let snapshots = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.cycle <= cycle);
+        self.snapshots.truncate(snapshots);
+        let snapshot = self.snapshots[snapshots - 1].clone();
+        let first_step = self.step_index(snapshot.cycle);
+        let last_step = self.step_index(cycle);
+
+        let steps = &self.trace.steps;
+        let mut registers = snapshot.registers;
+        for step in first_step..last_step {
+            if let Some(register) = steps.instruction[step].destination_register() {
+                registers.write(register, steps.rd_value[step]);
+            }
+        }
+        let memory = &self.trace.memory;
+        let mut words = snapshot.words;
+        let first_access = memory
+            .cycle
+            .partition_point(|access_cycle| *access_cycle < first_step as u64);
+        for access in first_access..memory.cycle.len() {
+            if memory.cycle[access] >= last_step as u64 {
+                break;
+            }
+            if memory.is_write[access] {
+                words.insert(memory.addr[access], memory.value[access]);
+            }
+        }
+
+        executor.set_pc(steps.pc.get(last_step).copied().unwrap_or(snapshot.pc));
+        executor.registers = registers;
+        executor.memory.restore(words);
+        executor.cycles = cycle;
+        self.trace.truncate(last_step);
+    }
+
+    /// The last step before the current one that wrote the word at `addr`, if any did since
+    /// the history started. An instruction that failed did not complete, so its writes are
+    /// not counted.
+    pub fn last_write(&self, addr: u32) -> Option<LastWrite> {
+        let memory = &self.trace.memory;
+        (0..memory.cycle.len())
+            .rev()
+            .filter(|access| memory.is_write[*access] && memory.addr[*access] == addr)
+            .find_map(|access| {
+                let step = memory.cycle[access] as usize;
+                Some(LastWrite {
+                    cycle: self.first_cycle() + step as u64,
+                    pc: *self.trace.steps.pc.get(step)?,
+                    instruction: self.trace.steps.instruction[step],
+                    value: memory.value[access],
+                    old_value: memory.old_value[access],
+                })
+            })
+    }
+
+    /// The latest cycle before `cycle` whose instruction is at one of `addresses`.
+    pub fn last_visit(&self, cycle: u64, addresses: impl Fn(u32) -> bool) -> Option<u64> {
+        let steps = &self.trace.steps.pc[..self.step_index(cycle).min(self.trace.len())];
+        steps
+            .iter()
+            .rposition(|pc| addresses(*pc))
+            .map(|step| self.first_cycle() + step as u64)
+    }
+
+    fn next_snapshot(&self) -> u64 {
+        self.snapshots.last().map_or(0, |snapshot| snapshot.cycle) + self.interval
+    }
+
+    fn step_index(&self, cycle: u64) -> usize {
+        cycle.saturating_sub(self.first_cycle()) as usize
+    }
+}
@@ -8,6 +8,10 @@ const JUMP_AND_LINK_REGISTER_OPCCODE: u32 = 0b1100111;
 const JUMP_AND_LINK_OPCCODE: u32 = 0b1101111;
 const LOAD_UPPER_IMM_OPCODE: u32 = 0b0110111;
 const ADD_UPPER_IMM_TO_PC: u32 = 0b0010111;
+const SYSTEM_OPCODE: u32 = 0b1110011;
+// Opcode spaces reserved by the spec for custom extensions, used for VM specific instructions
+const CUSTOM_0_OPCODE: u32 = 0b0001011;
+const CUSTOM_1_OPCODE: u32 = 0b0101011;
 
 enum Opcode {
     Arith,
@@ -19,10 +23,13 @@ enum Opcode {
     JumpAndLink,
     LoadUpperImm,
     AddUpperImmToPc,
+    System,
+    Custom0,
+    Custom1,
 }
 
 impl TryFrom<u32> for Opcode {
-    type Error = ();
+    type Error = DecodeError;
 
     fn try_from(value: u32) -> Result<Self, Self::Error> {
         Ok(match value {
@@ -35,7 +42,10 @@ impl TryFrom<u32> for Opcode {
             JUMP_AND_LINK_OPCCODE => Opcode::JumpAndLink,
             LOAD_UPPER_IMM_OPCODE => Opcode::LoadUpperImm,
             ADD_UPPER_IMM_TO_PC => Opcode::AddUpperImmToPc,
-            _ => panic!("Unknown Opcode: {value}"),
+            SYSTEM_OPCODE => Opcode::System,
+            CUSTOM_0_OPCODE => Opcode::Custom0,
+            CUSTOM_1_OPCODE => Opcode::Custom1,
+            _ => return Err(DecodeError::UnknownOpcode(value)),
         })
     }
 }
@@ -52,8 +62,8 @@ enum InstructionFormat {
 impl Opcode {
     fn instruction_format(&self) -> InstructionFormat {
         match self {
-            &Opcode::Arith => InstructionFormat::R,
-            &Opcode::ArithImm | &Opcode::Load | &Opcode::JumpAndLinkRegister => {
+            &Opcode::Arith | &Opcode::Custom0 | &Opcode::Custom1 => InstructionFormat::R,
+            &Opcode::ArithImm | &Opcode::Load | &Opcode::JumpAndLinkRegister | &Opcode::System => {
                 InstructionFormat::I
             }
             &Opcode::Store => InstructionFormat::S,
@@ -64,7 +74,7 @@ impl Opcode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ArithOp {
     Add,
     Sub,
@@ -76,30 +86,93 @@ pub enum ArithOp {
     ShiftRightArith,
     SetLessThan,
     SetLessThanU,
+    // M extension, which has no immediate forms
+    Mul,
+    MulHigh,
+    MulHighSU,
+    MulHighU,
+    Div,
+    DivU,
+    Rem,
+    RemU,
 }
 
-#[derive(Debug)]
+impl ArithOp {
+    pub fn has_immediate_form(&self) -> bool {
+        !matches!(self, ArithOp::Sub) && !self.is_multiply_or_divide()
+    }
+
+    pub fn is_multiply_or_divide(&self) -> bool {
+        matches!(
+            self,
+            ArithOp::Mul
+                | ArithOp::MulHigh
+                | ArithOp::MulHighSU
+                | ArithOp::MulHighU
+                | ArithOp::Div
+                | ArithOp::DivU
+                | ArithOp::Rem
+                | ArithOp::RemU
+        )
+    }
+}
+
+// Signed widths sign extend loaded values, unsigned ones are only used by loads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LoadStoreWidth {
     Byte,
     Half,
     Word,
+    ByteUnsigned,
+    HalfUnsigned,
 }
 
+const LOAD_STORE_BYTE_WIDTH: u32 = 0x0;
+const LOAD_STORE_HALF_WIDTH: u32 = 0x1;
+const LOAD_STORE_WORD_WIDTH: u32 = 0x2;
+const LOAD_BYTE_UNSIGNED_WIDTH: u32 = 0x4;
+const LOAD_HALF_UNSIGNED_WIDTH: u32 = 0x5;
+
 impl LoadStoreWidth {
-    fn from_func3(func3: u32) -> LoadStoreWidth {
-        const LOAD_STORE_BYTE_WIDTH: u32 = 0x0;
-        const LOAD_STORE_HALF_WIDTH: u32 = 0x1;
-        const LOAD_STORE_WORD_WIDTH: u32 = 0x2;
+    fn from_func3(func3: u32) -> Option<LoadStoreWidth> {
         match func3 {
-            LOAD_STORE_BYTE_WIDTH => LoadStoreWidth::Byte,
-            LOAD_STORE_HALF_WIDTH => LoadStoreWidth::Half,
-            LOAD_STORE_WORD_WIDTH => LoadStoreWidth::Word,
-            _ => panic!("Invalid Width"),
+            LOAD_STORE_BYTE_WIDTH => Some(LoadStoreWidth::Byte),
+            LOAD_STORE_HALF_WIDTH => Some(LoadStoreWidth::Half),
+            LOAD_STORE_WORD_WIDTH => Some(LoadStoreWidth::Word),
+            LOAD_BYTE_UNSIGNED_WIDTH => Some(LoadStoreWidth::ByteUnsigned),
+            LOAD_HALF_UNSIGNED_WIDTH => Some(LoadStoreWidth::HalfUnsigned),
+            _ => None,
+        }
+    }
+
+    fn func3(&self) -> u32 {
+        match self {
+            LoadStoreWidth::Byte => LOAD_STORE_BYTE_WIDTH,
+            LoadStoreWidth::Half => LOAD_STORE_HALF_WIDTH,
+            LoadStoreWidth::Word => LOAD_STORE_WORD_WIDTH,
+            LoadStoreWidth::ByteUnsigned => LOAD_BYTE_UNSIGNED_WIDTH,
+            LoadStoreWidth::HalfUnsigned => LOAD_HALF_UNSIGNED_WIDTH,
+        }
+    }
+
+    /// Number of bytes accessed.
+    pub fn size(&self) -> u32 {
+        match self {
+            LoadStoreWidth::Byte | LoadStoreWidth::ByteUnsigned => 1,
+            LoadStoreWidth::Half | LoadStoreWidth::HalfUnsigned => 2,
+            LoadStoreWidth::Word => 4,
         }
     }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            LoadStoreWidth::ByteUnsigned | LoadStoreWidth::HalfUnsigned
+        )
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Comparison {
     Equal,
     NotEqual,
@@ -109,7 +182,21 @@ pub enum Comparison {
     GreaterOrEqualUnsigned,
 }
 
-#[derive(Debug)]
+impl Comparison {
+    /// Whether a branch comparing `a` with `b` is taken.
+    pub fn holds(&self, a: u32, b: u32) -> bool {
+        match self {
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+            Comparison::LessThan => (a as i32) < (b as i32),
+            Comparison::GreaterOrEqual => (a as i32) >= (b as i32),
+            Comparison::LessThanUnsigned => a < b,
+            Comparison::GreaterOrEqualUnsigned => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Arith {
         dst: u32,
@@ -134,7 +221,7 @@ pub enum Instruction {
     },
     Store {
         src: u32,
-        offset: u32,
+        offset: i32,
         base: u32,
         width: LoadStoreWidth,
     },
@@ -148,7 +235,7 @@ pub enum Instruction {
         src1: u32,
         src2: u32,
         cond: Comparison,
-        offset: u32,
+        offset: i32,
     },
     LoadUpperImm {
         dst: u32,
@@ -158,6 +245,21 @@ pub enum Instruction {
         dst: u32,
         imm: u32,
     },
+    EnvironmentCall,
+    // Hands control to a debugger, see `ExecutionError::Breakpoint`
+    EnvironmentBreak,
+    // Applies the Poseidon2 permutation to the state at the address held in `base`,
+    // like the syscall but without the register marshalling.
+    Poseidon2Permute {
+        base: u32,
+    },
+    // Adds the 256-bit integers at the addresses held in `x_base` and `y_base`, storing
+    // the sum modulo 2^256 over x and the carry out in `dst`.
+    Uint256Add {
+        dst: u32,
+        x_base: u32,
+        y_base: u32,
+    },
 }
 
 const OPCODE_MASK: u32 = 0x0000007f;
@@ -170,9 +272,33 @@ const SIGN_MASK: u32 = 0x80000000;
 const I_TYPE_IMM_MASK: u32 = 0x7ff;
 const U_TYPE_IMM_MASK: u32 = 0xfffff000;
 
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("Unknown opcode: 0x{0:02x}")]
+    UnknownOpcode(u32),
+    #[error("Invalid instruction encoding: 0x{0:08x}")]
+    InvalidEncoding(u32),
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EncodeError {
+    #[error("Invalid register: x{0}")]
+    InvalidRegister(u32),
+    #[error("Immediate {0} does not fit in the instruction")]
+    ImmediateOutOfRange(i64),
+    #[error("Offset {0} is not a multiple of 2")]
+    MisalignedOffset(i32),
+    #[error("Upper immediate 0x{0:08x} has non-zero low 12 bits")]
+    UnalignedUpperImmediate(u32),
+    #[error("Operation {0:?} has no immediate form")]
+    NoImmediateForm(ArithOp),
+    #[error("Width {0:?} has no store form")]
+    NoStoreForm(LoadStoreWidth),
+}
+
 impl Instruction {
-    pub fn parse(instruction: u32) -> Instruction {
-        let opcode = parse_opcode(instruction);
+    pub fn parse(instruction: u32) -> Result<Instruction, DecodeError> {
+        let opcode = parse_opcode(instruction)?;
         match opcode.instruction_format() {
             InstructionFormat::R => parse_r_instruction(instruction, opcode),
             InstructionFormat::I => parse_i_instruction(instruction, opcode),
@@ -182,11 +308,50 @@ impl Instruction {
             InstructionFormat::U => parse_u_instruction(instruction, opcode),
         }
     }
+
+    /// Registers read as rs1 and rs2. Syscalls read a7, a0 and a1, but have no operands.
+    pub fn source_registers(&self) -> (Option<u32>, Option<u32>) {
+        match *self {
+            Instruction::Arith { src1, src2, .. } | Instruction::Branch { src1, src2, .. } => {
+                (Some(src1), Some(src2))
+            }
+            Instruction::Store { src, base, .. } => (Some(base), Some(src)),
+            Instruction::Uint256Add { x_base, y_base, .. } => (Some(x_base), Some(y_base)),
+            Instruction::ArithImm { src, .. } => (Some(src), None),
+            Instruction::JumpAndLinkRegister { base, .. }
+            | Instruction::Load { base, .. }
+            | Instruction::Poseidon2Permute { base } => (Some(base), None),
+            Instruction::JumpAndLink { .. }
+            | Instruction::LoadUpperImm { .. }
+            | Instruction::AddUpperImmToPc { .. }
+            | Instruction::EnvironmentCall
+            | Instruction::EnvironmentBreak => (None, None),
+        }
+    }
+
+    /// Register written as rd, which may be x0.
+    pub fn destination_register(&self) -> Option<u32> {
+        match *self {
+            Instruction::Arith { dst, .. }
+            | Instruction::ArithImm { dst, .. }
+            | Instruction::JumpAndLink { dst, .. }
+            | Instruction::JumpAndLinkRegister { dst, .. }
+            | Instruction::Load { dst, .. }
+            | Instruction::LoadUpperImm { dst, .. }
+            | Instruction::AddUpperImmToPc { dst, .. }
+            | Instruction::Uint256Add { dst, .. } => Some(dst),
+            Instruction::Store { .. }
+            | Instruction::Branch { .. }
+            | Instruction::EnvironmentCall
+            | Instruction::EnvironmentBreak
+            | Instruction::Poseidon2Permute { .. } => None,
+        }
+    }
 }
 
-fn parse_opcode(instruction: u32) -> Opcode {
+fn parse_opcode(instruction: u32) -> Result<Opcode, DecodeError> {
     let opcode = instruction & OPCODE_MASK;
-    Opcode::try_from(opcode).unwrap()
+    Opcode::try_from(opcode)
 }
 
 // Function Identifiers (func7 & func3)
@@ -200,11 +365,23 @@ const SRL_FUNC_IDENTIFIERS: (u32, u32) = (0x5, 0x00);
 const SRA_FUNC_IDENTIFIERS: (u32, u32) = (0x5, 0x20);
 const SLT_FUNC_IDENTIFIERS: (u32, u32) = (0x2, 0x00);
 const SLTU_FUNC_IDENTIFIERS: (u32, u32) = (0x3, 0x00);
+const MUL_FUNC_IDENTIFIERS: (u32, u32) = (0x0, 0x01);
+const MULH_FUNC_IDENTIFIERS: (u32, u32) = (0x1, 0x01);
+const MULHSU_FUNC_IDENTIFIERS: (u32, u32) = (0x2, 0x01);
+const MULHU_FUNC_IDENTIFIERS: (u32, u32) = (0x3, 0x01);
+const DIV_FUNC_IDENTIFIERS: (u32, u32) = (0x4, 0x01);
+const DIVU_FUNC_IDENTIFIERS: (u32, u32) = (0x5, 0x01);
+const REM_FUNC_IDENTIFIERS: (u32, u32) = (0x6, 0x01);
+const REMU_FUNC_IDENTIFIERS: (u32, u32) = (0x7, 0x01);
+// custom-0
+const POSEIDON2_PERMUTE_FUNC_IDENTIFIERS: (u32, u32) = (0x0, 0x00);
+// custom-1
+const UINT256_ADD_FUNC_IDENTIFIERS: (u32, u32) = (0x0, 0x00);
 
 // R-Type Instruction Format
 // |func7 | rs2  | rs1  |funct3|  rd |opcode|
 // |31..25|24..20|19..15|14..12|11..7| 6..0 |
-fn parse_r_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_r_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let func7 = (instruction & FUNC7_MASK) >> 25;
     let func3 = (instruction & FUNC3_MASK) >> 12;
     let rs2 = (instruction & RS2_MASK) >> 20;
@@ -223,16 +400,39 @@ fn parse_r_instruction(instruction: u32, opcode: Opcode) -> Instruction {
                 SRA_FUNC_IDENTIFIERS => ArithOp::ShiftRightArith,
                 SLT_FUNC_IDENTIFIERS => ArithOp::SetLessThan,
                 SLTU_FUNC_IDENTIFIERS => ArithOp::SetLessThanU,
-                _ => panic!("Unknown  arith opcode identifier"),
+                MUL_FUNC_IDENTIFIERS => ArithOp::Mul,
+                MULH_FUNC_IDENTIFIERS => ArithOp::MulHigh,
+                MULHSU_FUNC_IDENTIFIERS => ArithOp::MulHighSU,
+                MULHU_FUNC_IDENTIFIERS => ArithOp::MulHighU,
+                DIV_FUNC_IDENTIFIERS => ArithOp::Div,
+                DIVU_FUNC_IDENTIFIERS => ArithOp::DivU,
+                REM_FUNC_IDENTIFIERS => ArithOp::Rem,
+                REMU_FUNC_IDENTIFIERS => ArithOp::RemU,
+                _ => return Err(DecodeError::InvalidEncoding(instruction)),
             };
-            Instruction::Arith {
+            Ok(Instruction::Arith {
                 dst: rd,
                 src1: rs1,
                 src2: rs2,
                 op: operation,
-            }
+            })
         }
-        _ => panic!("Invalid Instruction Encoding"),
+        Opcode::Custom0 => match (func3, func7) {
+            // rd and rs2 are unused and must be zero, so that every word decodes uniquely
+            POSEIDON2_PERMUTE_FUNC_IDENTIFIERS if rd == 0 && rs2 == 0 => {
+                Ok(Instruction::Poseidon2Permute { base: rs1 })
+            }
+            _ => Err(DecodeError::InvalidEncoding(instruction)),
+        },
+        Opcode::Custom1 => match (func3, func7) {
+            UINT256_ADD_FUNC_IDENTIFIERS => Ok(Instruction::Uint256Add {
+                dst: rd,
+                x_base: rs1,
+                y_base: rs2,
+            }),
+            _ => Err(DecodeError::InvalidEncoding(instruction)),
+        },
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
     }
 }
 
@@ -246,10 +446,14 @@ const SR_FUNC_IDENTIFIER: u32 = 0x5;
 const SLT_FUNC_IDENTIFIER: u32 = 0x2;
 const SLTU_FUNC_IDENTIFIER: u32 = 0x3;
 
+// Function Identifiers (func3 & imm)
+const ECALL_FUNC_IDENTIFIERS: (u32, i32) = (0x0, 0x000);
+const EBREAK_FUNC_IDENTIFIERS: (u32, i32) = (0x0, 0x001);
+
 // I-Type Instruction Format
 // | imm  | rs1  |funct3|  rd |opcode|
 // |31..20|19..15|14..12|11..7| 6..0 |
-fn parse_i_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_i_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let func3 = (instruction & FUNC3_MASK) >> 12;
     let rs1 = (instruction & RS1_MASK) >> 15;
     let imm = ((instruction >> 20) & I_TYPE_IMM_MASK) as i32;
@@ -268,7 +472,9 @@ fn parse_i_instruction(instruction: u32, opcode: Opcode) -> Instruction {
                 OR_FUNC_IDENTIFIER => ArithOp::Or,
                 AND_FUNC_IDENTIFIER => ArithOp::And,
                 SHL_FUNC_IDENTIFIER => {
-                    assert!(imm >> 5 == 0);
+                    if imm >> 5 != 0 {
+                        return Err(DecodeError::InvalidEncoding(instruction));
+                    }
                     imm &= 0x1F;
                     ArithOp::ShiftLeftLogical
                 }
@@ -278,37 +484,45 @@ fn parse_i_instruction(instruction: u32, opcode: Opcode) -> Instruction {
                     match func_id {
                         0x00 => ArithOp::ShiftRightLogical,
                         0x20 => ArithOp::ShiftRightArith,
-                        _ => unimplemented!(),
+                        _ => return Err(DecodeError::InvalidEncoding(instruction)),
                     }
                 }
                 SLT_FUNC_IDENTIFIER => ArithOp::SetLessThan,
                 SLTU_FUNC_IDENTIFIER => ArithOp::SetLessThanU,
-                _ => panic!("Unknown  arith opcode identifier"),
+                _ => return Err(DecodeError::InvalidEncoding(instruction)),
             };
-            Instruction::ArithImm {
+            Ok(Instruction::ArithImm {
                 dst: rd,
                 src: rs1,
                 imm,
                 op: operation,
-            }
+            })
         }
         Opcode::JumpAndLinkRegister => {
             if func3 != 0x00 {
-                panic!("Invalid JALR Instruction")
+                return Err(DecodeError::InvalidEncoding(instruction));
             };
-            Instruction::JumpAndLinkRegister {
+            Ok(Instruction::JumpAndLinkRegister {
                 base: rs1,
                 dst: rd,
                 offset: imm,
-            }
+            })
         }
-        Opcode::Load => Instruction::Load {
+        Opcode::Load => Ok(Instruction::Load {
             dst: rd,
             offset: imm,
             base: rs1,
-            width: LoadStoreWidth::from_func3(func3),
+            width: LoadStoreWidth::from_func3(func3)
+                .ok_or(DecodeError::InvalidEncoding(instruction))?,
+        }),
+        // rd and rs1 are unused and must be zero
+        Opcode::System if rd != 0 || rs1 != 0 => Err(DecodeError::InvalidEncoding(instruction)),
+        Opcode::System => match (func3, imm) {
+            ECALL_FUNC_IDENTIFIERS => Ok(Instruction::EnvironmentCall),
+            EBREAK_FUNC_IDENTIFIERS => Ok(Instruction::EnvironmentBreak),
+            _ => Err(DecodeError::InvalidEncoding(instruction)),
         },
-        _ => panic!("Invalid Instruction Encoding"),
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
     }
 }
 
@@ -316,21 +530,28 @@ fn parse_i_instruction(instruction: u32, opcode: Opcode) -> Instruction {
 // imm[11:5] rs2 rs1 funct3 imm[4:0] opcode
 // |imm[11:5]| rs2  | rs1  |funct3|imm[4:0]|opcode|
 // | 31..25  |24..20|19..15|14..12| 11..7  | 6..0 |
-fn parse_s_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_s_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let func7 = (instruction & FUNC7_MASK) >> 25;
     let func3 = (instruction & FUNC3_MASK) >> 12;
     let rs2 = (instruction & RS2_MASK) >> 20;
     let rs1 = (instruction & RS1_MASK) >> 15;
     let rd = (instruction & RD_MASK) >> 7;
-    let imm = func7 | rd;
+    let imm = ((func7 << 5) | rd) as i32;
+    let imm = if (instruction & SIGN_MASK) != 0 {
+        imm - (1 << 12)
+    } else {
+        imm
+    };
     match opcode {
-        Opcode::Store => Instruction::Store {
+        Opcode::Store => Ok(Instruction::Store {
             src: rs2,
             offset: imm,
             base: rs1,
-            width: LoadStoreWidth::from_func3(func3),
-        },
-        _ => panic!("Invalid Instruction Encoding"),
+            width: LoadStoreWidth::from_func3(func3)
+                .filter(|width| !width.is_unsigned())
+                .ok_or(DecodeError::InvalidEncoding(instruction))?,
+        }),
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
     }
 }
 
@@ -345,12 +566,17 @@ const BRANCH_GTU_IDENTIFIER: u32 = 0x7;
 // B-Type Instruction Format
 // |imm[12|10:5]| rs2  | rs1  |funct3|imm[4:1|11]|opcode|
 // |    31..25  |24..20|19..15|14..12|  11..7    | 6..0 |
-fn parse_b_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_b_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let func3 = (instruction & FUNC3_MASK) >> 12;
     let rs2 = (instruction & RS2_MASK) >> 20;
     let rs1 = (instruction & RS1_MASK) >> 15;
     let imm =
         ((instruction >> 20) & 0x7e0) | ((instruction >> 7) & 0x1e) | ((instruction & 0x80) << 4);
+    let imm: i32 = if (instruction & SIGN_MASK) != 0 {
+        imm as i32 - (1 << 12)
+    } else {
+        imm as i32
+    };
     match opcode {
         Opcode::Branch => {
             let comparison = match func3 {
@@ -360,23 +586,23 @@ fn parse_b_instruction(instruction: u32, opcode: Opcode) -> Instruction {
                 BRANCH_GE_IDENTIFIER => Comparison::GreaterOrEqual,
                 BRANCH_LTU_IDENTIFIER => Comparison::LessThanUnsigned,
                 BRANCH_GTU_IDENTIFIER => Comparison::GreaterOrEqualUnsigned,
-                _ => unimplemented!(),
+                _ => return Err(DecodeError::InvalidEncoding(instruction)),
             };
-            Instruction::Branch {
+            Ok(Instruction::Branch {
                 src1: rs1,
                 src2: rs2,
                 cond: comparison,
                 offset: imm,
-            }
+            })
         }
-        _ => panic!("Unknown Opcode"),
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
     }
 }
 
 // J-Type Instruction Format
 // |imm[20|10:1|11|19:12] | rd  |opcode|
 // |         31..12       |11..7| 6..0 |
-fn parse_j_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_j_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let imm =
         instruction & 0xff000 | ((instruction & 0x100000) >> 9) | ((instruction >> 20) & 0x7fe);
     let imm: i32 = if (instruction & SIGN_MASK) != 0 {
@@ -386,23 +612,256 @@ fn parse_j_instruction(instruction: u32, opcode: Opcode) -> Instruction {
     };
     let rd = (instruction & RD_MASK) >> 7;
     match opcode {
-        Opcode::JumpAndLink => Instruction::JumpAndLink {
+        Opcode::JumpAndLink => Ok(Instruction::JumpAndLink {
             dst: rd,
             offset: imm,
-        },
-        _ => unimplemented!(),
+        }),
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
     }
 }
 
 // U-Type Instruction Format
 // |imm[31:12] | rd  |opcode|
 // | 31..12    |11..7| 6..0 |
-fn parse_u_instruction(instruction: u32, opcode: Opcode) -> Instruction {
+fn parse_u_instruction(instruction: u32, opcode: Opcode) -> Result<Instruction, DecodeError> {
     let imm = instruction & U_TYPE_IMM_MASK;
     let rd = (instruction & RD_MASK) >> 7;
     match opcode {
-        Opcode::LoadUpperImm => Instruction::LoadUpperImm { dst: rd, imm },
-        Opcode::AddUpperImmToPc => Instruction::AddUpperImmToPc { dst: rd, imm },
-        _ => unimplemented!(),
+        Opcode::LoadUpperImm => Ok(Instruction::LoadUpperImm { dst: rd, imm }),
+        Opcode::AddUpperImmToPc => Ok(Instruction::AddUpperImmToPc { dst: rd, imm }),
+        _ => Err(DecodeError::InvalidEncoding(instruction)),
+    }
+}
+
+impl Instruction {
+    /// Encodes the instruction into its machine word, the inverse of `parse`.
+    /// Panics if a register or immediate does not fit, see `try_encode`.
+    pub fn encode(&self) -> u32 {
+        self.try_encode()
+            .unwrap_or_else(|error| panic!("Cannot encode {self:?}: {error}"))
+    }
+
+    pub fn try_encode(&self) -> Result<u32, EncodeError> {
+        match *self {
+            Instruction::Arith {
+                dst,
+                src1,
+                src2,
+                op,
+            } => {
+                let (func3, func7) = arith_func_identifiers(op);
+                encode_r_instruction(ARITH_OPCODE, dst, func3, src1, src2, func7)
+            }
+            Instruction::ArithImm { dst, src, imm, op } => {
+                let (func3, imm) = match op {
+                    ArithOp::ShiftLeftLogical
+                    | ArithOp::ShiftRightLogical
+                    | ArithOp::ShiftRightArith => {
+                        if !(0..32).contains(&imm) {
+                            return Err(EncodeError::ImmediateOutOfRange(imm as i64));
+                        }
+                        let (func3, func7) = arith_func_identifiers(op);
+                        (func3, ((func7 << 5) as i32) | imm)
+                    }
+                    _ if !op.has_immediate_form() => return Err(EncodeError::NoImmediateForm(op)),
+                    _ => (arith_func_identifiers(op).0, imm),
+                };
+                encode_i_instruction(ARITH_IMM_OPCODE, dst, func3, src, imm)
+            }
+            Instruction::JumpAndLink { dst, offset } => {
+                encode_j_instruction(JUMP_AND_LINK_OPCCODE, dst, offset)
+            }
+            Instruction::JumpAndLinkRegister { base, dst, offset } => {
+                encode_i_instruction(JUMP_AND_LINK_REGISTER_OPCCODE, dst, 0x0, base, offset)
+            }
+            Instruction::Store {
+                src,
+                offset,
+                base,
+                width,
+            } => {
+                if width.is_unsigned() {
+                    return Err(EncodeError::NoStoreForm(width));
+                }
+                encode_s_instruction(STORE_OPCODE, width.func3(), base, src, offset)
+            }
+            Instruction::Load {
+                dst,
+                offset,
+                base,
+                width,
+            } => encode_i_instruction(LOAD_OPCODE, dst, width.func3(), base, offset),
+            Instruction::Branch {
+                src1,
+                src2,
+                cond,
+                offset,
+            } => {
+                let func3 = match cond {
+                    Comparison::Equal => BRANCH_EQ_IDENTIFIER,
+                    Comparison::NotEqual => BRANCH_NEQ_IDENTIFIER,
+                    Comparison::LessThan => BRANCH_LT_IDENTIFIER,
+                    Comparison::GreaterOrEqual => BRANCH_GE_IDENTIFIER,
+                    Comparison::LessThanUnsigned => BRANCH_LTU_IDENTIFIER,
+                    Comparison::GreaterOrEqualUnsigned => BRANCH_GTU_IDENTIFIER,
+                };
+                encode_b_instruction(BRANCH_OPCODE, func3, src1, src2, offset)
+            }
+            Instruction::LoadUpperImm { dst, imm } => {
+                encode_u_instruction(LOAD_UPPER_IMM_OPCODE, dst, imm)
+            }
+            Instruction::AddUpperImmToPc { dst, imm } => {
+                encode_u_instruction(ADD_UPPER_IMM_TO_PC, dst, imm)
+            }
+            Instruction::EnvironmentCall => {
+                let (func3, imm) = ECALL_FUNC_IDENTIFIERS;
+                encode_i_instruction(SYSTEM_OPCODE, 0, func3, 0, imm)
+            }
+            Instruction::EnvironmentBreak => {
+                let (func3, imm) = EBREAK_FUNC_IDENTIFIERS;
+                encode_i_instruction(SYSTEM_OPCODE, 0, func3, 0, imm)
+            }
+            Instruction::Poseidon2Permute { base } => {
+                let (func3, func7) = POSEIDON2_PERMUTE_FUNC_IDENTIFIERS;
+                encode_r_instruction(CUSTOM_0_OPCODE, 0, func3, base, 0, func7)
+            }
+            Instruction::Uint256Add {
+                dst,
+                x_base,
+                y_base,
+            } => {
+                let (func3, func7) = UINT256_ADD_FUNC_IDENTIFIERS;
+                encode_r_instruction(CUSTOM_1_OPCODE, dst, func3, x_base, y_base, func7)
+            }
+        }
+    }
+}
+
+fn arith_func_identifiers(op: ArithOp) -> (u32, u32) {
+    match op {
+        ArithOp::Add => ADD_FUNC_IDENTIFIERS,
+        ArithOp::Sub => SUB_FUNC_IDENTIFIERS,
+        ArithOp::Xor => XOR_FUNC_IDENTIFIERS,
+        ArithOp::Or => OR_FUNC_IDENTIFIERS,
+        ArithOp::And => AND_FUNC_IDENTIFIERS,
+        ArithOp::ShiftLeftLogical => SHL_FUNC_IDENTIFIERS,
+        ArithOp::ShiftRightLogical => SRL_FUNC_IDENTIFIERS,
+        ArithOp::ShiftRightArith => SRA_FUNC_IDENTIFIERS,
+        ArithOp::SetLessThan => SLT_FUNC_IDENTIFIERS,
+        ArithOp::SetLessThanU => SLTU_FUNC_IDENTIFIERS,
+        ArithOp::Mul => MUL_FUNC_IDENTIFIERS,
+        ArithOp::MulHigh => MULH_FUNC_IDENTIFIERS,
+        ArithOp::MulHighSU => MULHSU_FUNC_IDENTIFIERS,
+        ArithOp::MulHighU => MULHU_FUNC_IDENTIFIERS,
+        ArithOp::Div => DIV_FUNC_IDENTIFIERS,
+        ArithOp::DivU => DIVU_FUNC_IDENTIFIERS,
+        ArithOp::Rem => REM_FUNC_IDENTIFIERS,
+        ArithOp::RemU => REMU_FUNC_IDENTIFIERS,
+    }
+}
+
+fn check_register(register: u32) -> Result<u32, EncodeError> {
+    if register < 32 {
+        Ok(register)
+    } else {
+        Err(EncodeError::InvalidRegister(register))
+    }
+}
+
+// Checks that `imm` fits in a `bits` wide two's complement field and returns its raw bits.
+fn check_signed_immediate(imm: i32, bits: u32) -> Result<u32, EncodeError> {
+    let bound = 1i64 << (bits - 1);
+    if (-bound..bound).contains(&(imm as i64)) {
+        Ok(imm as u32 & ((1 << bits) - 1))
+    } else {
+        Err(EncodeError::ImmediateOutOfRange(imm as i64))
+    }
+}
+
+fn check_even_offset(offset: i32, bits: u32) -> Result<u32, EncodeError> {
+    if offset % 2 != 0 {
+        return Err(EncodeError::MisalignedOffset(offset));
+    }
+    check_signed_immediate(offset, bits)
+}
+
+fn encode_r_instruction(
+    opcode: u32,
+    rd: u32,
+    func3: u32,
+    rs1: u32,
+    rs2: u32,
+    func7: u32,
+) -> Result<u32, EncodeError> {
+    Ok(func7 << 25
+        | check_register(rs2)? << 20
+        | check_register(rs1)? << 15
+        | func3 << 12
+        | check_register(rd)? << 7
+        | opcode)
+}
+
+fn encode_i_instruction(
+    opcode: u32,
+    rd: u32,
+    func3: u32,
+    rs1: u32,
+    imm: i32,
+) -> Result<u32, EncodeError> {
+    Ok(check_signed_immediate(imm, 12)? << 20
+        | check_register(rs1)? << 15
+        | func3 << 12
+        | check_register(rd)? << 7
+        | opcode)
+}
+
+fn encode_s_instruction(
+    opcode: u32,
+    func3: u32,
+    rs1: u32,
+    rs2: u32,
+    imm: i32,
+) -> Result<u32, EncodeError> {
+    let imm = check_signed_immediate(imm, 12)?;
+    Ok((imm >> 5) << 25
+        | check_register(rs2)? << 20
+        | check_register(rs1)? << 15
+        | func3 << 12
+        | (imm & 0x1f) << 7
+        | opcode)
+}
+
+fn encode_b_instruction(
+    opcode: u32,
+    func3: u32,
+    rs1: u32,
+    rs2: u32,
+    offset: i32,
+) -> Result<u32, EncodeError> {
+    let imm = check_even_offset(offset, 13)?;
+    Ok((imm >> 12) << 31
+        | ((imm >> 5) & 0x3f) << 25
+        | check_register(rs2)? << 20
+        | check_register(rs1)? << 15
+        | func3 << 12
+        | ((imm >> 1) & 0xf) << 8
+        | ((imm >> 11) & 0x1) << 7
+        | opcode)
+}
+
+fn encode_j_instruction(opcode: u32, rd: u32, offset: i32) -> Result<u32, EncodeError> {
+    let imm = check_even_offset(offset, 21)?;
+    Ok((imm >> 20) << 31
+        | ((imm >> 1) & 0x3ff) << 21
+        | ((imm >> 11) & 0x1) << 20
+        | imm & 0xff000
+        | check_register(rd)? << 7
+        | opcode)
+}
+
+fn encode_u_instruction(opcode: u32, rd: u32, imm: u32) -> Result<u32, EncodeError> {
+    if imm & !U_TYPE_IMM_MASK != 0 {
+        return Err(EncodeError::UnalignedUpperImmediate(imm));
     }
+    Ok(imm | check_register(rd)? << 7 | opcode)
 }
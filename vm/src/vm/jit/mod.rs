@@ -0,0 +1,170 @@
+// Translation of hot basic blocks to host machine code, enabled with the `jit` feature.
+//
+// Translated blocks work directly on the executor's registers and call back into `Memory` for
+// loads and stores, so they can be freely mixed with interpreted ones. The translation covers
+// the RV32I register and immediate arithmetic, aligned word loads and stores, jumps and
+// branches. Everything else, including the M extension, is interpreted. There is only an
+// x86-64 backend, on other hosts every block is interpreted.
+
+use hashbrown::HashMap;
+
+use crate::vm::{blocks::BlockCache, execution::Memory, registers::Registers};
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+type BlockFunction = unsafe extern "C" fn(*mut u32, *mut Memory) -> u64;
+
+struct NativeBlock {
+    code: ExecutableCode,
+}
+
+impl NativeBlock {
+    fn run(&self, registers: &mut Registers, memory: &mut Memory) -> (u32, u64) {
+        // SAFETY: the code was generated by `translate` for this signature, and only accesses
+        // the 32 registers and the memory through the helpers below
+        let result = unsafe {
+            let function: BlockFunction = std::mem::transmute(self.code.ptr);
+            function(registers.as_mut_ptr(), memory)
+        };
+        (result as u32, result >> 32)
+    }
+}
+
+pub(crate) struct Jit {
+    // `None` for blocks that start with an instruction that cannot be translated
+    translations: HashMap<u32, Option<NativeBlock>>,
+    hits: HashMap<u32, u32>,
+    hot_threshold: u32,
+    code_generation: u64,
+}
+
+impl Jit {
+    pub(crate) fn new(hot_threshold: u32) -> Jit {
+        Jit {
+            translations: HashMap::new(),
+            hits: HashMap::new(),
+            hot_threshold,
+            code_generation: 0,
+        }
+    }
+
+    pub(crate) fn translated_blocks(&self) -> usize {
+        self.translations
+            .values()
+            .filter(|native| native.is_some())
+            .count()
+    }
+
+    /// Runs the block at `pc` natively if it is hot enough to be translated. Returns `false`
+    /// if it has to be interpreted instead.
+    pub(crate) fn run(
+        &mut self,
+        pc: &mut u32,
+        cycles: &mut u64,
+        registers: &mut Registers,
+        memory: &mut Memory,
+        blocks: &mut BlockCache,
+    ) -> bool {
+        if self.code_generation != memory.code_generation() {
+            self.translations.clear();
+            self.hits.clear();
+            self.code_generation = memory.code_generation();
+        }
+        if !self.translations.contains_key(pc) {
+            let hits = self.hits.entry(*pc).or_default();
+            *hits += 1;
+            if *hits < self.hot_threshold {
+                return false;
+            }
+            // Errors are left for the interpreter to report
+            let Ok(block) = blocks.get(*pc, memory) else {
+                return false;
+            };
+            let native = translate(*pc, &block.instructions)
+                .and_then(|code| ExecutableCode::new(&code))
+                .map(|code| NativeBlock { code });
+            self.translations.insert(*pc, native);
+        }
+        let Some(Some(native)) = self.translations.get(pc) else {
+            return false;
+        };
+        let (next_pc, executed) = native.run(registers, memory);
+        *pc = next_pc;
+        *cycles += executed;
+        // Nothing ran if the first instruction turned out to need the interpreter
+        executed != 0
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+use x86_64::translate;
+
+#[cfg(not(target_arch = "x86_64"))]
+fn translate(
+    _start: u32,
+    _instructions: &[crate::vm::instructions::Instruction],
+) -> Option<Vec<u8>> {
+    None
+}
+
+// Loads and stores of translated code, which only supports aligned whole words
+#[cfg(target_arch = "x86_64")]
+extern "C" fn load_word(memory: *mut Memory, addr: u32) -> u32 {
+    // SAFETY: called by translated code with the memory it was given, which is not borrowed
+    // elsewhere while it runs
+    let memory = unsafe { &*memory };
+    memory.read_word(addr).unwrap_or(0)
+}
+
+// Returns whether a decoded instruction was overwritten
+#[cfg(target_arch = "x86_64")]
+extern "C" fn store_word(memory: *mut Memory, addr: u32, value: u32) -> bool {
+    // SAFETY: see `load_word`
+    let memory = unsafe { &mut *memory };
+    let code_generation = memory.code_generation();
+    memory.write_word(addr, value);
+    memory.code_generation() != code_generation
+}
+
+// A read-only, executable mapping holding translated code
+struct ExecutableCode {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl ExecutableCode {
+    fn new(code: &[u8]) -> Option<ExecutableCode> {
+        let len = code.len();
+        // SAFETY: a fresh anonymous mapping is written and then made executable, it is never
+        // writable and executable at the same time
+        unsafe {
+            let ptr = libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if ptr == libc::MAP_FAILED {
+                return None;
+            }
+            std::ptr::copy_nonoverlapping(code.as_ptr(), ptr.cast(), len);
+            if libc::mprotect(ptr, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                libc::munmap(ptr, len);
+                return None;
+            }
+            Some(ExecutableCode { ptr, len })
+        }
+    }
+}
+
+impl Drop for ExecutableCode {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created in `new` and no translated code is running
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// Translation of basic blocks to x86-64, following the System V calling convention.
+//
+// The generated function is `extern "C" fn(registers: *mut u32, memory: *mut Memory) -> u64`
+// and returns the next pc in the low half and the number of executed instructions in the
+// high half. Guest registers stay in memory, pointed to by rbx, and are loaded into eax, ecx
+// and edx as each instruction needs them. r12 holds the memory pointer for the helpers.
+
+use crate::vm::{
+    instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+    jit::{load_word, store_word},
+};
+
+const EAX: u8 = 0;
+const ECX: u8 = 1;
+const EDX: u8 = 2;
+
+struct Emitter {
+    code: Vec<u8>,
+}
+
+impl Emitter {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn prologue(&mut self) {
+        // push rbx; push r12; sub rsp, 8 (keeps calls 16 byte aligned)
+        self.bytes(&[0x53, 0x41, 0x54, 0x48, 0x83, 0xec, 0x08]);
+        // mov rbx, rdi; mov r12, rsi
+        self.bytes(&[0x48, 0x89, 0xfb, 0x49, 0x89, 0xf4]);
+    }
+
+    fn epilogue(&mut self) {
+        // add rsp, 8; pop r12; pop rbx; ret
+        self.bytes(&[0x48, 0x83, 0xc4, 0x08, 0x41, 0x5c, 0x5b, 0xc3]);
+    }
+
+    // mov reg, [rbx + 4 * register]
+    fn load_register(&mut self, reg: u8, register: u32) {
+        self.bytes(&[0x8b, 0x83 | (reg << 3)]);
+        self.u32(4 * register);
+    }
+
+    // mov [rbx + 4 * register], eax, unless register is x0
+    fn store_register(&mut self, register: u32) {
+        if register != 0 {
+            self.bytes(&[0x89, 0x83]);
+            self.u32(4 * register);
+        }
+    }
+
+    // mov dword [rbx + 4 * register], value, unless register is x0
+    fn set_register(&mut self, register: u32, value: u32) {
+        if register != 0 {
+            self.bytes(&[0xc7, 0x83]);
+            self.u32(4 * register);
+            self.u32(value);
+        }
+    }
+
+    // mov reg, value
+    fn move_immediate(&mut self, reg: u8, value: u32) {
+        self.bytes(&[0xb8 + reg]);
+        self.u32(value);
+    }
+
+    // add eax, value
+    fn add_immediate(&mut self, value: u32) {
+        self.bytes(&[0x05]);
+        self.u32(value);
+    }
+
+    // eax = eax op ecx
+    fn arith(&mut self, op: ArithOp) {
+        match op {
+            ArithOp::Add => self.bytes(&[0x01, 0xc8]),
+            ArithOp::Sub => self.bytes(&[0x29, 0xc8]),
+            ArithOp::Xor => self.bytes(&[0x31, 0xc8]),
+            ArithOp::Or => self.bytes(&[0x09, 0xc8]),
+            ArithOp::And => self.bytes(&[0x21, 0xc8]),
+            // x86 masks the shift amount in cl to 5 bits, like RISC-V does
+            ArithOp::ShiftLeftLogical => self.bytes(&[0xd3, 0xe0]),
+            ArithOp::ShiftRightLogical => self.bytes(&[0xd3, 0xe8]),
+            ArithOp::ShiftRightArith => self.bytes(&[0xd3, 0xf8]),
+            // cmp eax, ecx; setl/setb al; movzx eax, al
+            ArithOp::SetLessThan => self.bytes(&[0x39, 0xc8, 0x0f, 0x9c, 0xc0, 0x0f, 0xb6, 0xc0]),
+            ArithOp::SetLessThanU => self.bytes(&[0x39, 0xc8, 0x0f, 0x92, 0xc0, 0x0f, 0xb6, 0xc0]),
+            // Left to the interpreter, see `translate`
+            _ => unreachable!("{op:?} is not translated"),
+        }
+    }
+
+    // Calls `helper` with the memory pointer and the address in eax, and the value in edx
+    fn call_helper(&mut self, helper: usize) {
+        // mov esi, eax; mov rdi, r12; mov rax, helper; call rax
+        self.bytes(&[0x89, 0xc6, 0x4c, 0x89, 0xe7, 0x48, 0xb8]);
+        self.bytes(&(helper as u64).to_le_bytes());
+        self.bytes(&[0xff, 0xd0]);
+    }
+
+    // Two-byte jcc with a 32-bit displacement, patched by `bind`
+    fn jump_if(&mut self, condition: u8) -> usize {
+        self.bytes(&[0x0f, condition]);
+        self.u32(0);
+        self.code.len()
+    }
+
+    fn bind(&mut self, jump: usize) {
+        let displacement = (self.code.len() - jump) as u32;
+        self.code[jump - 4..jump].copy_from_slice(&displacement.to_le_bytes());
+    }
+
+    // Misaligned accesses are left to the interpreter, which reports them, by exiting before
+    // the instruction at `pc` if the address in eax is not a multiple of 4
+    fn exit_if_misaligned(&mut self, pc: u32, executed: usize) {
+        // test al, 3; jz aligned
+        self.bytes(&[0xa8, 0x03]);
+        let aligned = self.jump_if(0x84);
+        self.exit(pc, executed);
+        self.bind(aligned);
+    }
+
+    fn exit(&mut self, pc: u32, executed: usize) {
+        // mov rax, executed << 32 | pc
+        self.bytes(&[0x48, 0xb8]);
+        self.bytes(&((executed as u64) << 32 | pc as u64).to_le_bytes());
+        self.epilogue();
+    }
+
+    // Exit to the pc in eax, whose upper half is already clear
+    fn exit_to_eax(&mut self, executed: usize) {
+        // mov rcx, executed << 32; or rax, rcx
+        self.bytes(&[0x48, 0xb9]);
+        self.bytes(&((executed as u64) << 32).to_le_bytes());
+        self.bytes(&[0x48, 0x09, 0xc8]);
+        self.epilogue();
+    }
+}
+
+// Condition codes of jcc, for `cmp eax, ecx`
+fn condition_code(cond: Comparison) -> u8 {
+    match cond {
+        Comparison::Equal => 0x84,
+        Comparison::NotEqual => 0x85,
+        Comparison::LessThan => 0x8c,
+        Comparison::GreaterOrEqual => 0x8d,
+        Comparison::LessThanUnsigned => 0x82,
+        Comparison::GreaterOrEqualUnsigned => 0x83,
+    }
+}
+
+/// Translates the longest prefix of `instructions`, which start at `start`, that needs no
+/// support from the interpreter. Returns `None` if not even the first one can be translated.
+pub(super) fn translate(start: u32, instructions: &[Instruction]) -> Option<Vec<u8>> {
+    let mut emitter = Emitter { code: Vec::new() };
+    emitter.prologue();
+    for (i, instruction) in instructions.iter().enumerate() {
+        let pc = start.wrapping_add(4 * i as u32);
+        let next_pc = pc.wrapping_add(4);
+        match *instruction {
+            Instruction::ArithImm { dst, src, imm, op } if op.has_immediate_form() => {
+                emitter.load_register(EAX, src);
+                emitter.move_immediate(ECX, imm as u32);
+                emitter.arith(op);
+                emitter.store_register(dst);
+            }
+            Instruction::Arith {
+                dst,
+                src1,
+                src2,
+                op,
+            } if !op.is_multiply_or_divide() => {
+                emitter.load_register(EAX, src1);
+                emitter.load_register(ECX, src2);
+                emitter.arith(op);
+                emitter.store_register(dst);
+            }
+            Instruction::LoadUpperImm { dst, imm } => emitter.set_register(dst, imm),
+            Instruction::AddUpperImmToPc { dst, imm } => {
+                emitter.set_register(dst, pc.wrapping_add(imm))
+            }
+            Instruction::Load {
+                dst,
+                offset,
+                base,
+                width: LoadStoreWidth::Word,
+            } => {
+                emitter.load_register(EAX, base);
+                emitter.add_immediate(offset as u32);
+                emitter.exit_if_misaligned(pc, i);
+                emitter.call_helper(load_word as usize);
+                emitter.store_register(dst);
+            }
+            Instruction::Store {
+                src,
+                offset,
+                base,
+                width: LoadStoreWidth::Word,
+            } => {
+                emitter.load_register(EAX, base);
+                emitter.add_immediate(offset as u32);
+                emitter.exit_if_misaligned(pc, i);
+                emitter.load_register(EDX, src);
+                emitter.call_helper(store_word as usize);
+                // The helper returns whether code was overwritten, then the rest of the block
+                // is stale: test al, al; jz continue
+                emitter.bytes(&[0x84, 0xc0]);
+                let code_intact = emitter.jump_if(0x84);
+                emitter.exit(next_pc, i + 1);
+                emitter.bind(code_intact);
+            }
+            Instruction::Branch {
+                src1,
+                src2,
+                cond,
+                offset,
+            } => {
+                emitter.load_register(EAX, src1);
+                emitter.load_register(ECX, src2);
+                emitter.bytes(&[0x39, 0xc8]);
+                let taken = emitter.jump_if(condition_code(cond));
+                emitter.exit(next_pc, i + 1);
+                emitter.bind(taken);
+                emitter.exit(pc.wrapping_add(offset as u32), i + 1);
+                return Some(emitter.code);
+            }
+            Instruction::JumpAndLink { dst, offset } => {
+                emitter.set_register(dst, next_pc);
+                emitter.exit(pc.wrapping_add(offset as u32), i + 1);
+                return Some(emitter.code);
+            }
+            Instruction::JumpAndLinkRegister { dst, base, offset } => {
+                // The target is computed before dst is written, in case they are the same
+                emitter.load_register(EAX, base);
+                emitter.add_immediate(offset as u32);
+                // and eax, !1
+                emitter.bytes(&[0x25]);
+                emitter.u32(!1);
+                emitter.set_register(dst, next_pc);
+                emitter.exit_to_eax(i + 1);
+                return Some(emitter.code);
+            }
+            // Multiplication and division, sub-word accesses, syscalls and custom instructions
+            // are left to the interpreter
+            _ if i == 0 => return None,
+            _ => {
+                emitter.exit(pc, i);
+                return Some(emitter.code);
+            }
+        }
+    }
+    let end = start.wrapping_add(4 * instructions.len() as u32);
+    emitter.exit(end, instructions.len());
+    Some(emitter.code)
+}
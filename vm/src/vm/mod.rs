@@ -1,2 +1,14 @@
+pub mod blocks;
+pub mod disassembler;
 pub mod execution;
+pub mod history;
 pub mod instructions;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod observer;
+pub mod profiler;
+pub mod registers;
+pub mod statistics;
+pub mod syscalls;
+pub mod trace;
+pub mod trace_file;
@@ -0,0 +1,327 @@
+use std::{fmt::Display, io::Write};
+
+use crate::vm::{
+    instructions::Instruction,
+    registers::{NUM_REGISTERS, REGISTER_NAMES, Registers},
+};
+
+/// Callbacks invoked by `Executor::step_with` and `Executor::run_with` as the program runs.
+/// Every method does nothing by default.
+pub trait Observer {
+    /// Called with the registers before `instruction`, at `pc`, runs.
+    fn before_instruction(&mut self, _pc: u32, _instruction: &Instruction, _registers: &Registers) {
+    }
+
+    /// Called with the registers after `instruction`, at `pc`, ran and moved to `next_pc`.
+    fn after_instruction(
+        &mut self,
+        _pc: u32,
+        _next_pc: u32,
+        _instruction: &Instruction,
+        _registers: &Registers,
+    ) {
+    }
+
+    /// A word read by a load, a syscall or a custom instruction, at its aligned address.
+    /// Unwritten bytes read as 0.
+    fn memory_read(&mut self, _addr: u32, _value: u32) {}
+
+    /// A word read while some of the bytes the access uses were never written, neither by
+    /// the program image, a store nor a syscall. Called before `memory_read`, with the same
+    /// aligned address.
+    fn uninitialized_read(&mut self, _addr: u32) {}
+
+    /// A word written by a store, a syscall or a custom instruction, at its aligned address.
+    /// Sub-word stores report the whole word before and after.
+    fn memory_write(&mut self, _addr: u32, _old_value: u32, _value: u32) {}
+
+    /// Called before the syscall `number` is handled.
+    fn syscall(&mut self, _number: u32, _registers: &Registers) {}
+
+    /// Called once the program returned, after `cycles` instructions.
+    fn halt(&mut self, _registers: &Registers, _cycles: u64) {}
+}
+
+/// Observes nothing, which is what `Executor::step` and `Executor::run` use.
+pub struct Silent;
+
+impl Observer for Silent {}
+
+impl<T: Observer + ?Sized> Observer for &mut T {
+    fn before_instruction(&mut self, pc: u32, instruction: &Instruction, registers: &Registers) {
+        (**self).before_instruction(pc, instruction, registers);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        next_pc: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) {
+        (**self).after_instruction(pc, next_pc, instruction, registers);
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        (**self).memory_read(addr, value);
+    }
+
+    fn uninitialized_read(&mut self, addr: u32) {
+        (**self).uninitialized_read(addr);
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        (**self).memory_write(addr, old_value, value);
+    }
+
+    fn syscall(&mut self, number: u32, registers: &Registers) {
+        (**self).syscall(number, registers);
+    }
+
+    fn halt(&mut self, registers: &Registers, cycles: u64) {
+        (**self).halt(registers, cycles);
+    }
+}
+
+/// Observes with both, the first one first.
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn before_instruction(&mut self, pc: u32, instruction: &Instruction, registers: &Registers) {
+        self.0.before_instruction(pc, instruction, registers);
+        self.1.before_instruction(pc, instruction, registers);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        next_pc: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) {
+        self.0
+            .after_instruction(pc, next_pc, instruction, registers);
+        self.1
+            .after_instruction(pc, next_pc, instruction, registers);
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        self.0.memory_read(addr, value);
+        self.1.memory_read(addr, value);
+    }
+
+    fn uninitialized_read(&mut self, addr: u32) {
+        self.0.uninitialized_read(addr);
+        self.1.uninitialized_read(addr);
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        self.0.memory_write(addr, old_value, value);
+        self.1.memory_write(addr, old_value, value);
+    }
+
+    fn syscall(&mut self, number: u32, registers: &Registers) {
+        self.0.syscall(number, registers);
+        self.1.syscall(number, registers);
+    }
+
+    fn halt(&mut self, registers: &Registers, cycles: u64) {
+        self.0.halt(registers, cycles);
+        self.1.halt(registers, cycles);
+    }
+}
+
+/// Writes each instruction and its effects in a human-readable form, e.g.
+///
+/// ```text
+/// 0x00001000  addi a0, a0, 1
+///             a0 = 0x00000001
+/// ```
+pub struct Log<W: Write> {
+    output: W,
+    registers: Registers,
+    error: Option<std::io::Error>,
+}
+
+impl Log<std::io::Stdout> {
+    pub fn stdout() -> Log<std::io::Stdout> {
+        Log::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> Log<W> {
+    pub fn new(output: W) -> Log<W> {
+        Log {
+            output,
+            registers: Registers::default(),
+            error: None,
+        }
+    }
+
+    /// Returns the output, or the first error writing to it.
+    pub fn finish(self) -> std::io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.output),
+        }
+    }
+
+    // Keeps the first error and stops writing after it
+    fn write(&mut self, line: std::fmt::Arguments) {
+        if self.error.is_none()
+            && let Err(error) = writeln!(self.output, "{line}")
+        {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> Observer for Log<W> {
+    fn before_instruction(&mut self, pc: u32, instruction: &Instruction, registers: &Registers) {
+        self.registers = registers.clone();
+        self.write(format_args!("0x{pc:08x}  {instruction}"));
+    }
+
+    fn after_instruction(&mut self, _: u32, _: u32, _: &Instruction, registers: &Registers) {
+        let before = std::mem::take(&mut self.registers);
+        for register in changed_registers(&before, registers) {
+            let name = REGISTER_NAMES[register as usize];
+            let value = registers.read(register);
+            self.write(format_args!("            {name} = 0x{value:08x}"));
+        }
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        self.write(format_args!(
+            "            [0x{addr:08x}] = 0x{value:08x} (was 0x{old_value:08x})"
+        ));
+    }
+
+    fn uninitialized_read(&mut self, addr: u32) {
+        self.write(format_args!(
+            "            [0x{addr:08x}] read before written"
+        ));
+    }
+
+    fn syscall(&mut self, number: u32, _: &Registers) {
+        self.write(format_args!("            syscall 0x{number:02x}"));
+    }
+
+    fn halt(&mut self, _: &Registers, cycles: u64) {
+        self.write(format_args!("halted after {cycles} cycles"));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Instruction {
+        pc: u32,
+        instruction: Instruction,
+    },
+    RegisterWrite {
+        register: u32,
+        value: u32,
+    },
+    MemoryRead {
+        addr: u32,
+        value: u32,
+    },
+    MemoryWrite {
+        addr: u32,
+        old_value: u32,
+        value: u32,
+    },
+    Syscall {
+        number: u32,
+    },
+    Halt {
+        cycles: u64,
+    },
+}
+
+/// Records every event in order, for programs to inspect.
+#[derive(Default)]
+pub struct StructuredTrace {
+    pub events: Vec<Event>,
+    registers: Registers,
+}
+
+impl Observer for StructuredTrace {
+    fn before_instruction(&mut self, pc: u32, instruction: &Instruction, registers: &Registers) {
+        self.registers = registers.clone();
+        self.events.push(Event::Instruction {
+            pc,
+            instruction: *instruction,
+        });
+    }
+
+    fn after_instruction(&mut self, _: u32, _: u32, _: &Instruction, registers: &Registers) {
+        for register in changed_registers(&self.registers, registers) {
+            self.events.push(Event::RegisterWrite {
+                register,
+                value: registers.read(register),
+            });
+        }
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        self.events.push(Event::MemoryRead { addr, value });
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        self.events.push(Event::MemoryWrite {
+            addr,
+            old_value,
+            value,
+        });
+    }
+
+    fn syscall(&mut self, number: u32, _: &Registers) {
+        self.events.push(Event::Syscall { number });
+    }
+
+    fn halt(&mut self, _: &Registers, cycles: u64) {
+        self.events.push(Event::Halt { cycles });
+    }
+}
+
+/// A read of memory nothing was written to, by the instruction at `pc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitializedRead {
+    pub pc: u32,
+    pub addr: u32,
+}
+
+impl Display for UninitializedRead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Read of uninitialized 0x{:08x} at 0x{:08x}",
+            self.addr, self.pc
+        )
+    }
+}
+
+/// Collects the reads of uninitialized memory. Bytes are tracked one by one, so loading a
+/// byte next to one that was stored is reported. Reads are reported by the address of their
+/// word.
+#[derive(Default)]
+pub struct UninitializedReads {
+    pub reads: Vec<UninitializedRead>,
+    pc: u32,
+}
+
+impl Observer for UninitializedReads {
+    fn before_instruction(&mut self, pc: u32, _: &Instruction, _: &Registers) {
+        self.pc = pc;
+    }
+
+    fn uninitialized_read(&mut self, addr: u32) {
+        self.reads.push(UninitializedRead { pc: self.pc, addr });
+    }
+}
+
+fn changed_registers<'a>(
+    before: &'a Registers,
+    after: &'a Registers,
+) -> impl Iterator<Item = u32> + 'a {
+    (0..NUM_REGISTERS as u32).filter(|register| before.read(*register) != after.read(*register))
+}
@@ -0,0 +1,237 @@
+// Where the cycles of a program go, by function and by call path. Calls and returns are
+// recognised by the calling convention: a jump that links `ra` calls the function it jumps
+// to, and a jump to the address a call linked returns from it.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write};
+
+use hashbrown::HashMap;
+
+use crate::{
+    elf::Elf,
+    vm::{
+        disassembler::symbolize,
+        instructions::Instruction,
+        observer::Observer,
+        registers::{RA, Registers},
+    },
+};
+
+// A function on a call path, the root being the entry point
+struct Node {
+    function: u32,
+    parent: usize,
+    cycles: u64,
+    children: HashMap<u32, usize>,
+}
+
+/// Cycles spent in a function, see `Profiler::functions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionProfile {
+    // Entry address
+    pub function: u32,
+    // In the function itself, and in it and everything it called
+    pub self_cycles: u64,
+    pub total_cycles: u64,
+    pub calls: u64,
+}
+
+/// Counts the cycles spent on each call path as the program runs. Function names are only
+/// needed for the reports, as a function of the entry address, e.g. `function_name`.
+///
+/// A tail call, jumping to a function without linking, is counted as part of the function
+/// that made it.
+pub struct Profiler {
+    nodes: Vec<Node>,
+    current: usize,
+    // Return address of each call on the current path, innermost last, and the node
+    // returned to
+    returns: Vec<(u32, usize)>,
+    calls: HashMap<u32, u64>,
+}
+
+impl Profiler {
+    pub fn new(entry_point: u32) -> Profiler {
+        Profiler {
+            nodes: vec![Node {
+                function: entry_point,
+                parent: 0,
+                cycles: 0,
+                children: HashMap::new(),
+            }],
+            current: 0,
+            returns: Vec::new(),
+            calls: HashMap::new(),
+        }
+    }
+
+    /// Entry addresses of the functions on the current call path, innermost first.
+    pub fn call_stack(&self) -> impl Iterator<Item = u32> + '_ {
+        self.path(self.current)
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.nodes.iter().map(|node| node.cycles).sum()
+    }
+
+    /// Every function that ran, by self cycles, most first.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: HashMap<u32, FunctionProfile> = HashMap::new();
+        let totals = self.totals();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let function = functions
+                .entry(node.function)
+                .or_insert_with(|| FunctionProfile {
+                    function: node.function,
+                    self_cycles: 0,
+                    total_cycles: 0,
+                    calls: self.calls.get(&node.function).copied().unwrap_or(0),
+                });
+            function.self_cycles += node.cycles;
+            // Recursive calls are already in the total of the outermost one
+            if !self
+                .path(index)
+                .skip(1)
+                .any(|caller| caller == node.function)
+            {
+                function.total_cycles += totals[index];
+            }
+        }
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by_key(|function| (Reverse(function.self_cycles), function.function));
+        functions
+    }
+
+    /// The call paths and their cycles in the folded format of flamegraph tools, one line
+    /// per path: the functions from the outermost one separated by `;`, then the cycles.
+    pub fn folded(&self, name: impl Fn(u32) -> String) -> String {
+        let mut paths = BTreeMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.cycles == 0 {
+                continue;
+            }
+            let mut path: Vec<_> = self.path(index).map(&name).collect();
+            path.reverse();
+            *paths.entry(path.join(";")).or_insert(0) += node.cycles;
+        }
+        let mut folded = String::new();
+        for (path, cycles) in paths {
+            let _ = writeln!(folded, "{path} {cycles}");
+        }
+        folded
+    }
+
+    /// A table of the `count` functions with the most self cycles.
+    pub fn top(&self, count: usize, name: impl Fn(u32) -> String) -> String {
+        let cycles = self.cycles().max(1) as f64;
+        let mut table = format!(
+            "{:>12} {:>7} {:>12} {:>7} {:>10}  function\n",
+            "self", "%", "total", "%", "calls"
+        );
+        for function in self.functions().into_iter().take(count) {
+            let _ = writeln!(
+                table,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>10}  {}",
+                function.self_cycles,
+                function.self_cycles as f64 * 100.0 / cycles,
+                function.total_cycles,
+                function.total_cycles as f64 * 100.0 / cycles,
+                function.calls,
+                name(function.function)
+            );
+        }
+        table
+    }
+
+    // Functions from the node at `index` up to the entry point
+    fn path(&self, mut index: usize) -> impl Iterator<Item = u32> + '_ {
+        std::iter::from_fn(move || {
+            let node = self.nodes.get(index)?;
+            index = if index == 0 { usize::MAX } else { node.parent };
+            Some(node.function)
+        })
+    }
+
+    // Cycles of each node and its descendants, children come after their parents
+    fn totals(&self) -> Vec<u64> {
+        let mut totals: Vec<_> = self.nodes.iter().map(|node| node.cycles).collect();
+        for index in (1..self.nodes.len()).rev() {
+            totals[self.nodes[index].parent] += totals[index];
+        }
+        totals
+    }
+
+    fn call(&mut self, function: u32, return_addr: u32) {
+        *self.calls.entry(function).or_insert(0) += 1;
+        self.returns.push((return_addr, self.current));
+        let next = self.nodes.len();
+        let child = *self.nodes[self.current]
+            .children
+            .entry(function)
+            .or_insert(next);
+        if child == next {
+            self.nodes.push(Node {
+                function,
+                parent: self.current,
+                cycles: 0,
+                children: HashMap::new(),
+            });
+        }
+        self.current = child;
+    }
+
+    // Returns through every call that linked `addr`, those above it on the path made tail
+    // calls or never returned
+    fn return_to(&mut self, addr: u32) {
+        if let Some(call) = self.returns.iter().rposition(|(linked, _)| *linked == addr) {
+            self.current = self.returns[call].1;
+            self.returns.truncate(call);
+        }
+    }
+}
+
+impl Observer for Profiler {
+    fn before_instruction(&mut self, _: u32, _: &Instruction, _: &Registers) {
+        self.nodes[self.current].cycles += 1;
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        next_pc: u32,
+        instruction: &Instruction,
+        _: &Registers,
+    ) {
+        match instruction {
+            Instruction::JumpAndLink { dst: RA, .. }
+            | Instruction::JumpAndLinkRegister { dst: RA, .. } => {
+                self.call(next_pc, pc.wrapping_add(4))
+            }
+            Instruction::JumpAndLinkRegister { base: RA, .. } => self.return_to(next_pc),
+            _ => {}
+        }
+    }
+}
+
+/// Name of the function at `addr` from the symbols of `elf`, or the address.
+pub fn function_name(elf: &Elf, addr: u32) -> String {
+    elf.symbols
+        .get(&addr)
+        .cloned()
+        .or_else(|| symbolize(elf, addr))
+        .unwrap_or_else(|| format!("0x{addr:08x}"))
+}
+
+/// Like `function_name`, with the demangled names of `debug_info` where it has the function.
+#[cfg(feature = "debug-info")]
+pub fn function_name_with_debug_info(
+    elf: &Elf,
+    debug_info: &crate::debug_info::DebugInfo,
+    addr: u32,
+) -> String {
+    match debug_info.function(addr) {
+        Some((name, 0)) => name.to_string(),
+        _ => debug_info
+            .symbolize(addr)
+            .unwrap_or_else(|| function_name(elf, addr)),
+    }
+}
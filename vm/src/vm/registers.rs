@@ -0,0 +1,86 @@
+use std::fmt::Display;
+
+pub const NUM_REGISTERS: usize = 32;
+
+pub const REGISTER_NAMES: [&str; NUM_REGISTERS] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+// Registers with a fixed role in the VM's ABI
+pub const ZERO: u32 = 0;
+pub const RA: u32 = 1;
+pub const SP: u32 = 2;
+pub const A0: u32 = 10;
+pub const A1: u32 = 11;
+pub const A7: u32 = 17;
+
+/// Index of the register called `name`, either by ABI name (`a0`, `fp`) or as `x0`..`x31`.
+pub fn register_index(name: &str) -> Option<u32> {
+    if let Some(index) = REGISTER_NAMES.iter().position(|register| *register == name) {
+        return Some(index as u32);
+    }
+    match name {
+        "fp" => Some(8),
+        _ => name
+            .strip_prefix('x')
+            .filter(|index| !index.starts_with('+'))
+            .and_then(|index| index.parse().ok())
+            .filter(|index| *index < NUM_REGISTERS as u32),
+    }
+}
+
+/// The 32 integer registers. x0 always reads as zero, writes to it are discarded.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Registers([u32; NUM_REGISTERS]);
+
+impl Registers {
+    pub fn read(&self, index: u32) -> u32 {
+        self.0[index as usize]
+    }
+
+    pub fn write(&mut self, index: u32, value: u32) {
+        if index != ZERO {
+            self.0[index as usize] = value;
+        }
+    }
+
+    /// Value of the register called `name`, see `register_index`.
+    pub fn read_by_name(&self, name: &str) -> Option<u32> {
+        register_index(name).map(|index| self.read(index))
+    }
+
+    pub fn values(&self) -> &[u32; NUM_REGISTERS] {
+        &self.0
+    }
+
+    // For translated code, which must not write x0
+    #[cfg(feature = "jit")]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut u32 {
+        self.0.as_mut_ptr()
+    }
+}
+
+// Four columns of eight registers, e.g. `x10 a0   0x0000002a`
+impl Display for Registers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const ROWS: usize = NUM_REGISTERS / 4;
+        for row in 0..ROWS {
+            for column in 0..4 {
+                let index = column * ROWS + row;
+                if column > 0 {
+                    write!(f, "  ")?;
+                }
+                let register = format!("x{index}");
+                write!(
+                    f,
+                    "{register:<3} {:<4} 0x{:08x}",
+                    REGISTER_NAMES[index], self.0[index]
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+// Which instructions a program ran, and how often. The sizes of the prover tables depend on
+// the mix, e.g. the number of memory accesses, not only on the cycles.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::vm::{
+    instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+    observer::Observer,
+    registers::Registers,
+};
+
+/// Counts of what the instructions of a run did, collected by `Executor::with_statistics` or
+/// by observing a run. Arithmetic operations count both the register and the immediate forms.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InstructionMix {
+    // By the name of the `Instruction` variant
+    pub instructions: BTreeMap<&'static str, u64>,
+    pub arith_ops: BTreeMap<ArithOp, u64>,
+    pub comparisons: BTreeMap<Comparison, u64>,
+    pub loads: BTreeMap<LoadStoreWidth, u64>,
+    pub stores: BTreeMap<LoadStoreWidth, u64>,
+    // By syscall number
+    pub syscalls: BTreeMap<u32, u64>,
+    pub branches_taken: u64,
+    pub branches_not_taken: u64,
+    // Words, by instructions and syscalls alike
+    pub memory_reads: u64,
+    pub memory_writes: u64,
+    // Of the branch being run, set before it runs
+    branch_taken: Option<bool>,
+}
+
+impl InstructionMix {
+    pub fn cycles(&self) -> u64 {
+        self.instructions.values().sum()
+    }
+}
+
+impl Observer for InstructionMix {
+    // Whether a branch is taken depends on the registers before it runs
+    fn before_instruction(&mut self, _: u32, instruction: &Instruction, registers: &Registers) {
+        self.branch_taken = match instruction {
+            Instruction::Branch {
+                src1, src2, cond, ..
+            } => Some(cond.holds(registers.read(*src1), registers.read(*src2))),
+            _ => None,
+        };
+    }
+
+    // Counted once they completed, so an ebreak or a failing instruction is not
+    fn after_instruction(&mut self, _: u32, _: u32, instruction: &Instruction, _: &Registers) {
+        *self.instructions.entry(variant(instruction)).or_insert(0) += 1;
+        match instruction {
+            Instruction::Arith { op, .. } | Instruction::ArithImm { op, .. } => {
+                *self.arith_ops.entry(*op).or_insert(0) += 1;
+            }
+            Instruction::Branch { cond, .. } => {
+                *self.comparisons.entry(*cond).or_insert(0) += 1;
+                // A taken branch may still go to the next instruction
+                if self.branch_taken.take() == Some(true) {
+                    self.branches_taken += 1;
+                } else {
+                    self.branches_not_taken += 1;
+                }
+            }
+            Instruction::Load { width, .. } => *self.loads.entry(*width).or_insert(0) += 1,
+            Instruction::Store { width, .. } => *self.stores.entry(*width).or_insert(0) += 1,
+            _ => {}
+        }
+    }
+
+    fn memory_read(&mut self, _: u32, _: u32) {
+        self.memory_reads += 1;
+    }
+
+    fn memory_write(&mut self, _: u32, _: u32, _: u32) {
+        self.memory_writes += 1;
+    }
+
+    fn syscall(&mut self, number: u32, _: &Registers) {
+        *self.syscalls.entry(number).or_insert(0) += 1;
+    }
+}
+
+/// A table per kind of count, most frequent first, with the share of each.
+impl Display for InstructionMix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cycles = self.cycles();
+        writeln!(f, "Instructions: {cycles}")?;
+        table(f, &self.instructions, |name| name.to_string())?;
+        writeln!(f, "Arithmetic operations:")?;
+        table(f, &self.arith_ops, |op| format!("{op:?}"))?;
+        writeln!(f, "Branch comparisons:")?;
+        table(f, &self.comparisons, |cond| format!("{cond:?}"))?;
+        let branches = self.branches_taken + self.branches_not_taken;
+        writeln!(
+            f,
+            "Branches: {} taken ({:.2}%), {} not taken",
+            self.branches_taken,
+            share(self.branches_taken, branches),
+            self.branches_not_taken
+        )?;
+        writeln!(f, "Loads by width:")?;
+        table(f, &self.loads, |width| format!("{width:?}"))?;
+        writeln!(f, "Stores by width:")?;
+        table(f, &self.stores, |width| format!("{width:?}"))?;
+        writeln!(
+            f,
+            "Memory: {} reads, {} writes",
+            self.memory_reads, self.memory_writes
+        )?;
+        writeln!(f, "Syscalls:")?;
+        table(f, &self.syscalls, |number| format!("0x{number:02x}"))
+    }
+}
+
+fn table<K>(
+    f: &mut std::fmt::Formatter<'_>,
+    counts: &BTreeMap<K, u64>,
+    name: impl Fn(&K) -> String,
+) -> std::fmt::Result {
+    let total = counts.values().sum();
+    let mut rows: Vec<_> = counts.iter().collect();
+    // Stable, so equal counts stay in key order
+    rows.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+    for (key, count) in rows {
+        writeln!(
+            f,
+            "  {:<24} {count:>12} {:>6.2}%",
+            name(key),
+            share(*count, total)
+        )?;
+    }
+    Ok(())
+}
+
+fn share(count: u64, total: u64) -> f64 {
+    count as f64 * 100.0 / total.max(1) as f64
+}
+
+fn variant(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Arith { .. } => "Arith",
+        Instruction::ArithImm { .. } => "ArithImm",
+        Instruction::JumpAndLink { .. } => "JumpAndLink",
+        Instruction::JumpAndLinkRegister { .. } => "JumpAndLinkRegister",
+        Instruction::Store { .. } => "Store",
+        Instruction::Load { .. } => "Load",
+        Instruction::Branch { .. } => "Branch",
+        Instruction::LoadUpperImm { .. } => "LoadUpperImm",
+        Instruction::AddUpperImmToPc { .. } => "AddUpperImmToPc",
+        Instruction::EnvironmentCall => "EnvironmentCall",
+        Instruction::EnvironmentBreak => "EnvironmentBreak",
+        Instruction::Poseidon2Permute { .. } => "Poseidon2Permute",
+        Instruction::Uint256Add { .. } => "Uint256Add",
+    }
+}
@@ -0,0 +1,138 @@
+use crypto::{
+    bigint::{U256, WORDS},
+    curve::{AffinePoint, CurveError, POINT_WORDS},
+    edwards25519,
+    field::FieldElement,
+    poseidon2, secp256k1,
+};
+
+use crate::{
+    elf::WORD_SIZE,
+    vm::{
+        execution::{ExecutionError, Memory},
+        observer::Observer,
+        registers::{A0, A1, A7, Registers},
+    },
+};
+
+// Syscall numbers, passed in a7. They must match the ones in `sdk::syscalls`.
+pub const POSEIDON2_PERMUTE: u32 = 0x01;
+pub const SECP256K1_ADD: u32 = 0x02;
+pub const SECP256K1_DOUBLE: u32 = 0x03;
+pub const ED25519_ADD: u32 = 0x04;
+pub const ED25519_DOUBLE: u32 = 0x05;
+pub const UINT256_MUL_MOD: u32 = 0x06;
+
+pub(crate) fn handle_syscall<O: Observer>(
+    registers: &mut Registers,
+    memory: &mut Memory,
+    observer: &mut O,
+) -> Result<(), ExecutionError> {
+    let syscall_number = registers.read(A7);
+    let first_argument = registers.read(A0);
+    let second_argument = registers.read(A1);
+    match syscall_number {
+        POSEIDON2_PERMUTE => poseidon2_permute(first_argument, memory, observer),
+        SECP256K1_ADD => curve_add(
+            first_argument,
+            second_argument,
+            memory,
+            observer,
+            secp256k1::add,
+        ),
+        SECP256K1_DOUBLE => curve_double(first_argument, memory, observer, secp256k1::double),
+        ED25519_ADD => curve_add(
+            first_argument,
+            second_argument,
+            memory,
+            observer,
+            edwards25519::add,
+        ),
+        ED25519_DOUBLE => curve_double(first_argument, memory, observer, edwards25519::double),
+        UINT256_MUL_MOD => uint256_mul_mod(first_argument, second_argument, memory, observer),
+        _ => Err(ExecutionError::UnknownSyscall(syscall_number)),
+    }
+}
+
+// a0: address of a state of `poseidon2::WIDTH` words, each a canonical field element.
+// The state is replaced with its permutation.
+pub(crate) fn poseidon2_permute<O: Observer>(
+    state_addr: u32,
+    memory: &mut Memory,
+    observer: &mut O,
+) -> Result<(), ExecutionError> {
+    let words = memory.read_words::<{ poseidon2::WIDTH }, _>(state_addr, observer)?;
+    let mut state = [FieldElement::ZERO; poseidon2::WIDTH];
+    for (element, word) in state.iter_mut().zip(words) {
+        *element = FieldElement::from_canonical(word)
+            .ok_or(ExecutionError::NonCanonicalFieldElement(word))?;
+    }
+    poseidon2::permute(&mut state);
+    memory.write_words(state_addr, &state.map(|element| element.value()), observer)
+}
+
+// a0: address of the affine point p, overwritten with p + q.
+// a1: address of the affine point q.
+fn curve_add<O: Observer>(
+    p_addr: u32,
+    q_addr: u32,
+    memory: &mut Memory,
+    observer: &mut O,
+    add: fn(&AffinePoint, &AffinePoint) -> Result<AffinePoint, CurveError>,
+) -> Result<(), ExecutionError> {
+    let p = AffinePoint::from_le_words(memory.read_words::<POINT_WORDS, _>(p_addr, observer)?);
+    let q = AffinePoint::from_le_words(memory.read_words::<POINT_WORDS, _>(q_addr, observer)?);
+    let result = add(&p, &q)?;
+    memory.write_words(p_addr, &result.to_le_words(), observer)
+}
+
+// a0: address of the affine point p, overwritten with 2p.
+fn curve_double<O: Observer>(
+    p_addr: u32,
+    memory: &mut Memory,
+    observer: &mut O,
+    double: fn(&AffinePoint) -> Result<AffinePoint, CurveError>,
+) -> Result<(), ExecutionError> {
+    let p = AffinePoint::from_le_words(memory.read_words::<POINT_WORDS, _>(p_addr, observer)?);
+    let result = double(&p)?;
+    memory.write_words(p_addr, &result.to_le_words(), observer)
+}
+
+// Semantics of the uint256.add instruction: x at `x_addr` is overwritten with x + y mod 2^256
+// and the carry out is returned. Operands are little-endian 256-bit integers.
+pub(crate) fn uint256_add<O: Observer>(
+    x_addr: u32,
+    y_addr: u32,
+    memory: &mut Memory,
+    observer: &mut O,
+) -> Result<bool, ExecutionError> {
+    let x = U256::from_le_words(memory.read_words::<WORDS, _>(x_addr, observer)?);
+    let y = U256::from_le_words(memory.read_words::<WORDS, _>(y_addr, observer)?);
+    let (sum, carry) = x.overflowing_add(&y);
+    memory.write_words(x_addr, &sum.to_le_words(), observer)?;
+    Ok(carry)
+}
+
+// a0: address of x, overwritten with x * y mod m.
+// a1: address of y, immediately followed by m.
+// Operands are little-endian 256-bit integers and need not be reduced. A zero modulus
+// stands for 2^256, so the result is the low half of the product.
+fn uint256_mul_mod<O: Observer>(
+    x_addr: u32,
+    y_addr: u32,
+    memory: &mut Memory,
+    observer: &mut O,
+) -> Result<(), ExecutionError> {
+    let x = U256::from_le_words(memory.read_words::<WORDS, _>(x_addr, observer)?);
+    let y = U256::from_le_words(memory.read_words::<WORDS, _>(y_addr, observer)?);
+    let modulus_addr = y_addr
+        .checked_add(WORDS as u32 * WORD_SIZE)
+        .ok_or(ExecutionError::InvalidSyscallBuffer(y_addr))?;
+    let modulus = U256::from_le_words(memory.read_words::<WORDS, _>(modulus_addr, observer)?);
+    let result = if modulus.is_zero() {
+        x.wrapping_mul(&y)
+    } else {
+        x.mul_mod(&y, &modulus)
+    };
+    memory.write_words(x_addr, &result.to_le_words(), observer)
+}
@@ -0,0 +1,198 @@
+// Execution traces in the layout the trace generator builds AIR tables from: one column per
+// field, with a row per cycle for the instructions and a row per word for memory accesses.
+
+use crate::vm::{instructions::Instruction, observer::Observer, registers::Registers};
+
+/// An executed instruction. Registers that are not operands read as 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub pc: u32,
+    pub instruction: Instruction,
+    pub rs1_value: u32,
+    pub rs2_value: u32,
+    // Value of rd after the instruction, 0 if it has none or it is x0
+    pub rd_value: u32,
+}
+
+/// A word read or written. Reads have the value as `old_value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    // Starts at 1, leaving 0 for the initial memory
+    pub timestamp: u64,
+    // Step of the instruction that made the access, past the last one if it failed
+    pub cycle: u64,
+    pub addr: u32,
+    pub value: u32,
+    pub old_value: u32,
+    pub is_write: bool,
+}
+
+/// Records the execution as columns when passed to `Executor::run_with`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionTrace {
+    pub steps: StepColumns,
+    pub memory: MemoryColumns,
+    rows: RowBuilder,
+}
+
+/// A row per executed instruction, see `Step`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct StepColumns {
+    pub pc: Vec<u32>,
+    pub instruction: Vec<Instruction>,
+    pub rs1_value: Vec<u32>,
+    pub rs2_value: Vec<u32>,
+    pub rd_value: Vec<u32>,
+}
+
+/// A row per memory access in the order they happened, see `MemoryAccess`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MemoryColumns {
+    pub timestamp: Vec<u64>,
+    pub cycle: Vec<u64>,
+    pub addr: Vec<u32>,
+    pub value: Vec<u32>,
+    pub old_value: Vec<u32>,
+    pub is_write: Vec<bool>,
+}
+
+impl ExecutionTrace {
+    /// Number of instructions recorded.
+    pub fn len(&self) -> usize {
+        self.steps.pc.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.pc.is_empty()
+    }
+
+    /// Keeps the first `steps` instructions and their memory accesses, so that recording
+    /// continues from there.
+    pub fn truncate(&mut self, steps: usize) {
+        let accesses = self
+            .memory
+            .cycle
+            .partition_point(|cycle| *cycle < steps as u64);
+        self.steps.truncate(steps);
+        self.memory.truncate(accesses);
+        self.rows.cycle = self.len() as u64;
+        self.rows.timestamp = accesses as u64;
+    }
+}
+
+impl StepColumns {
+    pub fn push(&mut self, step: Step) {
+        self.pc.push(step.pc);
+        self.instruction.push(step.instruction);
+        self.rs1_value.push(step.rs1_value);
+        self.rs2_value.push(step.rs2_value);
+        self.rd_value.push(step.rd_value);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.pc.truncate(len);
+        self.instruction.truncate(len);
+        self.rs1_value.truncate(len);
+        self.rs2_value.truncate(len);
+        self.rd_value.truncate(len);
+    }
+}
+
+impl MemoryColumns {
+    pub fn push(&mut self, access: MemoryAccess) {
+        self.timestamp.push(access.timestamp);
+        self.cycle.push(access.cycle);
+        self.addr.push(access.addr);
+        self.value.push(access.value);
+        self.old_value.push(access.old_value);
+        self.is_write.push(access.is_write);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.timestamp.truncate(len);
+        self.cycle.truncate(len);
+        self.addr.truncate(len);
+        self.value.truncate(len);
+        self.old_value.truncate(len);
+        self.is_write.truncate(len);
+    }
+}
+
+impl Observer for ExecutionTrace {
+    fn before_instruction(&mut self, _: u32, instruction: &Instruction, registers: &Registers) {
+        self.rows.begin(instruction, registers);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        _: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) {
+        self.steps.push(self.rows.step(pc, instruction, registers));
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        self.memory
+            .push(self.rows.access(addr, value, value, false));
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        self.memory
+            .push(self.rows.access(addr, old_value, value, true));
+    }
+}
+
+// Turns observer callbacks into rows. A step is only complete once its instruction is, so a
+// failing instruction leaves no step behind.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RowBuilder {
+    // Values of rs1 and rs2 of the running instruction
+    sources: (u32, u32),
+    cycle: u64,
+    timestamp: u64,
+}
+
+impl RowBuilder {
+    pub(crate) fn begin(&mut self, instruction: &Instruction, registers: &Registers) {
+        let (rs1, rs2) = instruction.source_registers();
+        let read = |register: Option<u32>| register.map_or(0, |register| registers.read(register));
+        self.sources = (read(rs1), read(rs2));
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        pc: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) -> Step {
+        self.cycle += 1;
+        let rd = instruction.destination_register();
+        Step {
+            pc,
+            instruction: *instruction,
+            rs1_value: self.sources.0,
+            rs2_value: self.sources.1,
+            rd_value: rd.map_or(0, |register| registers.read(register)),
+        }
+    }
+
+    pub(crate) fn access(
+        &mut self,
+        addr: u32,
+        old_value: u32,
+        value: u32,
+        is_write: bool,
+    ) -> MemoryAccess {
+        self.timestamp += 1;
+        MemoryAccess {
+            timestamp: self.timestamp,
+            cycle: self.cycle,
+            addr,
+            value,
+            old_value,
+            is_write,
+        }
+    }
+}
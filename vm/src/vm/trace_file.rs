@@ -0,0 +1,257 @@
+// Binary execution traces, for programs whose trace does not fit in memory.
+//
+// A file starts with `MAGIC` and the format version as a little-endian u16, followed by
+// records in the order they happened. Each record is its length as a little-endian u32, then
+// a kind byte and the fields, all little-endian:
+//
+// - step (kind 0): pc u32, instruction word u32, rs1 value u32, rs2 value u32, rd value u32
+// - memory access (kind 1): timestamp u64, cycle u64, addr u32, value u32, old value u32,
+//   is write u8
+//
+// The memory accesses of an instruction come before its step.
+
+use std::io::{Read, Write};
+
+use crate::vm::{
+    instructions::{DecodeError, Instruction},
+    observer::Observer,
+    registers::Registers,
+    trace::{ExecutionTrace, MemoryAccess, RowBuilder, Step},
+};
+
+pub const MAGIC: &[u8; 8] = b"NVMTRACE";
+pub const VERSION: u16 = 1;
+
+const STEP_KIND: u8 = 0;
+const MEMORY_ACCESS_KIND: u8 = 1;
+const STEP_LENGTH: usize = 1 + 5 * 4;
+const MEMORY_ACCESS_LENGTH: usize = 1 + 2 * 8 + 3 * 4 + 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TraceFileError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Not a trace file")]
+    NotATrace,
+    #[error("Unsupported trace file version {0}")]
+    UnsupportedVersion(u16),
+    #[error("Invalid record of kind {kind} and length {length}")]
+    InvalidRecord { kind: u8, length: u32 },
+    #[error("Trace file ends in the middle of a record")]
+    Truncated,
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record {
+    Step(Step),
+    MemoryAccess(MemoryAccess),
+}
+
+/// Streams the execution to `output` when passed to `Executor::run_with`. Wrap files in a
+/// `BufWriter`, every record is a separate write.
+pub struct TraceWriter<W: Write> {
+    output: W,
+    rows: RowBuilder,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// Writes the header to `output`.
+    pub fn new(mut output: W) -> std::io::Result<TraceWriter<W>> {
+        output.write_all(MAGIC)?;
+        output.write_all(&VERSION.to_le_bytes())?;
+        Ok(TraceWriter {
+            output,
+            rows: RowBuilder::default(),
+            error: None,
+        })
+    }
+
+    pub fn write(&mut self, record: &Record) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(4 + MEMORY_ACCESS_LENGTH);
+        match record {
+            Record::Step(step) => {
+                bytes.extend((STEP_LENGTH as u32).to_le_bytes());
+                bytes.push(STEP_KIND);
+                for field in [
+                    step.pc,
+                    step.instruction.encode(),
+                    step.rs1_value,
+                    step.rs2_value,
+                    step.rd_value,
+                ] {
+                    bytes.extend(field.to_le_bytes());
+                }
+            }
+            Record::MemoryAccess(access) => {
+                bytes.extend((MEMORY_ACCESS_LENGTH as u32).to_le_bytes());
+                bytes.push(MEMORY_ACCESS_KIND);
+                bytes.extend(access.timestamp.to_le_bytes());
+                bytes.extend(access.cycle.to_le_bytes());
+                for field in [access.addr, access.value, access.old_value] {
+                    bytes.extend(field.to_le_bytes());
+                }
+                bytes.push(access.is_write as u8);
+            }
+        }
+        self.output.write_all(&bytes)
+    }
+
+    /// Flushes and returns the output, or the first error writing to it.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    // Observer callbacks can't fail, the first error is kept for `finish`
+    fn record(&mut self, record: Record) {
+        if self.error.is_none()
+            && let Err(error) = self.write(&record)
+        {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> Observer for TraceWriter<W> {
+    fn before_instruction(&mut self, _: u32, instruction: &Instruction, registers: &Registers) {
+        self.rows.begin(instruction, registers);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u32,
+        _: u32,
+        instruction: &Instruction,
+        registers: &Registers,
+    ) {
+        let step = self.rows.step(pc, instruction, registers);
+        self.record(Record::Step(step));
+    }
+
+    fn memory_read(&mut self, addr: u32, value: u32) {
+        let access = self.rows.access(addr, value, value, false);
+        self.record(Record::MemoryAccess(access));
+    }
+
+    fn memory_write(&mut self, addr: u32, old_value: u32, value: u32) {
+        let access = self.rows.access(addr, old_value, value, true);
+        self.record(Record::MemoryAccess(access));
+    }
+}
+
+/// Reads the records of a trace file one at a time.
+pub struct TraceReader<R: Read> {
+    input: R,
+}
+
+impl<R: Read> TraceReader<R> {
+    /// Reads and checks the header of `input`.
+    pub fn new(mut input: R) -> Result<TraceReader<R>, TraceFileError> {
+        let mut header = [0; MAGIC.len() + 2];
+        input
+            .read_exact(&mut header)
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::UnexpectedEof => TraceFileError::NotATrace,
+                _ => error.into(),
+            })?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(TraceFileError::NotATrace);
+        }
+        let version = u16::from_le_bytes([header[MAGIC.len()], header[MAGIC.len() + 1]]);
+        if version != VERSION {
+            return Err(TraceFileError::UnsupportedVersion(version));
+        }
+        Ok(TraceReader { input })
+    }
+
+    /// The next record, or `None` at the end of the file.
+    pub fn read(&mut self) -> Result<Option<Record>, TraceFileError> {
+        let mut length = [0; 4];
+        // A clean end of file is only allowed between records
+        let mut read = 0;
+        while read < length.len() {
+            match self.input.read(&mut length[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(TraceFileError::Truncated),
+                Ok(n) => read += n,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        let length = u32::from_le_bytes(length);
+        let mut kind = [0];
+        self.read_exact(&mut kind)?;
+        let kind = kind[0];
+        let invalid = TraceFileError::InvalidRecord { kind, length };
+        match (kind, length as usize) {
+            (STEP_KIND, STEP_LENGTH) => {
+                let mut fields = [0; STEP_LENGTH - 1];
+                self.read_exact(&mut fields)?;
+                let word =
+                    |i: usize| u32::from_le_bytes(fields[4 * i..4 * i + 4].try_into().unwrap());
+                Ok(Some(Record::Step(Step {
+                    pc: word(0),
+                    instruction: Instruction::parse(word(1))?,
+                    rs1_value: word(2),
+                    rs2_value: word(3),
+                    rd_value: word(4),
+                })))
+            }
+            (MEMORY_ACCESS_KIND, MEMORY_ACCESS_LENGTH) => {
+                let mut fields = [0; MEMORY_ACCESS_LENGTH - 1];
+                self.read_exact(&mut fields)?;
+                let word = |i: usize| {
+                    u32::from_le_bytes(fields[16 + 4 * i..20 + 4 * i].try_into().unwrap())
+                };
+                Ok(Some(Record::MemoryAccess(MemoryAccess {
+                    timestamp: u64::from_le_bytes(fields[0..8].try_into().unwrap()),
+                    cycle: u64::from_le_bytes(fields[8..16].try_into().unwrap()),
+                    addr: word(0),
+                    value: word(1),
+                    old_value: word(2),
+                    is_write: match fields[28] {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(invalid),
+                    },
+                })))
+            }
+            _ => Err(invalid),
+        }
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), TraceFileError> {
+        self.input
+            .read_exact(buffer)
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::UnexpectedEof => TraceFileError::Truncated,
+                _ => error.into(),
+            })
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = Result<Record, TraceFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Reads a whole trace file into columns.
+pub fn read_trace<R: Read>(input: R) -> Result<ExecutionTrace, TraceFileError> {
+    let mut trace = ExecutionTrace::default();
+    for record in TraceReader::new(input)? {
+        match record? {
+            Record::Step(step) => trace.steps.push(step),
+            Record::MemoryAccess(access) => trace.memory.push(access),
+        }
+    }
+    Ok(trace)
+}
@@ -1,300 +1,372 @@
-use vm::{elf::Elf, vm::execution::run_program};
-
-fn run_program_and_check_output(elf_path: &str, expected_output: i32) {
-    println!("Testing {}", elf_path);
-    let elf_data = std::fs::read(elf_path).unwrap();
-    let program = Elf::load(&elf_data).unwrap();
+use crypto::curve::CurveError;
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::execution::{ExecutionError, run_program},
+};
+
+// Programs are assembled from source, so no RISC-V toolchain is needed to run these tests
+fn assemble_program(source_path: &str) -> Elf {
+    println!("Testing {}", source_path);
+    let source = std::fs::read_to_string(source_path).unwrap();
+    assemble(&source).unwrap()
+}
+
+fn run_program_and_check_output(source_path: &str, expected_output: i32) {
+    let program = assemble_program(source_path);
     println!("Program entry: 0x{:08x}", program.entry_point);
     program.image.iter().for_each(|(addr, word)| {
         println!("0x{:08x}: 0x{:08x}", addr, word);
     });
-    let results = run_program(program.image, program.entry_point);
+    let results = run_program(program.image, program.entry_point).unwrap();
 
     assert!(results.0 == expected_output);
 }
 
 #[test]
 fn test_basic_program() {
-    run_program_and_check_output("./program_artifacts/asm/basic_program.elf", 0);
+    run_program_and_check_output("./programs/asm/basic_program.s", 0);
 }
 
 #[test]
 fn test_addi_one() {
-    run_program_and_check_output("./program_artifacts/asm/addi_one.elf", 1);
+    run_program_and_check_output("./programs/asm/addi_one.s", 1);
 }
 
 #[test]
 fn test_addi_minus_one() {
-    run_program_and_check_output("./program_artifacts/asm/addi_minus_one.elf", -1);
+    run_program_and_check_output("./programs/asm/addi_minus_one.s", -1);
 }
 
 #[test]
 fn test_addi_max() {
-    run_program_and_check_output("./program_artifacts/asm/addi_max.elf", 2047);
+    run_program_and_check_output("./programs/asm/addi_max.s", 2047);
 }
 
 #[test]
 fn test_addi_min() {
-    run_program_and_check_output("./program_artifacts/asm/addi_min.elf", -2048);
+    run_program_and_check_output("./programs/asm/addi_min.s", -2048);
 }
 
 #[test]
 fn test_addi_reg() {
-    run_program_and_check_output("./program_artifacts/asm/addi_reg.elf", 30);
+    run_program_and_check_output("./programs/asm/addi_reg.s", 30);
 }
 
 #[test]
 fn test_addi_reg_max() {
-    run_program_and_check_output("./program_artifacts/asm/addi_reg_max.elf", 2080);
+    run_program_and_check_output("./programs/asm/addi_reg_max.s", 2080);
 }
 
 #[test]
 fn test_addi_reg_min() {
-    run_program_and_check_output("./program_artifacts/asm/addi_reg_min.elf", -2070);
+    run_program_and_check_output("./programs/asm/addi_reg_min.s", -2070);
 }
 
 #[test]
 fn test_addi_255() {
-    run_program_and_check_output("./program_artifacts/asm/addi_255.elf", 255);
+    run_program_and_check_output("./programs/asm/addi_255.s", 255);
 }
 
 #[test]
 fn test_add() {
-    run_program_and_check_output("./program_artifacts/asm/add.elf", 30);
+    run_program_and_check_output("./programs/asm/add.s", 30);
 }
 
 #[test]
 fn test_add_neg() {
-    run_program_and_check_output("./program_artifacts/asm/add_neg.elf", 10);
+    run_program_and_check_output("./programs/asm/add_neg.s", 10);
 }
 
 #[test]
 fn test_add_max() {
-    run_program_and_check_output("./program_artifacts/asm/add_max.elf", i32::MAX);
+    run_program_and_check_output("./programs/asm/add_max.s", i32::MAX);
 }
 
 #[test]
 fn test_add_max_plus_one() {
-    run_program_and_check_output("./program_artifacts/asm/add_max_plus_one.elf", i32::MIN);
+    run_program_and_check_output("./programs/asm/add_max_plus_one.s", i32::MIN);
 }
 
 #[test]
 fn test_add_min() {
-    run_program_and_check_output("./program_artifacts/asm/add_min.elf", i32::MIN);
+    run_program_and_check_output("./programs/asm/add_min.s", i32::MIN);
 }
 
 #[test]
 fn test_add_min_minus_one() {
-    run_program_and_check_output("./program_artifacts/asm/add_min_minus_one.elf", i32::MAX);
+    run_program_and_check_output("./programs/asm/add_min_minus_one.s", i32::MAX);
 }
 
 #[test]
 fn test_andi() {
-    run_program_and_check_output("./program_artifacts/asm/andi.elf", 0x00);
+    run_program_and_check_output("./programs/asm/andi.s", 0x00);
 }
 
 #[test]
 fn test_andi_one() {
-    run_program_and_check_output("./program_artifacts/asm/andi_one.elf", 0x01);
+    run_program_and_check_output("./programs/asm/andi_one.s", 0x01);
 }
 
 #[test]
 fn test_andi_one_and_zero() {
-    run_program_and_check_output("./program_artifacts/asm/andi_one_and_zero.elf", 0x00);
+    run_program_and_check_output("./programs/asm/andi_one_and_zero.s", 0x00);
 }
 
 #[test]
 fn test_andi_one_and_two() {
-    run_program_and_check_output("./program_artifacts/asm/andi_one_and_two.elf", 0x00);
+    run_program_and_check_output("./programs/asm/andi_one_and_two.s", 0x00);
 }
 
 #[test]
 fn test_andi_max() {
-    run_program_and_check_output("./program_artifacts/asm/andi_max.elf", 0xFFFFFFFFu32 as i32);
+    run_program_and_check_output("./programs/asm/andi_max.s", 0xFFFFFFFFu32 as i32);
 }
 
 #[test]
 fn test_ori() {
-    run_program_and_check_output("./program_artifacts/asm/ori.elf", 0x00);
+    run_program_and_check_output("./programs/asm/ori.s", 0x00);
 }
 
 #[test]
 fn test_ori_one() {
-    run_program_and_check_output("./program_artifacts/asm/ori_one.elf", 0x01);
+    run_program_and_check_output("./programs/asm/ori_one.s", 0x01);
 }
 
 #[test]
 fn test_ori_one_and_one() {
-    run_program_and_check_output("./program_artifacts/asm/ori_one_and_one.elf", 0x01);
+    run_program_and_check_output("./programs/asm/ori_one_and_one.s", 0x01);
 }
 
 #[test]
 fn test_ori_two_and_one() {
-    run_program_and_check_output("./program_artifacts/asm/ori_two_and_one.elf", 0x03);
+    run_program_and_check_output("./programs/asm/ori_two_and_one.s", 0x03);
 }
 
 #[test]
 fn test_ori_five_and_four() {
-    run_program_and_check_output("./program_artifacts/asm/ori_five_and_four.elf", 0x05);
+    run_program_and_check_output("./programs/asm/ori_five_and_four.s", 0x05);
 }
 
 #[test]
 fn test_ori_three_and_five() {
-    run_program_and_check_output("./program_artifacts/asm/ori_three_and_five.elf", 0x07);
+    run_program_and_check_output("./programs/asm/ori_three_and_five.s", 0x07);
 }
 
 #[test]
 fn test_ori_max() {
-    run_program_and_check_output("./program_artifacts/asm/ori_max.elf", 0xFFFFFFFFu32 as i32);
+    run_program_and_check_output("./programs/asm/ori_max.s", 0xFFFFFFFFu32 as i32);
 }
 
 #[test]
 fn test_xori() {
-    run_program_and_check_output("./program_artifacts/asm/xori.elf", 0x00);
+    run_program_and_check_output("./programs/asm/xori.s", 0x00);
 }
 
 #[test]
 fn test_xori_one() {
-    run_program_and_check_output("./program_artifacts/asm/xori_one.elf", 0x01);
+    run_program_and_check_output("./programs/asm/xori_one.s", 0x01);
 }
 
 #[test]
 fn test_xori_one_and_one() {
-    run_program_and_check_output("./program_artifacts/asm/xori_one_and_one.elf", 0x00);
+    run_program_and_check_output("./programs/asm/xori_one_and_one.s", 0x00);
 }
 
 #[test]
 fn test_xori_max() {
-    run_program_and_check_output("./program_artifacts/asm/xori_max.elf", 0xFFFFFFFFu32 as i32);
+    run_program_and_check_output("./programs/asm/xori_max.s", 0xFFFFFFFFu32 as i32);
 }
 
 #[test]
 fn test_xori_negate() {
-    run_program_and_check_output("./program_artifacts/asm/xori_negate.elf", 0x01);
+    run_program_and_check_output("./programs/asm/xori_negate.s", 0x01);
 }
 
 #[test]
 fn test_slti() {
-    run_program_and_check_output("./program_artifacts/asm/slti.elf", 0);
+    run_program_and_check_output("./programs/asm/slti.s", 0);
 }
 
 #[test]
 fn test_slti_one() {
-    run_program_and_check_output("./program_artifacts/asm/slti_one.elf", 1);
+    run_program_and_check_output("./programs/asm/slti_one.s", 1);
 }
 
 #[test]
 fn test_slti_minus_one() {
-    run_program_and_check_output("./program_artifacts/asm/slti_minus_one.elf", 0);
+    run_program_and_check_output("./programs/asm/slti_minus_one.s", 0);
 }
 
 #[test]
 fn test_slti_negative() {
-    run_program_and_check_output("./program_artifacts/asm/slti_negative.elf", 1);
+    run_program_and_check_output("./programs/asm/slti_negative.s", 1);
 }
 
 #[test]
 fn test_slti_negative_minus() {
-    run_program_and_check_output("./program_artifacts/asm/slti_negative_minus.elf", 0);
+    run_program_and_check_output("./programs/asm/slti_negative_minus.s", 0);
 }
 
 #[test]
 fn test_sltiu() {
-    run_program_and_check_output("./program_artifacts/asm/sltiu.elf", 0);
+    run_program_and_check_output("./programs/asm/sltiu.s", 0);
 }
 
 #[test]
 fn test_sltiu_one() {
-    run_program_and_check_output("./program_artifacts/asm/sltiu_one.elf", 1);
+    run_program_and_check_output("./programs/asm/sltiu_one.s", 1);
 }
 
 #[test]
 fn test_sltiu_negative() {
-    run_program_and_check_output("./program_artifacts/asm/sltiu_negative.elf", 0);
+    run_program_and_check_output("./programs/asm/sltiu_negative.s", 0);
 }
 
 #[test]
 fn test_sltiu_two_negatives() {
-    run_program_and_check_output("./program_artifacts/asm/sltiu_two_negatives.elf", 1);
+    run_program_and_check_output("./programs/asm/sltiu_two_negatives.s", 1);
 }
 
 #[test]
 fn test_slli() {
-    run_program_and_check_output("./program_artifacts/asm/slli.elf", 0);
+    run_program_and_check_output("./programs/asm/slli.s", 0);
 }
 
 #[test]
 fn test_slli_one() {
-    run_program_and_check_output("./program_artifacts/asm/slli_one.elf", 0);
+    run_program_and_check_output("./programs/asm/slli_one.s", 0);
 }
 
 #[test]
 fn test_slli_one_one() {
-    run_program_and_check_output("./program_artifacts/asm/slli_one_one.elf", 2);
+    run_program_and_check_output("./programs/asm/slli_one_one.s", 2);
 }
 
 #[test]
 fn test_slli_one_zero() {
-    run_program_and_check_output("./program_artifacts/asm/slli_one_zero.elf", 1);
+    run_program_and_check_output("./programs/asm/slli_one_zero.s", 1);
 }
 
 #[test]
 fn test_slli_ff_four() {
-    run_program_and_check_output("./program_artifacts/asm/slli_ff_four.elf", 0xFF0);
+    run_program_and_check_output("./programs/asm/slli_ff_four.s", 0xFF0);
 }
 
 #[test]
 fn test_slli_max() {
-    run_program_and_check_output("./program_artifacts/asm/slli_max.elf", 0xFFFFFFF0u32 as i32);
+    run_program_and_check_output("./programs/asm/slli_max.s", 0xFFFFFFF0u32 as i32);
 }
 
 #[test]
 fn test_slli_max_half() {
-    run_program_and_check_output(
-        "./program_artifacts/asm/slli_max_half.elf",
-        0xFFFF8000u32 as i32,
-    );
+    run_program_and_check_output("./programs/asm/slli_max_half.s", 0xFFFF8000u32 as i32);
 }
 
 #[test]
 fn test_slli_max_max() {
-    run_program_and_check_output(
-        "./program_artifacts/asm/slli_max_max.elf",
-        0x80000000u32 as i32,
-    );
+    run_program_and_check_output("./programs/asm/slli_max_max.s", 0x80000000u32 as i32);
 }
 
 #[test]
 fn test_srli() {
-    run_program_and_check_output("./program_artifacts/asm/srli.elf", 0);
+    run_program_and_check_output("./programs/asm/srli.s", 0);
 }
 
 #[test]
 fn test_srli_one() {
-    run_program_and_check_output("./program_artifacts/asm/srli_one.elf", 0);
+    run_program_and_check_output("./programs/asm/srli_one.s", 0);
 }
 
 #[test]
 fn test_srli_one_zero() {
-    run_program_and_check_output("./program_artifacts/asm/srli_one_zero.elf", 1);
+    run_program_and_check_output("./programs/asm/srli_one_zero.s", 1);
 }
 
 #[test]
 fn test_srli_one_one() {
-    run_program_and_check_output("./program_artifacts/asm/srli_one_one.elf", 0);
+    run_program_and_check_output("./programs/asm/srli_one_one.s", 0);
 }
 
 #[test]
 fn test_srli_two_one() {
-    run_program_and_check_output("./program_artifacts/asm/srli_two_one.elf", 1);
+    run_program_and_check_output("./programs/asm/srli_two_one.s", 1);
 }
 
 #[test]
 fn test_srli_max() {
-    run_program_and_check_output("./program_artifacts/asm/srli_max.elf", 0x0FFFFFFFu32 as i32);
+    run_program_and_check_output("./programs/asm/srli_max.s", 0x0FFFFFFFu32 as i32);
 }
 
 #[test]
 fn test_srli_max_max() {
-    run_program_and_check_output("./program_artifacts/asm/srli_max_max.elf", 0x00000001);
+    run_program_and_check_output("./programs/asm/srli_max_max.s", 0x00000001);
+}
+
+fn run_program_and_check_error(source_path: &str) -> ExecutionError {
+    let program = assemble_program(source_path);
+    run_program(program.image, program.entry_point).unwrap_err()
+}
+
+#[test]
+fn test_poseidon2_permute() {
+    run_program_and_check_output("./programs/asm/poseidon2_permute.s", 703824558);
+}
+
+#[test]
+fn test_poseidon2_non_canonical() {
+    let error = run_program_and_check_error("./programs/asm/poseidon2_non_canonical.s");
+    assert!(matches!(
+        error,
+        ExecutionError::NonCanonicalFieldElement(0x78000001)
+    ));
+}
+
+#[test]
+fn test_unknown_syscall() {
+    let error = run_program_and_check_error("./programs/asm/unknown_syscall.s");
+    assert!(matches!(error, ExecutionError::UnknownSyscall(2047)));
+}
+
+#[test]
+fn test_secp256k1_double() {
+    run_program_and_check_output("./programs/asm/secp256k1_double.s", 0x5c709ee5);
+}
+
+#[test]
+fn test_secp256k1_not_on_curve() {
+    let error = run_program_and_check_error("./programs/asm/secp256k1_not_on_curve.s");
+    assert!(matches!(
+        error,
+        ExecutionError::Curve(CurveError::NotOnCurve)
+    ));
+}
+
+#[test]
+fn test_ed25519_add() {
+    run_program_and_check_output("./programs/asm/ed25519_add.s", 0xd3f8e25cu32 as i32);
+}
+
+#[test]
+fn test_uint256_mul_mod() {
+    run_program_and_check_output("./programs/asm/uint256_mul_mod.s", 0xe08e3c55u32 as i32);
+}
+
+#[test]
+fn test_uint256_mul_mod_zero_modulus() {
+    run_program_and_check_output(
+        "./programs/asm/uint256_mul_mod_zero.s",
+        0xc067c568u32 as i32,
+    );
+}
+
+#[test]
+fn test_custom_poseidon2_permute() {
+    run_program_and_check_output("./programs/asm/custom_poseidon2_permute.s", 703824558);
+}
+
+#[test]
+fn test_custom_uint256_add() {
+    // (2^256 - 1) + 2 wraps to 1 with a carry out of 1
+    run_program_and_check_output("./programs/asm/custom_uint256_add.s", 2);
 }
@@ -0,0 +1,207 @@
+use vm::{
+    assembler::{AssemblerError, LineError, TEXT_START, assemble},
+    elf::Elf,
+    vm::{execution::run_program, instructions::Instruction},
+};
+
+fn code(program: &Elf) -> Vec<u32> {
+    program
+        .executable_ranges
+        .iter()
+        .flat_map(|range| range.clone().step_by(4))
+        .map(|addr| program.image[&addr])
+        .collect()
+}
+
+fn check_code(source: &str, expected: &[&str]) {
+    let program = assemble(source).unwrap();
+    let listing: Vec<_> = code(&program)
+        .into_iter()
+        .map(|word| Instruction::parse(word).unwrap().to_string())
+        .collect();
+    assert_eq!(listing, expected);
+}
+
+#[test]
+fn test_assemble_matches_toolchain() {
+    // Programs with data are laid out differently than by the linker, so `la` differs
+    for entry in std::fs::read_dir("./programs/asm").unwrap() {
+        let path = entry.unwrap().path();
+        let source = std::fs::read_to_string(&path).unwrap();
+        if source.contains(".data") {
+            continue;
+        }
+        let artifact = path
+            .to_str()
+            .unwrap()
+            .replace("programs", "program_artifacts")
+            .replace(".s", ".elf");
+        let expected = Elf::load(&std::fs::read(artifact).unwrap()).unwrap();
+        let program = assemble(&source).unwrap();
+        assert_eq!(code(&program), code(&expected), "{path:?}");
+    }
+}
+
+#[test]
+fn test_assemble_pseudo_instructions() {
+    let source = "
+    .globl main
+main:
+    li a0, 42
+    li a1, 0x12345678
+    li a2, -4096
+    mv a3, a0
+    not a4, a0
+    neg a5, a0
+    seqz a6, a0
+    snez a7, a0
+    nop
+    ret";
+    check_code(
+        source,
+        &[
+            "li a0, 42",
+            "lui a1, 0x12345",
+            "addi a1, a1, 1656",
+            "lui a2, 0xfffff",
+            "mv a3, a0",
+            "not a4, a0",
+            "neg a5, a0",
+            "seqz a6, a0",
+            "snez a7, a0",
+            "nop",
+            "ret",
+        ],
+    );
+}
+
+#[test]
+fn test_assemble_loads_and_stores() {
+    let widths = ["b", "h", "w", "bu", "hu"];
+    let source: String = widths
+        .iter()
+        .map(|width| format!("l{width} a0, -2(a1)\n"))
+        .chain(
+            widths[..3]
+                .iter()
+                .map(|width| format!("s{width} a0, 2(a1)\n")),
+        )
+        .collect();
+    let expected: Vec<_> = source.lines().collect();
+    check_code(&format!("main:\n{source}"), &expected);
+    assert!(assemble("main:\n  sbu a0, 0(a1)").is_err());
+}
+
+#[test]
+fn test_assemble_labels() {
+    let source = "
+main:
+    beqz a0, end
+loop:
+    addi a0, a0, -1
+    bgt a0, zero, loop
+    j end
+end:
+    jalr zero, 0(ra)";
+    check_code(
+        source,
+        &[
+            "beqz a0, 16",
+            "addi a0, a0, -1",
+            "blt zero, a0, -4",
+            "j 4",
+            "ret",
+        ],
+    );
+}
+
+#[test]
+fn test_assemble_and_run() {
+    // Sums the words of `values` in a function called from main
+    let source = r#"
+    .text
+    .globl main
+main:
+    mv s0, ra               # call clobbers ra
+    la a0, values
+    la a1, count
+    lw a1, 0(a1)
+    call sum
+    mv ra, s0
+    ret
+
+sum:
+    li t0, 0
+loop:
+    beqz a1, done
+    lw t1, 0(a0)
+    add t0, t0, t1
+    addi a0, a0, 4
+    addi a1, a1, -1
+    j loop
+done:
+    mv a0, t0
+    ret
+
+    .data
+    .p2align 2
+values:
+    .word 1, 2, 3, 0x100, -1
+    .byte 0xff
+    .p2align 2
+count:
+    .word 5
+"#;
+    let program = assemble(source).unwrap();
+    assert_eq!(program.entry_point, TEXT_START);
+    assert_eq!(program.symbols[&TEXT_START], "main");
+    let (count_addr, _) = program
+        .symbols
+        .iter()
+        .find(|(_, name)| *name == "count")
+        .unwrap();
+    assert_eq!(program.image[count_addr], 5);
+    assert_eq!(program.image[&(count_addr - 4)], 0xff);
+    let (output, _) = run_program(program.image, program.entry_point).unwrap();
+    assert_eq!(output, 0x100 + 5);
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = |source: &str| match assemble(source) {
+        Err(AssemblerError::Line { line, error }) => (line, error),
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    };
+    assert!(matches!(
+        error("main:\n  frob a0, a1"),
+        (2, LineError::UnknownInstruction(_))
+    ));
+    assert!(matches!(
+        error("main:\n  add a0, a1"),
+        (2, LineError::InvalidOperands(_))
+    ));
+    assert!(matches!(
+        error("main:\n  add a0, a1, x32"),
+        (2, LineError::InvalidRegister(_))
+    ));
+    assert!(matches!(
+        error("main:\n\n  j nowhere"),
+        (3, LineError::UndefinedSymbol(_))
+    ));
+    assert!(matches!(
+        error("main:\nmain:"),
+        (2, LineError::DuplicateSymbol(_))
+    ));
+    assert!(matches!(
+        error("main:\n  addi a0, a0, 2048"),
+        (2, LineError::Encode(_))
+    ));
+    assert!(matches!(
+        error("main:\n  .frob"),
+        (2, LineError::UnknownDirective(_))
+    ));
+    assert!(matches!(
+        assemble("start:\n  ret"),
+        Err(AssemblerError::MissingEntryPoint)
+    ));
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{assert_same_state, step_to_end};
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::execution::{ExecutionError, Executor},
+};
+
+#[test]
+fn test_blocks_match_stepping() {
+    for entry in std::fs::read_dir("./program_artifacts/asm").unwrap() {
+        let path = entry.unwrap().path();
+        let program = Elf::load(&std::fs::read(&path).unwrap()).unwrap();
+        let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+        let mut run = Executor::new(program.image, program.entry_point);
+        let stepped_result = step_to_end(&mut stepped).map_err(|error| error.to_string());
+        let run_result = run.run().map_err(|error| error.to_string());
+        assert_eq!(stepped_result, run_result, "{path:?}");
+        assert_same_state(&stepped, &run, &path.to_string_lossy());
+    }
+}
+
+const LOOP: &str = "
+main:
+    li a0, 0
+    li t0, 10
+loop:
+    addi a0, a0, 3
+    addi t0, t0, -1
+    bnez t0, loop
+    ret
+";
+
+#[test]
+fn test_blocks_count_cycles() {
+    let program = assemble(LOOP).unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    executor.run().unwrap();
+    assert_eq!(executor.registers.read(10), 30);
+    assert_eq!(executor.cycles, 2 + 10 * 3 + 1);
+}
+
+#[test]
+fn test_run_stops_at_breakpoints() {
+    let program = assemble(LOOP).unwrap();
+    let loop_addr = program.symbol_address("loop").unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    executor.breakpoints.insert(loop_addr);
+    for iteration in 0..10 {
+        executor.run().unwrap();
+        assert_eq!(executor.pc, loop_addr);
+        assert_eq!(executor.registers.read(10), 3 * iteration);
+    }
+    executor.run().unwrap();
+    assert!(executor.is_halted());
+    assert_eq!(executor.registers.read(10), 30);
+}
+
+#[test]
+fn test_run_reports_errors_like_stepping() {
+    let program = assemble("main:\n  addi a0, a0, 1\n  j 8").unwrap();
+    let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+    let mut run = Executor::new(program.image, program.entry_point);
+    assert!(matches!(
+        step_to_end(&mut stepped),
+        Err(ExecutionError::InvalidPc(_))
+    ));
+    assert!(matches!(run.run(), Err(ExecutionError::InvalidPc(_))));
+    assert_same_state(&stepped, &run, "error");
+}
+
+#[test]
+fn test_store_into_current_block() {
+    // Overwrites an instruction further down the block that is running
+    let source = "
+main:
+    la t1, target
+    li t0, 0x00a50513   # addi a0, a0, 10
+    sw t0, 0(t1)
+    addi a0, a0, 1
+target:
+    addi a0, a0, 1
+    ret
+";
+    let program = assemble(source).unwrap();
+    let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+    let mut run = Executor::new(program.image, program.entry_point);
+    step_to_end(&mut stepped).unwrap();
+    run.run().unwrap();
+    assert_eq!(run.registers.read(10), 11);
+    assert_same_state(&stepped, &run, "store into current block");
+}
@@ -0,0 +1,197 @@
+use vm::{
+    builder::{BuildError, ProgramBuilder},
+    elf::Elf,
+    vm::{
+        disassembler::disassemble,
+        execution::run_program,
+        instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+    },
+};
+
+const RET: Instruction = Instruction::JumpAndLinkRegister {
+    base: 1,
+    dst: 0,
+    offset: 0,
+};
+
+fn li(dst: u32, imm: i32) -> Instruction {
+    Instruction::ArithImm {
+        dst,
+        src: 0,
+        imm,
+        op: ArithOp::Add,
+    }
+}
+
+fn run(program: Elf) -> i32 {
+    run_program(program.image, program.entry_point).unwrap().0
+}
+
+#[test]
+fn test_branch_max_negative_offset() {
+    let program = ProgramBuilder::new()
+        .code(0x2000, &[li(10, 42), RET])
+        .code(
+            0x3000,
+            &[Instruction::Branch {
+                src1: 0,
+                src2: 0,
+                cond: Comparison::Equal,
+                offset: -4096,
+            }],
+        )
+        .entry_point(0x3000)
+        .build()
+        .unwrap();
+    assert_eq!(run(program), 42);
+}
+
+#[test]
+fn test_jump_max_negative_offset() {
+    let program = ProgramBuilder::new()
+        .code(0x0010_0000, &[li(10, 7), RET])
+        .code(
+            0x0020_0000,
+            &[Instruction::JumpAndLink {
+                dst: 0,
+                offset: -0x0010_0000,
+            }],
+        )
+        .entry_point(0x0020_0000)
+        .build()
+        .unwrap();
+    assert_eq!(run(program), 7);
+}
+
+#[test]
+fn test_negative_load_store_offsets() {
+    // Copies the word before `0x4004` to the word before `0x4008` and loads it back
+    let program = ProgramBuilder::new()
+        .code(
+            0x1000,
+            &[
+                Instruction::LoadUpperImm {
+                    dst: 11,
+                    imm: 0x4000,
+                },
+                Instruction::Load {
+                    dst: 12,
+                    offset: -4,
+                    base: 11,
+                    width: LoadStoreWidth::Word,
+                },
+                Instruction::ArithImm {
+                    dst: 11,
+                    src: 11,
+                    imm: 8,
+                    op: ArithOp::Add,
+                },
+                Instruction::Store {
+                    src: 12,
+                    offset: -4,
+                    base: 11,
+                    width: LoadStoreWidth::Word,
+                },
+                Instruction::Load {
+                    dst: 10,
+                    offset: -4,
+                    base: 11,
+                    width: LoadStoreWidth::Word,
+                },
+                RET,
+            ],
+        )
+        .data(0x3ffc, &[1234, 0])
+        .entry_point(0x1000)
+        .build()
+        .unwrap();
+    assert_eq!(run(program), 1234);
+}
+
+#[test]
+fn test_built_program_disassembles() {
+    let program = ProgramBuilder::new()
+        .code(0x1000, &[li(10, 1), RET])
+        .symbol(0x1000, "main")
+        .entry_point(0x1000)
+        .build()
+        .unwrap();
+    assert_eq!(program.executable_ranges.first(), Some(&(0x1000..0x1008)));
+    assert_eq!(
+        disassemble(&program),
+        "\n00001000 <main>:\n    1000:\t00100513\tli a0, 1\n    1004:\t00008067\tret\n"
+    );
+}
+
+#[test]
+fn test_build_errors() {
+    let error = |builder: ProgramBuilder| builder.build().err().unwrap();
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0x1002, &[RET])
+                .entry_point(0x1002)
+        ),
+        BuildError::UnalignedAddress(0x1002)
+    ));
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0x1000, &[RET, RET])
+                .data(0x1004, &[0])
+                .entry_point(0x1000)
+        ),
+        BuildError::Overlap(0x1004)
+    ));
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0x1000, &[RET, li(10, 4096)])
+                .entry_point(0x1000)
+        ),
+        BuildError::Encode { addr: 0x1004, .. }
+    ));
+    assert!(matches!(
+        error(ProgramBuilder::new().data(0xfffffffc, &[0, 0])),
+        BuildError::AddressOverflow(0xfffffffc)
+    ));
+    // The end of a code range must fit in a u32, data may fill the last word
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0xfffffffc, &[RET])
+                .entry_point(0xfffffffc)
+        ),
+        BuildError::AddressOverflow(0xfffffffc)
+    ));
+    // A code block running past 2^32
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0xfffffff8, &[RET, RET, RET])
+                .entry_point(0xfffffff8)
+        ),
+        BuildError::AddressOverflow(0xfffffff8)
+    ));
+    assert!(
+        ProgramBuilder::new()
+            .code(0xfffffff8, &[RET])
+            .data(0xfffffffc, &[0])
+            .entry_point(0xfffffff8)
+            .build()
+            .is_ok()
+    );
+    assert!(matches!(
+        error(ProgramBuilder::new().code(0x1000, &[RET])),
+        BuildError::MissingEntryPoint
+    ));
+    assert!(matches!(
+        error(
+            ProgramBuilder::new()
+                .code(0x1000, &[RET])
+                .data(0x2000, &[0])
+                .entry_point(0x2000)
+        ),
+        BuildError::InvalidEntryPoint(0x2000)
+    ));
+}
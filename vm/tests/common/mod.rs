@@ -0,0 +1,64 @@
+// Helpers shared by the integration tests. Each test uses only some of them.
+#![allow(dead_code)]
+
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::execution::{ExecutionError, Executor},
+};
+
+// Stores 7 over the 5 at `value`, then loads it into a0
+pub const STORE_AND_LOAD: &str = "
+main:
+    la t0, value
+    li t1, 7
+    sw t1, 0(t0)
+    lw a0, 0(t0)
+    ret
+value:
+    .word 5
+";
+
+// Adds 5 to a0 three times, stops at an ebreak, then loads the 42 at `value` into a1. For the
+// debugger and the gdb stub
+pub const DEBUGGEE: &str = "
+main:
+    li a0, 0
+    li t0, 3
+loop:
+    addi a0, a0, 5
+    addi t0, t0, -1
+    bnez t0, loop
+    ebreak
+    la t1, value
+    lw a1, 0(t1)
+    ret
+value:
+    .word 42
+";
+
+// Fails with a misaligned load at 0x10004
+pub const MISALIGNED_LOAD: &str = "main:\n    li t0, 2\n    lw a0, 0(t0)\n    ret";
+
+// The interpreter stepping one instruction at a time, as the reference for the faster ways of
+// running a program
+pub fn step_to_end(executor: &mut Executor) -> Result<(), ExecutionError> {
+    while !executor.is_halted() && executor.cycles < executor.cycle_limit {
+        executor.step()?;
+    }
+    Ok(())
+}
+
+pub fn assert_same_state(stepped: &Executor, run: &Executor, name: &str) {
+    assert_eq!(stepped.pc, run.pc, "{name}");
+    assert_eq!(stepped.registers, run.registers, "{name}");
+    assert_eq!(stepped.cycles, run.cycles, "{name}");
+    assert_eq!(stepped.memory.words(), run.memory.words(), "{name}");
+}
+
+// An executor at the start of the assembled `source`, and the program for its symbols
+pub fn load(source: &str) -> (Executor, Elf) {
+    let program = assemble(source).unwrap();
+    let executor = Executor::new(program.image.clone(), program.entry_point);
+    (executor, program)
+}
@@ -0,0 +1,106 @@
+use vm::{assembler::assemble, elf::Elf, vm::execution::Executor};
+
+// riscv-tests style programs report their outcome by writing to `tohost`:
+// 1 when every case passed, (case number << 1) | 1 for the first case that failed.
+const TOHOST_SYMBOL: &str = "tohost";
+const MAX_STEPS: usize = 1_000_000;
+// Tests the VM fails by design, with the reason. They are checked to still fail, so that
+// the list is updated once one passes.
+const EXPECTED_FAILURES: [(&str, &str); 1] = [(
+    "rv32ui-ma_data",
+    "misaligned loads and stores trap, which the spec allows",
+)];
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Pass,
+    Fail(u32),
+    MissingTohost,
+    Halted,
+    Timeout,
+    Error(String),
+}
+
+fn run_tohost_test(program: Elf) -> Outcome {
+    let Some(tohost) = program.symbol_address(TOHOST_SYMBOL) else {
+        return Outcome::MissingTohost;
+    };
+    let mut executor = Executor::new(program.image, program.entry_point);
+    for _ in 0..MAX_STEPS {
+        if executor.is_halted() {
+            return Outcome::Halted;
+        }
+        if let Err(error) = executor.step() {
+            return Outcome::Error(error.to_string());
+        }
+        match executor.memory.read_word(tohost) {
+            None | Some(0) => {}
+            Some(1) => return Outcome::Pass,
+            Some(code) => return Outcome::Fail(code >> 1),
+        }
+    }
+    Outcome::Timeout
+}
+
+#[test]
+fn test_riscv_tests() {
+    let mut paths: Vec<_> = std::fs::read_dir("./program_artifacts/riscv-tests")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let mut failures = Vec::new();
+    let mut unexpected_passes = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let program = Elf::load(&std::fs::read(&path).unwrap()).unwrap();
+        let outcome = run_tohost_test(program);
+        let expected_failure = EXPECTED_FAILURES
+            .iter()
+            .find(|(failing, _)| *failing == name)
+            .map(|(_, reason)| reason);
+        match (&outcome, expected_failure) {
+            (Outcome::Pass, _) => println!("{name:<20} pass"),
+            (_, Some(reason)) => println!("{name:<20} expected failure: {reason}"),
+            (Outcome::Fail(case), None) => println!("{name:<20} FAIL (test case {case})"),
+            (outcome, None) => println!("{name:<20} FAIL ({outcome:?})"),
+        }
+        match (outcome == Outcome::Pass, expected_failure.is_some()) {
+            (true, true) => unexpected_passes.push(name),
+            (false, false) => failures.push(name),
+            _ => {}
+        }
+    }
+    assert!(failures.is_empty(), "Failing tests: {failures:?}");
+    assert!(
+        unexpected_passes.is_empty(),
+        "Expected failures that pass: {unexpected_passes:?}"
+    );
+}
+
+#[test]
+fn test_tohost_failure_is_reported() {
+    let source = "
+main:
+    li gp, 3
+    slli gp, gp, 1
+    ori gp, gp, 1
+    la t5, tohost
+    sw gp, 0(t5)
+spin:
+    j spin
+
+    .data
+tohost:
+    .word 0, 0";
+    let program = assemble(source).unwrap();
+    assert_eq!(run_tohost_test(program), Outcome::Fail(3));
+}
+
+#[test]
+fn test_tohost_missing() {
+    let program = assemble("main:\n  ret").unwrap();
+    assert_eq!(run_tohost_test(program), Outcome::MissingTohost);
+}
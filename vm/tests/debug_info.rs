@@ -0,0 +1,321 @@
+#![cfg(feature = "debug-info")]
+
+use gimli::{
+    Encoding, Format, LineEncoding,
+    write::{Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections},
+};
+use vm::{
+    debug_info::{DebugInfo, SourceLocation},
+    debugger::Debugger,
+    elf::Elf,
+    vm::{
+        disassembler::disassemble_with_source,
+        execution::Executor,
+        profiler::{function_name, function_name_with_debug_info},
+        trace_file::TraceWriter,
+    },
+};
+
+const TEXT_ADDR: u32 = 0x10000;
+// li a0, 5; ret; addi a0, a0, 1; ret
+const TEXT: [u32; 4] = [0x00500513, 0x00008067, 0x00150513, 0x00008067];
+const MAIN: &str = "_ZN4demo4main17h0123456789abcdefE";
+
+// Line table of the code, a row is an offset into it, a file and a line
+fn dwarf_sections(directory: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(directory.as_bytes().to_vec()),
+        LineString::String(b"main.rs".to_vec()),
+        None,
+    );
+    let main_file = program.add_file(
+        LineString::String(b"main.rs".to_vec()),
+        program.default_directory(),
+        None,
+    );
+    let lib = program.add_directory(LineString::String(b"lib".to_vec()));
+    let util_file = program.add_file(LineString::String(b"util.rs".to_vec()), lib, None);
+    program.begin_sequence(Some(Address::Constant(TEXT_ADDR.into())));
+    for (offset, file, line) in [(0, main_file, 3), (4, main_file, 4), (8, util_file, 10)] {
+        program.row().address_offset = offset;
+        program.row().file = file;
+        program.row().line = line;
+        program.generate_row();
+    }
+    program.end_sequence(12);
+    dwarf.unit.line_program = program;
+    let root = dwarf.unit.root();
+    let root = dwarf.unit.get_mut(root);
+    root.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(b"main.rs".to_vec()),
+    );
+    root.set(
+        gimli::DW_AT_comp_dir,
+        AttributeValue::String(directory.as_bytes().to_vec()),
+    );
+
+    let mut sections = Sections::new(EndianVec::new(gimli::LittleEndian));
+    dwarf.write(&mut sections).unwrap();
+    let mut result = Vec::new();
+    sections
+        .for_each(|id, data| {
+            if !data.slice().is_empty() {
+                result.push((id.name(), data.slice().to_vec()));
+            }
+            Ok::<(), ()>(())
+        })
+        .unwrap();
+    result
+}
+
+fn program() -> Vec<u8> {
+    program_in("/src/demo")
+}
+
+// An executable with the code at TEXT_ADDR, a symbol for each function and the line table of
+// sources in `directory`
+fn program_in(directory: &str) -> Vec<u8> {
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; 16];
+    for (name, addr, size) in [(MAIN, TEXT_ADDR, 8), ("helper", TEXT_ADDR + 8, 0)] {
+        let name_offset = strtab.len() as u32;
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+        symtab.extend_from_slice(&name_offset.to_le_bytes());
+        symtab.extend_from_slice(&addr.to_le_bytes());
+        symtab.extend_from_slice(&(size as u32).to_le_bytes());
+        // Global function in section 1
+        symtab.extend_from_slice(&[0x12, 0, 1, 0]);
+    }
+    let text: Vec<u8> = TEXT.iter().flat_map(|word| word.to_le_bytes()).collect();
+
+    // Name, type, flags, address and data, .text first, .symtab links to .strtab after it
+    let mut sections = vec![
+        (".text", 1, 6, TEXT_ADDR, text),
+        (".symtab", 2, 0, 0, symtab),
+        (".strtab", 3, 0, 0, strtab),
+    ];
+    for (name, data) in dwarf_sections(directory) {
+        sections.push((name, 1, 0, 0, data));
+    }
+    let mut shstrtab = vec![0];
+    let mut names = Vec::new();
+    for (name, ..) in &sections {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+    names.push(shstrtab.len() as u32);
+    shstrtab.extend_from_slice(b".shstrtab\0");
+    sections.push((".shstrtab", 3, 0, 0, shstrtab));
+
+    // Header and one program header, then the section data and the section headers
+    let mut file = vec![0; 52 + 32];
+    let mut headers = vec![0; 40];
+    for (index, (_, kind, flags, addr, data)) in sections.iter().enumerate() {
+        file.resize(file.len().next_multiple_of(4), 0);
+        let (link, info, entry_size) = if *kind == 2 { (3, 1, 16) } else { (0, 0, 0) };
+        for field in [
+            names[index],
+            *kind,
+            *flags,
+            *addr,
+            file.len() as u32,
+            data.len() as u32,
+            link,
+            info,
+            4,
+            entry_size,
+        ] {
+            headers.extend_from_slice(&field.to_le_bytes());
+        }
+        file.extend_from_slice(data);
+    }
+    file.resize(file.len().next_multiple_of(4), 0);
+    let section_headers = file.len() as u32;
+    file.extend_from_slice(&headers);
+
+    let mut header = vec![0x7f, b'E', b'L', b'F', 1, 1, 1];
+    header.resize(16, 0);
+    // Executable, RISC-V, version 1, entry point, program and section header offsets
+    for half in [2u16, 0xf3] {
+        header.extend_from_slice(&half.to_le_bytes());
+    }
+    for word in [1, TEXT_ADDR, 52, section_headers, 0] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    let section_count = sections.len() as u16 + 1;
+    for half in [52, 32, 1, 40, section_count, section_count - 1] {
+        header.extend_from_slice(&half.to_le_bytes());
+    }
+    // Loadable and executable .text, at the offset in its section header
+    let text_offset = u32::from_le_bytes(headers[40 + 16..40 + 20].try_into().unwrap());
+    let text_size = TEXT.len() as u32 * 4;
+    for word in [
+        1,
+        text_offset,
+        TEXT_ADDR,
+        TEXT_ADDR,
+        text_size,
+        text_size,
+        5,
+        4,
+    ] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    file[..header.len()].copy_from_slice(&header);
+    file
+}
+
+#[test]
+fn test_functions_and_lines() {
+    let debug_info = DebugInfo::load(&program()).unwrap();
+    assert_eq!(debug_info.function(TEXT_ADDR), Some(("demo::main", 0)));
+    assert_eq!(debug_info.function(TEXT_ADDR + 4), Some(("demo::main", 4)));
+    // Without a size, helper extends to the end of the address space
+    assert_eq!(debug_info.function(TEXT_ADDR + 12), Some(("helper", 4)));
+    assert_eq!(debug_info.function(TEXT_ADDR - 4), None);
+
+    assert_eq!(
+        debug_info.location(TEXT_ADDR + 4),
+        Some(SourceLocation {
+            file: "/src/demo/main.rs",
+            line: 4
+        })
+    );
+    assert_eq!(
+        debug_info.location(TEXT_ADDR + 8).unwrap().to_string(),
+        "/src/demo/lib/util.rs:10"
+    );
+    // The line table ends before the last instruction
+    assert_eq!(debug_info.location(TEXT_ADDR + 12), None);
+    assert_eq!(
+        debug_info.describe(TEXT_ADDR + 4).unwrap(),
+        "<demo::main+0x4> at /src/demo/main.rs:4"
+    );
+    assert_eq!(debug_info.describe(TEXT_ADDR + 12).unwrap(), "<helper+0x4>");
+    assert_eq!(debug_info.describe(0), None);
+}
+
+#[test]
+fn test_disassembly_with_source() {
+    let program = program();
+    let elf = Elf::load(&program).unwrap();
+    // The ELF symbols are kept as they are, the listing names functions as `DebugInfo` does
+    assert_eq!(elf.symbol_address(MAIN), Some(TEXT_ADDR));
+
+    let listing = disassemble_with_source(&elf, &DebugInfo::load(&program).unwrap());
+    let lines: Vec<_> = listing.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "",
+            "00010000 <demo::main>:",
+            "; /src/demo/main.rs:3",
+            "   10000:\t00500513\tli a0, 5",
+            "; /src/demo/main.rs:4",
+            "   10004:\t00008067\tret",
+            "",
+            "00010008 <helper>:",
+            "; /src/demo/lib/util.rs:10",
+            "   10008:\t00150513\taddi a0, a0, 1",
+            "   1000c:\t00008067\tret",
+        ]
+    );
+}
+
+#[test]
+fn test_debugger_and_profiler_names() {
+    let program = program();
+    let elf = Elf::load(&program).unwrap();
+    let debug_info = DebugInfo::load(&program).unwrap();
+    assert_eq!(function_name(&elf, TEXT_ADDR), MAIN);
+    assert_eq!(
+        function_name_with_debug_info(&elf, &debug_info, TEXT_ADDR),
+        "demo::main"
+    );
+    assert_eq!(
+        function_name_with_debug_info(&elf, &debug_info, TEXT_ADDR + 12),
+        "<helper+0x4>"
+    );
+
+    let mut debugger = Debugger::new(elf).with_debug_info(debug_info);
+    let mut output = Vec::new();
+    for line in ["break 0x10004", "c"] {
+        debugger.execute(line, &mut output).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Breakpoint set at 0x00010004 <demo::main+0x4> at /src/demo/main.rs:4\n\
+         Breakpoint at 0x00010004 <demo::main+0x4> at /src/demo/main.rs:4\n\
+         =>* 0x00010004 <demo::main+0x4> at /src/demo/main.rs:4: ret\n"
+    );
+}
+
+// The output of dump-trace for a run of `program`, through files named after `name`
+fn dump_trace(program: &[u8], format: &str, name: &str) -> String {
+    let elf = Elf::load(program).unwrap();
+    let mut executor = Executor::new(elf.image, elf.entry_point);
+    let mut writer = TraceWriter::new(Vec::new()).unwrap();
+    executor.run_with(&mut writer).unwrap();
+    let directory = std::env::temp_dir();
+    let trace_path = directory.join(format!("{name}-{}.trace", std::process::id()));
+    let elf_path = directory.join(format!("{name}-{}.elf", std::process::id()));
+    std::fs::write(&trace_path, writer.finish().unwrap()).unwrap();
+    std::fs::write(&elf_path, program).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_dump-trace"))
+        .arg(&trace_path)
+        .arg(format)
+        .arg(&elf_path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&trace_path).unwrap();
+    std::fs::remove_file(&elf_path).unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_dump_trace_with_source() {
+    let csv = dump_trace(&program(), "csv", "dump-trace-source");
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with(",is_write,source"));
+    assert!(lines[2].ends_with(",,,,,\"<demo::main+0x4> at /src/demo/main.rs:4\""));
+}
+
+#[test]
+fn test_dump_trace_escapes_source() {
+    let program = program_in("/src/a \"b\"\\c\td");
+    let csv = dump_trace(&program, "csv", "dump-trace-escaped");
+    assert!(
+        csv.lines()
+            .nth(2)
+            .unwrap()
+            .ends_with(",\"<demo::main+0x4> at /src/a \"\"b\"\"\\c\td/main.rs:4\"")
+    );
+    let json = dump_trace(&program, "jsonl", "dump-trace-escaped");
+    assert!(
+        json.lines().nth(1).unwrap().ends_with(
+            ",\"source\":\"<demo::main+0x4> at /src/a \\\"b\\\"\\\\c\\u0009d/main.rs:4\"}"
+        )
+    );
+}
+
+#[test]
+fn test_missing_debug_info() {
+    // Assembled programs have symbols but no line tables
+    let program = std::fs::read("./program_artifacts/asm/add.elf").unwrap();
+    let debug_info = DebugInfo::load(&program).unwrap();
+    assert!(debug_info.location(0x10000).is_none());
+    assert!(DebugInfo::load(b"not an elf").is_err());
+}
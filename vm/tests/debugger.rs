@@ -0,0 +1,286 @@
+mod common;
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use common::{DEBUGGEE, MISALIGNED_LOAD};
+use vm::{assembler::assemble, debugger::Debugger};
+
+// Runs each command and returns the output of the last one
+fn session(debugger: &mut Debugger, commands: &[&str]) -> String {
+    let mut output = Vec::new();
+    for command in commands {
+        output.clear();
+        assert!(debugger.execute(command, &mut output).unwrap());
+    }
+    String::from_utf8(output).unwrap()
+}
+
+fn debugger() -> Debugger {
+    Debugger::new(assemble(DEBUGGEE).unwrap())
+}
+
+#[test]
+fn test_breakpoints_by_symbol_and_address() {
+    let mut debugger = debugger();
+    let output = session(&mut debugger, &["break loop", "continue"]);
+    assert_eq!(
+        output,
+        "Breakpoint at 0x00010008 <loop>\n=>* 0x00010008 <loop>: addi a0, a0, 5\n"
+    );
+    // An empty line repeats the last command
+    session(&mut debugger, &["continue", ""]);
+    assert_eq!(debugger.executor.registers.read(10), 10);
+
+    let output = session(&mut debugger, &["delete loop", "break 0x10010", "c"]);
+    assert!(output.starts_with("Breakpoint at 0x00010010 <loop+0x8>\n"));
+    assert_eq!(
+        session(&mut debugger, &["breakpoints"]),
+        "0x00010010 <loop+0x8>\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["delete 0x10008"]),
+        "error: No breakpoint at 0x00010008\n"
+    );
+}
+
+#[test]
+fn test_step_and_ebreak() {
+    let mut debugger = debugger();
+    let output = session(&mut debugger, &["step 2"]);
+    assert_eq!(output, "=>  0x00010008 <loop>: addi a0, a0, 5\n");
+    let output = session(&mut debugger, &["c"]);
+    assert_eq!(
+        output,
+        "ebreak at 0x00010014 <loop+0xc>\n=>  0x00010014 <loop+0xc>: ebreak\n"
+    );
+    assert_eq!(debugger.executor.pc, 0x10014);
+    // Resuming steps over the ebreak
+    let output = session(&mut debugger, &["s"]);
+    assert_eq!(output, "=>  0x00010018 <loop+0x10>: auipc t1, 0x0\n");
+    let output = session(&mut debugger, &["c"]);
+    assert_eq!(
+        output,
+        "Program returned a0 = 0x0000000f, a1 = 0x0000002a after 15 cycles\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["s"]),
+        "error: The program has returned\n"
+    );
+}
+
+#[test]
+fn test_errors_stop_at_the_fault() {
+    let mut debugger = Debugger::new(assemble(MISALIGNED_LOAD).unwrap());
+    let fault = "error: Misaligned memory access at 0x00000002\n\
+                 =>  0x00010004 <main+0x4>: lw a0, 0(t0)\n";
+    assert_eq!(session(&mut debugger, &["c"]), fault);
+    // Neither stepping nor continuing gets past the faulting instruction
+    assert_eq!(session(&mut debugger, &["s"]), fault);
+    assert_eq!(session(&mut debugger, &["c"]), fault);
+    assert_eq!(debugger.executor.cycles, 1);
+    let output = session(&mut debugger, &["reg t0 0x10008", "c"]);
+    assert_eq!(
+        output,
+        "Program returned a0 = 0x00008067, a1 = 0x00000000 after 3 cycles\n"
+    );
+}
+
+#[test]
+fn test_inspect_and_modify_state() {
+    let mut debugger = debugger();
+    assert_eq!(
+        session(&mut debugger, &["reg sp"]),
+        "sp = 0xfffffff0 (-16)\n"
+    );
+    session(&mut debugger, &["write value 0x2b"]);
+    assert_eq!(
+        session(&mut debugger, &["mem value 1"]),
+        "0x00010028: 0x0000002b\n"
+    );
+    // Skips the loop and the ebreak
+    let output = session(
+        &mut debugger,
+        &["break loop", "c", "reg a0 -10", "reg pc 0x10018", "c"],
+    );
+    assert_eq!(
+        output,
+        "Program returned a0 = 0xfffffff6, a1 = 0x0000002b after 6 cycles\n"
+    );
+    let registers = session(&mut debugger, &["regs"]);
+    assert!(registers.starts_with("pc  0x00000000  cycles 6\n"));
+    assert!(registers.contains("x11 a1   0x0000002b"));
+}
+
+#[test]
+fn test_watchpoints_and_uninitialized_reads() {
+    let mut debugger = debugger();
+    assert_eq!(
+        session(&mut debugger, &["watch value read"]),
+        "Watchpoint set on 0x00010028 <value> read\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["watchpoints"]),
+        "0x00010028 <value> read\n"
+    );
+    let output = session(&mut debugger, &["c", "c"]);
+    assert_eq!(
+        output,
+        "Watchpoint: read of 0x00010028 <value> at 0x00010020 <loop+0x18>\n\
+         =>  0x00010024 <loop+0x1c>: ret\n"
+    );
+    assert_eq!(debugger.executor.registers.read(11), 42);
+
+    // Loading again from a word nothing wrote
+    let output = session(
+        &mut debugger,
+        &["unwatch value", "reg t1 0x20000", "reg pc 0x10020", "s"],
+    );
+    assert_eq!(
+        output,
+        "warning: read of uninitialized 0x00020000 at 0x00010020 <loop+0x18>\n\
+         =>  0x00010024 <loop+0x1c>: ret\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["unwatch value"]),
+        "error: No watchpoint at 0x00010028\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["watch 0x1002a"]),
+        "error: Watchpoints cover whole words, 0x0001002a is not aligned\n"
+    );
+}
+
+#[test]
+fn test_reverse_execution() {
+    let program = assemble(
+        "
+main:
+    la t0, value
+    li t1, 7
+    sw t1, 0(t0)
+    li t1, 9
+    sw t1, 0(t0)
+    lw a0, 0(t0)
+    ret
+value:
+    .word 5
+",
+    )
+    .unwrap();
+    let mut debugger = Debugger::new(program);
+    let output = session(&mut debugger, &["b 0x10010", "c", "c"]);
+    assert_eq!(
+        output,
+        "Program returned a0 = 0x00000009, a1 = 0x00000000 after 8 cycles\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["last-write value"]),
+        "0x00010020 <value> = 0x00000009 (was 0x00000007), written at cycle 5 by \
+         0x00010014 <main+0x14>: sw t1, 0(t0)\n"
+    );
+
+    assert_eq!(
+        session(&mut debugger, &["rc"]),
+        "Breakpoint at 0x00010010 <main+0x10>\n=>* 0x00010010 <main+0x10>: li t1, 9\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["mem value 1"]),
+        "0x00010020: 0x00000007\n"
+    );
+    assert!(session(&mut debugger, &["last-write value"]).contains("written at cycle 3"));
+    assert_eq!(
+        session(&mut debugger, &["rs 2"]),
+        "=>  0x00010008 <main+0x8>: li t1, 7\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["rc"]),
+        "Reached the start of the history\n=>  0x00010000 <main>: auipc t0, 0x0\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["last-write value"]),
+        "0x00010020 <value> was not written\n"
+    );
+
+    // Running forward again, after changing the past
+    let output = session(
+        &mut debugger,
+        &["c", "write value 1", "reg t1 3", "reg pc 0x10014", "c"],
+    );
+    assert_eq!(
+        output,
+        "Program returned a0 = 0x00000003, a1 = 0x00000000 after 7 cycles\n"
+    );
+    assert_eq!(
+        session(&mut debugger, &["rs 3"]),
+        "=>  0x00010014 <main+0x14>: sw t1, 0(t0)\n"
+    );
+    assert_eq!(debugger.executor.registers.read(6), 3);
+    assert_eq!(debugger.executor.memory.read_word(0x10020), Some(1));
+}
+
+#[test]
+fn test_disassemble_around_pc() {
+    let mut debugger = debugger();
+    let output = session(&mut debugger, &["b loop", "s 3", "disas"]);
+    let lines: Vec<_> = output.lines().collect();
+    // The window starts one word before the code, which is not shown
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[0], "    0x00010000 <main>: li a0, 0");
+    assert_eq!(lines[1], "    0x00010004 <main+0x4>: li t0, 3");
+    assert_eq!(lines[2], "  * 0x00010008 <loop>: addi a0, a0, 5");
+    assert_eq!(lines[3], "=>  0x0001000c <loop+0x4>: addi t0, t0, -1");
+    assert_eq!(lines[4], "    0x00010010 <loop+0x8>: bnez t0, -8 <loop>");
+    assert_eq!(session(&mut debugger, &["disas main 1"]).lines().count(), 1);
+}
+
+#[test]
+fn test_command_errors() {
+    let mut debugger = debugger();
+    for (command, error) in [
+        ("frobnicate", "Unknown command frobnicate, try help"),
+        ("mem", "Usage: memory <addr> [count]"),
+        ("b nowhere", "nowhere is neither an address nor a symbol"),
+        ("reg x32", "Unknown register x32"),
+        ("s lots", "Invalid value lots"),
+        ("disas main 0xffffffff", "Invalid value 0xffffffff"),
+        ("mem value 0x40000000", "Invalid value 0x40000000"),
+        (
+            "watch value often",
+            "Usage: watch <addr> [read|write|access]",
+        ),
+    ] {
+        assert_eq!(
+            session(&mut debugger, &[command]),
+            format!("error: {error}\n")
+        );
+    }
+    let mut output = Vec::new();
+    assert!(!debugger.execute("quit", &mut output).unwrap());
+}
+
+#[test]
+fn test_debugger_binary() {
+    let path = std::env::temp_dir().join(format!("debugger-{}.s", std::process::id()));
+    std::fs::write(&path, DEBUGGEE).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_debugger"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"b value\nc\nc\nreg a1\nquit\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(output.contains("ebreak at 0x00010014"));
+    assert!(output.contains("a1 = 0x0000002a (42)"));
+}
@@ -0,0 +1,388 @@
+mod reference;
+
+use reference::ReferenceMachine;
+use vm::{
+    builder::ProgramBuilder,
+    elf::Elf,
+    vm::{
+        execution::Executor,
+        instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+        registers::REGISTER_NAMES,
+    },
+};
+
+const CODE_BASE: u32 = 0x10000;
+const DATA_BASE: u32 = 0x20000;
+const DATA_WORDS: u32 = 64;
+// Hold DATA_BASE and CODE_BASE for the whole program, so loads, stores and jalr targets are
+// known to be inside the image
+const DATA_POINTER: u32 = 31;
+const CODE_POINTER: u32 = 30;
+const MAX_STEPS: usize = 100_000;
+
+// xorshift32
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn below(&mut self, bound: u32) -> u32 {
+        self.next() % bound
+    }
+
+    fn source(&mut self) -> u32 {
+        self.below(32)
+    }
+
+    fn destination(&mut self) -> u32 {
+        self.below(CODE_POINTER)
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len() as u32) as usize]
+    }
+
+    // Biased towards the edge cases of comparisons and shifts
+    fn value(&mut self) -> u32 {
+        match self.below(4) {
+            0 => self.pick(&[0, 1, 31, 32, 0x7fffffff, 0x80000000, u32::MAX]),
+            1 => self.below(64),
+            _ => self.next(),
+        }
+    }
+}
+
+const ARITH_OPS: [ArithOp; 10] = [
+    ArithOp::Add,
+    ArithOp::Sub,
+    ArithOp::Xor,
+    ArithOp::Or,
+    ArithOp::And,
+    ArithOp::ShiftLeftLogical,
+    ArithOp::ShiftRightLogical,
+    ArithOp::ShiftRightArith,
+    ArithOp::SetLessThan,
+    ArithOp::SetLessThanU,
+];
+const MUL_DIV_OPS: [ArithOp; 8] = [
+    ArithOp::Mul,
+    ArithOp::MulHigh,
+    ArithOp::MulHighSU,
+    ArithOp::MulHighU,
+    ArithOp::Div,
+    ArithOp::DivU,
+    ArithOp::Rem,
+    ArithOp::RemU,
+];
+const LOAD_WIDTHS: [LoadStoreWidth; 5] = [
+    LoadStoreWidth::Byte,
+    LoadStoreWidth::Half,
+    LoadStoreWidth::Word,
+    LoadStoreWidth::ByteUnsigned,
+    LoadStoreWidth::HalfUnsigned,
+];
+const COMPARISONS: [Comparison; 6] = [
+    Comparison::Equal,
+    Comparison::NotEqual,
+    Comparison::LessThan,
+    Comparison::GreaterOrEqual,
+    Comparison::LessThanUnsigned,
+    Comparison::GreaterOrEqualUnsigned,
+];
+
+fn load_immediate(dst: u32, value: u32) -> [Instruction; 2] {
+    // addi sign extends, so the upper part is rounded to compensate
+    let upper = value.wrapping_add(0x800) & 0xfffff000;
+    [
+        Instruction::LoadUpperImm { dst, imm: upper },
+        Instruction::ArithImm {
+            dst,
+            src: dst,
+            imm: value.wrapping_sub(upper) as i32,
+            op: ArithOp::Add,
+        },
+    ]
+}
+
+// Control flow only moves forward, so every program reaches the final `jalr zero, 0(zero)`.
+// `start` is the address of the first instruction of the body.
+fn random_body(rng: &mut Rng, start: u32, length: usize) -> Vec<Instruction> {
+    let mut body = Vec::with_capacity(length);
+    while body.len() < length {
+        // Distance in instructions to the end of the program
+        let remaining = (length - body.len()) as u32;
+        let forward = |rng: &mut Rng, min: u32| 4 * (min + rng.below(remaining + 1 - min)) as i32;
+        // Aligned to the width, as the VM traps on misaligned accesses
+        let data_offset = |rng: &mut Rng, width: LoadStoreWidth| {
+            (width.size() * rng.below(DATA_WORDS * 4 / width.size())) as i32
+        };
+        let instruction = match rng.below(11) {
+            0 | 1 => Instruction::Arith {
+                dst: rng.destination(),
+                src1: rng.source(),
+                src2: rng.source(),
+                op: rng.pick(&ARITH_OPS),
+            },
+            10 => Instruction::Arith {
+                dst: rng.destination(),
+                src1: rng.source(),
+                src2: rng.source(),
+                op: rng.pick(&MUL_DIV_OPS),
+            },
+            2 | 3 => {
+                let op = rng.pick(&ARITH_OPS[2..]);
+                let imm = match op {
+                    ArithOp::ShiftLeftLogical
+                    | ArithOp::ShiftRightLogical
+                    | ArithOp::ShiftRightArith => rng.below(32) as i32,
+                    _ => (rng.value() as i32) >> 20,
+                };
+                Instruction::ArithImm {
+                    dst: rng.destination(),
+                    src: rng.source(),
+                    imm,
+                    op,
+                }
+            }
+            4 => Instruction::ArithImm {
+                dst: rng.destination(),
+                src: rng.source(),
+                imm: (rng.value() as i32) >> 20,
+                op: ArithOp::Add,
+            },
+            5 => {
+                let width = rng.pick(&LOAD_WIDTHS);
+                Instruction::Load {
+                    dst: rng.destination(),
+                    offset: data_offset(rng, width),
+                    base: DATA_POINTER,
+                    width,
+                }
+            }
+            6 => {
+                let width = rng.pick(&LOAD_WIDTHS[..3]);
+                Instruction::Store {
+                    src: rng.source(),
+                    offset: data_offset(rng, width),
+                    base: DATA_POINTER,
+                    width,
+                }
+            }
+            7 => Instruction::Branch {
+                src1: rng.source(),
+                src2: rng.source(),
+                cond: rng.pick(&COMPARISONS),
+                offset: forward(rng, 1),
+            },
+            8 => match rng.below(3) {
+                0 => Instruction::LoadUpperImm {
+                    dst: rng.destination(),
+                    imm: rng.next() & 0xfffff000,
+                },
+                1 => Instruction::AddUpperImmToPc {
+                    dst: rng.destination(),
+                    imm: rng.next() & 0xfffff000,
+                },
+                _ => Instruction::JumpAndLink {
+                    dst: rng.destination(),
+                    offset: forward(rng, 1),
+                },
+            },
+            _ => {
+                // The odd offset checks that the low bit of the target is cleared
+                let pc = start + 4 * body.len() as u32;
+                let offset = (pc - CODE_BASE) as i32 + forward(rng, 1);
+                Instruction::JumpAndLinkRegister {
+                    base: CODE_POINTER,
+                    dst: rng.destination(),
+                    offset: offset | rng.below(2) as i32,
+                }
+            }
+        };
+        body.push(instruction);
+    }
+    body
+}
+
+fn random_program(seed: u32, length: usize) -> Elf {
+    let mut rng = Rng(seed);
+    let mut code = Vec::new();
+    for register in 1..CODE_POINTER {
+        code.extend(load_immediate(register, rng.value()));
+    }
+    code.extend(load_immediate(CODE_POINTER, CODE_BASE));
+    code.extend(load_immediate(DATA_POINTER, DATA_BASE));
+    let start = CODE_BASE + 4 * code.len() as u32;
+    code.extend(random_body(&mut rng, start, length));
+    code.push(Instruction::JumpAndLinkRegister {
+        base: 0,
+        dst: 0,
+        offset: 0,
+    });
+    let data: Vec<u32> = (0..DATA_WORDS).map(|_| rng.value()).collect();
+    ProgramBuilder::new()
+        .code(CODE_BASE, &code)
+        .data(DATA_BASE, &data)
+        .entry_point(CODE_BASE)
+        .build()
+        .unwrap()
+}
+
+fn state_diff(executor: &Executor, reference: &ReferenceMachine, addresses: &[u32]) -> Vec<String> {
+    let mut diff = Vec::new();
+    if executor.pc != reference.pc {
+        diff.push(format!(
+            "pc: vm 0x{:08x}, reference 0x{:08x}",
+            executor.pc, reference.pc
+        ));
+    }
+    for (index, name) in REGISTER_NAMES.iter().enumerate() {
+        let (vm, expected) = (executor.registers.read(index as u32), reference.x[index]);
+        if vm != expected {
+            diff.push(format!(
+                "x{index} ({name}): vm 0x{vm:08x}, reference 0x{expected:08x}"
+            ));
+        }
+    }
+    for &addr in addresses {
+        let vm = executor.memory.read_word(addr);
+        let expected = reference.load(addr, 4).ok();
+        if vm != expected {
+            diff.push(format!(
+                "mem[0x{addr:08x}]: vm {vm:08x?}, reference {expected:08x?}"
+            ));
+        }
+    }
+    diff
+}
+
+/// Steps the VM and the reference model in lockstep, returning the number of steps until
+/// both halt, or a report of the first step after which their states differ.
+fn run_differential(program: &Elf) -> Result<usize, String> {
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut reference = ReferenceMachine::new(&program.image, program.entry_point);
+    // The VM starts with a stack pointer, which is part of its ABI and not of the ISA
+    reference.x[2] = executor.registers.read(2);
+    // Stores are confined to the image, so comparing its words covers all of memory. That is
+    // only needed after stores, until the report.
+    let addresses: Vec<u32> = program.image.keys().copied().collect();
+    let is_store = |word: u32| word & 0x7f == 0b0100011;
+    for step in 0..MAX_STEPS {
+        if executor.is_halted() && reference.pc == 0 {
+            return Ok(step);
+        }
+        let pc = executor.pc;
+        let word = reference.load(pc, 4).unwrap_or_default();
+        let vm_result = executor.step().map_err(|error| error.to_string());
+        let reference_result = reference.step().map_err(|trap| format!("{trap:?}"));
+        let checked = if is_store(word) { &addresses[..] } else { &[] };
+        if vm_result.is_ok() == reference_result.is_ok()
+            && state_diff(&executor, &reference, checked).is_empty()
+        {
+            if vm_result.is_err() {
+                return Ok(step);
+            }
+            continue;
+        }
+        let mut diff = state_diff(&executor, &reference, &addresses);
+        if vm_result.is_err() || reference_result.is_err() {
+            diff.insert(
+                0,
+                format!("result: vm {vm_result:?}, reference {reference_result:?}"),
+            );
+        }
+        let listing = Instruction::parse(word)
+            .map(|instruction| instruction.to_string())
+            .unwrap_or_else(|_| "<invalid>".to_string());
+        return Err(format!(
+            "Divergence at step {step}, pc 0x{pc:08x} (0x{word:08x}: {listing})\n  {}",
+            diff.join("\n  ")
+        ));
+    }
+    Err(format!("No halt after {MAX_STEPS} steps"))
+}
+
+#[test]
+fn test_random_programs_match_reference() {
+    for seed in 1..=300 {
+        let program = random_program(seed, 200);
+        if let Err(report) = run_differential(&program) {
+            panic!("Seed {seed}: {report}");
+        }
+    }
+}
+
+#[test]
+fn test_basic_blocks_match_stepping() {
+    for seed in 1..=100 {
+        let program = random_program(seed, 200);
+        let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+        while !stepped.is_halted() {
+            stepped.step().unwrap();
+        }
+        let mut run = Executor::new(program.image, program.entry_point);
+        run.run().unwrap();
+        assert_eq!(stepped.registers, run.registers, "Seed {seed}");
+        assert_eq!(stepped.cycles, run.cycles, "Seed {seed}");
+        assert_eq!(stepped.memory.words(), run.memory.words(), "Seed {seed}");
+    }
+}
+
+#[test]
+fn test_asm_programs_match_reference() {
+    for entry in std::fs::read_dir("./program_artifacts/asm").unwrap() {
+        let path = entry.unwrap().path();
+        let program = Elf::load(&std::fs::read(&path).unwrap()).unwrap();
+        // Syscalls and custom instructions are outside the reference model
+        let has_extensions = program.executable_ranges.iter().any(|range| {
+            range.clone().step_by(4).any(|addr| {
+                matches!(
+                    Instruction::parse(program.image[&addr]),
+                    Ok(Instruction::EnvironmentCall
+                        | Instruction::Poseidon2Permute { .. }
+                        | Instruction::Uint256Add { .. })
+                )
+            })
+        });
+        if has_extensions {
+            continue;
+        }
+        if let Err(report) = run_differential(&program) {
+            panic!("{path:?}: {report}");
+        }
+    }
+}
+
+#[test]
+fn test_divergence_is_reported() {
+    // The VM traps on misaligned stores, while the reference model performs them
+    let mut code = load_immediate(5, DATA_BASE).to_vec();
+    code.extend(load_immediate(10, 6));
+    code.push(Instruction::Store {
+        src: 10,
+        offset: 2,
+        base: 5,
+        width: LoadStoreWidth::Word,
+    });
+    code.push(Instruction::EnvironmentCall);
+    let program = ProgramBuilder::new()
+        .code(CODE_BASE, &code)
+        .data(DATA_BASE, &[0, 0])
+        .entry_point(CODE_BASE)
+        .build()
+        .unwrap();
+    let report = run_differential(&program).unwrap_err();
+    assert!(
+        report.starts_with("Divergence at step 4, pc 0x00010010 (0x00a2a123: sw a0, 2(t0))"),
+        "{report}"
+    );
+    assert!(
+        report.contains("mem[0x00020000]: vm Some(00000000), reference Some(00060000)"),
+        "{report}"
+    );
+}
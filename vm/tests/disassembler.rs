@@ -0,0 +1,100 @@
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::{disassembler::disassemble, instructions::Instruction},
+};
+
+fn check_display(word: u32, expected: &str) {
+    let instruction = Instruction::parse(word).unwrap();
+    assert_eq!(instruction.to_string(), expected);
+}
+
+#[test]
+fn test_display_arith_imm() {
+    check_display(0x00000513, "li a0, 0");
+    check_display(0x00a00613, "li a2, 10");
+    check_display(0x00058513, "mv a0, a1");
+    check_display(0x00000013, "nop");
+    check_display(0xff010113, "addi sp, sp, -16");
+    check_display(0xfff5c513, "not a0, a1");
+    check_display(0x0015b513, "seqz a0, a1");
+    check_display(0x0055c513, "xori a0, a1, 5");
+    check_display(0x00359513, "slli a0, a1, 3");
+    check_display(0x41f35293, "srai t0, t1, 31");
+    check_display(0x0075b513, "sltiu a0, a1, 7");
+}
+
+#[test]
+fn test_display_arith() {
+    check_display(0x00d60533, "add a0, a2, a3");
+    check_display(0x40c58533, "sub a0, a1, a2");
+    check_display(0x40b00533, "neg a0, a1");
+    check_display(0x00b03533, "snez a0, a1");
+    check_display(0x4124d433, "sra s0, s1, s2");
+    check_display(0x01eebe33, "sltu t3, t4, t5");
+    check_display(0x02c5a533, "mulhsu a0, a1, a2");
+    check_display(0x027372b3, "remu t0, t1, t2");
+}
+
+#[test]
+fn test_display_loads_and_stores() {
+    check_display(0xffc12503, "lw a0, -4(sp)");
+    check_display(0x00358503, "lb a0, 3(a1)");
+    check_display(0xfff5c503, "lbu a0, -1(a1)");
+    check_display(0x7fe15283, "lhu t0, 2046(sp)");
+    check_display(0x00a58023, "sb a0, 0(a1)");
+    check_display(0x00112623, "sw ra, 12(sp)");
+}
+
+#[test]
+fn test_display_jumps_and_branches() {
+    check_display(0x00b50863, "beq a0, a1, 16");
+    check_display(0x00059463, "bnez a1, 8");
+    check_display(0x00b56663, "bltu a0, a1, 12");
+    check_display(0x001000ef, "jal 2048");
+    check_display(0xffdff06f, "j -4");
+    check_display(0x0080056f, "jal a0, 8");
+    check_display(0x00008067, "ret");
+    check_display(0x00050067, "jr a0");
+    check_display(0x000580e7, "jalr a1");
+    check_display(0x00458567, "jalr a0, 4(a1)");
+}
+
+#[test]
+fn test_display_upper_immediates_and_system() {
+    check_display(0x12345537, "lui a0, 0x12345");
+    check_display(0x00001517, "auipc a0, 0x1");
+    check_display(0x00000073, "ecall");
+    check_display(0x00100073, "ebreak");
+    check_display(0x0005000b, "poseidon2.permute a0");
+    check_display(0x00c586ab, "uint256.add a3, a1, a2");
+}
+
+#[test]
+fn test_disassemble_elf() {
+    let elf_data = std::fs::read("./program_artifacts/asm/add.elf").unwrap();
+    let program = Elf::load(&elf_data).unwrap();
+    let listing = disassemble(&program);
+    assert!(listing.contains("00010074 <main>:"));
+    assert!(listing.contains("   10074:\t00a00613\tli a2, 10"));
+    assert!(listing.contains("   1007c:\t00d60533\tadd a0, a2, a3"));
+    assert!(listing.contains("   10080:\t00008067\tret"));
+}
+
+#[test]
+fn test_listing_shows_jump_targets_as_addresses() {
+    let program = assemble(
+        "
+main:
+    li t0, 3
+loop:
+    addi t0, t0, -1
+    bnez t0, loop
+    j main
+",
+    )
+    .unwrap();
+    let listing = disassemble(&program);
+    assert!(listing.contains("   10008:\tfe029ee3\tbnez t0, 10004 <loop>\n"));
+    assert!(listing.contains("   1000c:\tff5ff06f\tj 10000 <main>\n"));
+}
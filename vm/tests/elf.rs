@@ -0,0 +1,126 @@
+use vm::elf::{Elf, ElfError, LoadLimits, MAX_IMAGE_SIZE};
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+// Offset in the file of the first PT_LOAD program header
+fn first_load_header(data: &[u8]) -> usize {
+    let phoff = read_u32(data, 0x1c) as usize;
+    let phentsize = u16::from_le_bytes([data[0x2a], data[0x2b]]) as usize;
+    (phoff..)
+        .step_by(phentsize)
+        .find(|header| read_u32(data, *header) == PT_LOAD)
+        .unwrap()
+}
+
+// Offsets in the file of the section headers
+fn section_headers(data: &[u8]) -> Vec<usize> {
+    let shoff = read_u32(data, 0x20) as usize;
+    let shentsize = u16::from_le_bytes([data[0x2e], data[0x2f]]) as usize;
+    let shnum = u16::from_le_bytes([data[0x30], data[0x31]]) as usize;
+    (0..shnum).map(|index| shoff + index * shentsize).collect()
+}
+
+fn patch_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn program() -> Vec<u8> {
+    std::fs::read("./program_artifacts/asm/add.elf").unwrap()
+}
+
+#[test]
+fn test_huge_bss_is_rejected() {
+    let mut data = program();
+    let header = first_load_header(&data);
+    // p_memsz of a 3GB segment, mostly zero filled
+    patch_u32(&mut data, header + 20, 0xc000_0000);
+    assert!(matches!(
+        Elf::load(&data),
+        Err(ElfError::ImageTooLarge(MAX_IMAGE_SIZE))
+    ));
+}
+
+#[test]
+fn test_load_limits() {
+    let data = program();
+    let image_size = Elf::load(&data).unwrap().image.len() as u32 * 4;
+    let limits = |max_image_size| LoadLimits {
+        max_image_size,
+        ..LoadLimits::default()
+    };
+    assert!(Elf::load_with_limits(&data, limits(image_size)).is_ok());
+    assert!(matches!(
+        Elf::load_with_limits(&data, limits(image_size - 4)),
+        Err(ElfError::ImageTooLarge(_))
+    ));
+    let no_segments = LoadLimits {
+        max_segments: 0,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Elf::load_with_limits(&data, no_segments),
+        Err(ElfError::TooManySegments)
+    ));
+}
+
+#[test]
+fn test_segment_outside_file_is_rejected() {
+    let mut data = program();
+    let header = first_load_header(&data);
+    // p_offset close to the end of the address space
+    patch_u32(&mut data, header + 4, u32::MAX - 2);
+    assert!(matches!(Elf::load(&data), Err(ElfError::InvalidOffset)));
+}
+
+#[test]
+fn test_truncated_elf_does_not_panic() {
+    let data = program();
+    for len in 0..data.len() {
+        let _ = Elf::load(&data[..len]);
+    }
+}
+
+#[test]
+fn test_malformed_symbols_are_skipped() {
+    let data = program();
+    let headers = section_headers(&data);
+    let symbol_table = *headers
+        .iter()
+        .find(|header| read_u32(&data, *header + 4) == SHT_SYMTAB)
+        .unwrap();
+    let (offset, size) = (
+        read_u32(&data, symbol_table + 16) as usize,
+        read_u32(&data, symbol_table + 20) as usize,
+    );
+    let string_table = read_u32(
+        &data,
+        headers[read_u32(&data, symbol_table + 24) as usize] + 16,
+    );
+    // A name past the end of the string table only loses that symbol
+    let mut bad_name = data.clone();
+    let symbol = (offset..offset + size)
+        .step_by(16)
+        .find(|symbol| {
+            let name = (string_table + read_u32(&data, *symbol)) as usize;
+            data[name..].starts_with(b"main\0")
+        })
+        .unwrap();
+    patch_u32(&mut bad_name, symbol, 0xffff_0000);
+    let elf = Elf::load(&bad_name).unwrap();
+    assert_eq!(elf.symbol_address("main"), None);
+    assert_eq!(
+        elf.symbols.len(),
+        Elf::load(&data).unwrap().symbols.len() - 1
+    );
+    // A missing string table loses all of them
+    let mut bad_link = data.clone();
+    patch_u32(&mut bad_link, symbol_table + 24, 0xffff);
+    let elf = Elf::load(&bad_link).unwrap();
+    assert!(elf.symbols.is_empty());
+    assert_eq!(elf.image, Elf::load(&data).unwrap().image);
+}
@@ -0,0 +1,463 @@
+use vm::{
+    elf::Elf,
+    vm::instructions::{ArithOp, Comparison, EncodeError, Instruction, LoadStoreWidth},
+};
+
+// Encodings taken from llvm-mc
+const REFERENCE_ENCODINGS: [(u32, Instruction); 18] = [
+    (
+        0xfea59f23,
+        Instruction::Store {
+            src: 10,
+            offset: -2,
+            base: 11,
+            width: LoadStoreWidth::Half,
+        },
+    ),
+    (
+        0x7e512fa3,
+        Instruction::Store {
+            src: 5,
+            offset: 2047,
+            base: 2,
+            width: LoadStoreWidth::Word,
+        },
+    ),
+    (
+        0x8005a503,
+        Instruction::Load {
+            dst: 10,
+            offset: -2048,
+            base: 11,
+            width: LoadStoreWidth::Word,
+        },
+    ),
+    (
+        0xfff5c503,
+        Instruction::Load {
+            dst: 10,
+            offset: -1,
+            base: 11,
+            width: LoadStoreWidth::ByteUnsigned,
+        },
+    ),
+    (
+        0x7fe15283,
+        Instruction::Load {
+            dst: 5,
+            offset: 2046,
+            base: 2,
+            width: LoadStoreWidth::HalfUnsigned,
+        },
+    ),
+    (
+        0x41f35293,
+        Instruction::ArithImm {
+            dst: 5,
+            src: 6,
+            imm: 31,
+            op: ArithOp::ShiftRightArith,
+        },
+    ),
+    (
+        0x40c58533,
+        Instruction::Arith {
+            dst: 10,
+            src1: 11,
+            src2: 12,
+            op: ArithOp::Sub,
+        },
+    ),
+    (
+        0x02c58533,
+        Instruction::Arith {
+            dst: 10,
+            src1: 11,
+            src2: 12,
+            op: ArithOp::Mul,
+        },
+    ),
+    (
+        0x027372b3,
+        Instruction::Arith {
+            dst: 5,
+            src1: 6,
+            src2: 7,
+            op: ArithOp::RemU,
+        },
+    ),
+    (
+        0xfffff537,
+        Instruction::LoadUpperImm {
+            dst: 10,
+            imm: 0xfffff000,
+        },
+    ),
+    (
+        0xfff280e7,
+        Instruction::JumpAndLinkRegister {
+            base: 5,
+            dst: 1,
+            offset: -1,
+        },
+    ),
+    (
+        0xfe050ce3,
+        Instruction::Branch {
+            src1: 10,
+            src2: 0,
+            cond: Comparison::Equal,
+            offset: -8,
+        },
+    ),
+    (
+        0xfeb55ee3,
+        Instruction::Branch {
+            src1: 10,
+            src2: 11,
+            cond: Comparison::GreaterOrEqual,
+            offset: -4,
+        },
+    ),
+    (
+        0x7eb56fe3,
+        Instruction::Branch {
+            src1: 10,
+            src2: 11,
+            cond: Comparison::LessThanUnsigned,
+            offset: 4094,
+        },
+    ),
+    (
+        0x80b54063,
+        Instruction::Branch {
+            src1: 10,
+            src2: 11,
+            cond: Comparison::LessThan,
+            offset: -4096,
+        },
+    ),
+    (0xffdff06f, Instruction::JumpAndLink { dst: 0, offset: -4 }),
+    (
+        0x7ffff0ef,
+        Instruction::JumpAndLink {
+            dst: 1,
+            offset: 1048574,
+        },
+    ),
+    (
+        0x800000ef,
+        Instruction::JumpAndLink {
+            dst: 1,
+            offset: -1048576,
+        },
+    ),
+];
+
+#[test]
+fn test_reference_encodings() {
+    for (word, instruction) in REFERENCE_ENCODINGS {
+        assert_eq!(Instruction::parse(word).unwrap(), instruction);
+        assert_eq!(instruction.encode(), word, "{instruction:?}");
+    }
+}
+
+#[test]
+fn test_round_trip_program_code() {
+    for entry in std::fs::read_dir("./program_artifacts/asm").unwrap() {
+        let path = entry.unwrap().path();
+        let elf_data = std::fs::read(&path).unwrap();
+        let program = Elf::load(&elf_data).unwrap();
+        for range in &program.executable_ranges {
+            for addr in range.clone().step_by(4) {
+                let word = program.image[&addr];
+                let instruction = Instruction::parse(word).unwrap();
+                assert_eq!(
+                    instruction.encode(),
+                    word,
+                    "{instruction} at 0x{addr:x} in {path:?}"
+                );
+            }
+        }
+    }
+}
+
+// xorshift32, enough to spread the fields over their whole range
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn register(&mut self) -> u32 {
+        self.next() % 32
+    }
+
+    // Uniform value in the `bits` wide two's complement range
+    fn signed(&mut self, bits: u32) -> i32 {
+        (self.next() as i32) >> (32 - bits)
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.next() as usize % values.len()]
+    }
+}
+
+// Add is generated more often, the ones from Xor to SetLessThanU also as immediates
+const ARITH_OPS: [ArithOp; 18] = [
+    ArithOp::Add,
+    ArithOp::Sub,
+    ArithOp::Xor,
+    ArithOp::Or,
+    ArithOp::And,
+    ArithOp::ShiftLeftLogical,
+    ArithOp::ShiftRightLogical,
+    ArithOp::ShiftRightArith,
+    ArithOp::SetLessThan,
+    ArithOp::SetLessThanU,
+    ArithOp::Mul,
+    ArithOp::MulHigh,
+    ArithOp::MulHighSU,
+    ArithOp::MulHighU,
+    ArithOp::Div,
+    ArithOp::DivU,
+    ArithOp::Rem,
+    ArithOp::RemU,
+];
+// Stores only use the first three
+const WIDTHS: [LoadStoreWidth; 5] = [
+    LoadStoreWidth::Byte,
+    LoadStoreWidth::Half,
+    LoadStoreWidth::Word,
+    LoadStoreWidth::ByteUnsigned,
+    LoadStoreWidth::HalfUnsigned,
+];
+const COMPARISONS: [Comparison; 6] = [
+    Comparison::Equal,
+    Comparison::NotEqual,
+    Comparison::LessThan,
+    Comparison::GreaterOrEqual,
+    Comparison::LessThanUnsigned,
+    Comparison::GreaterOrEqualUnsigned,
+];
+
+fn random_instruction(rng: &mut Rng) -> Instruction {
+    match rng.next() % 12 {
+        0 => Instruction::Arith {
+            dst: rng.register(),
+            src1: rng.register(),
+            src2: rng.register(),
+            op: rng.pick(&ARITH_OPS),
+        },
+        1 => {
+            let op = rng.pick(&ARITH_OPS[2..10]);
+            let imm = match op {
+                ArithOp::ShiftLeftLogical
+                | ArithOp::ShiftRightLogical
+                | ArithOp::ShiftRightArith => (rng.next() % 32) as i32,
+                _ => rng.signed(12),
+            };
+            Instruction::ArithImm {
+                dst: rng.register(),
+                src: rng.register(),
+                imm,
+                op,
+            }
+        }
+        2 => Instruction::ArithImm {
+            dst: rng.register(),
+            src: rng.register(),
+            imm: rng.signed(12),
+            op: ArithOp::Add,
+        },
+        3 => Instruction::JumpAndLink {
+            dst: rng.register(),
+            offset: rng.signed(21) & !1,
+        },
+        4 => Instruction::JumpAndLinkRegister {
+            base: rng.register(),
+            dst: rng.register(),
+            offset: rng.signed(12),
+        },
+        5 => Instruction::Store {
+            src: rng.register(),
+            offset: rng.signed(12),
+            base: rng.register(),
+            width: rng.pick(&WIDTHS[..3]),
+        },
+        6 => Instruction::Load {
+            dst: rng.register(),
+            offset: rng.signed(12),
+            base: rng.register(),
+            width: rng.pick(&WIDTHS),
+        },
+        7 => Instruction::Branch {
+            src1: rng.register(),
+            src2: rng.register(),
+            cond: rng.pick(&COMPARISONS),
+            offset: rng.signed(13) & !1,
+        },
+        8 => Instruction::LoadUpperImm {
+            dst: rng.register(),
+            imm: rng.next() & 0xfffff000,
+        },
+        9 => Instruction::AddUpperImmToPc {
+            dst: rng.register(),
+            imm: rng.next() & 0xfffff000,
+        },
+        10 => Instruction::Poseidon2Permute {
+            base: rng.register(),
+        },
+        _ => Instruction::Uint256Add {
+            dst: rng.register(),
+            x_base: rng.register(),
+            y_base: rng.register(),
+        },
+    }
+}
+
+#[test]
+fn test_round_trip_random_instructions() {
+    let mut rng = Rng(0x4e554c4c);
+    for _ in 0..100_000 {
+        let instruction = random_instruction(&mut rng);
+        let word = instruction.encode();
+        assert_eq!(
+            Instruction::parse(word).unwrap(),
+            instruction,
+            "0x{word:08x}"
+        );
+    }
+    for instruction in [Instruction::EnvironmentCall, Instruction::EnvironmentBreak] {
+        assert_eq!(
+            Instruction::parse(instruction.encode()).unwrap(),
+            instruction
+        );
+    }
+    assert_eq!(Instruction::EnvironmentBreak.encode(), 0x00100073);
+}
+
+#[test]
+fn test_encode_out_of_range() {
+    let imm = |imm| Instruction::ArithImm {
+        dst: 10,
+        src: 10,
+        imm,
+        op: ArithOp::Add,
+    };
+    assert_eq!(
+        imm(2048).try_encode(),
+        Err(EncodeError::ImmediateOutOfRange(2048))
+    );
+    assert_eq!(
+        imm(-2049).try_encode(),
+        Err(EncodeError::ImmediateOutOfRange(-2049))
+    );
+    let shift = Instruction::ArithImm {
+        dst: 10,
+        src: 10,
+        imm: 32,
+        op: ArithOp::ShiftLeftLogical,
+    };
+    assert_eq!(
+        shift.try_encode(),
+        Err(EncodeError::ImmediateOutOfRange(32))
+    );
+    let subi = Instruction::ArithImm {
+        dst: 10,
+        src: 10,
+        imm: 1,
+        op: ArithOp::Sub,
+    };
+    assert_eq!(
+        subi.try_encode(),
+        Err(EncodeError::NoImmediateForm(ArithOp::Sub))
+    );
+    let muli = Instruction::ArithImm {
+        dst: 10,
+        src: 10,
+        imm: 3,
+        op: ArithOp::Mul,
+    };
+    assert_eq!(
+        muli.try_encode(),
+        Err(EncodeError::NoImmediateForm(ArithOp::Mul))
+    );
+    let store = Instruction::Store {
+        src: 10,
+        offset: 0,
+        base: 11,
+        width: LoadStoreWidth::HalfUnsigned,
+    };
+    assert_eq!(
+        store.try_encode(),
+        Err(EncodeError::NoStoreForm(LoadStoreWidth::HalfUnsigned))
+    );
+    // Nor do they decode, sb with the func3 of lbu
+    assert!(Instruction::parse(0x00a5c023).is_err());
+    let branch = |offset| Instruction::Branch {
+        src1: 10,
+        src2: 11,
+        cond: Comparison::Equal,
+        offset,
+    };
+    assert_eq!(
+        branch(4096).try_encode(),
+        Err(EncodeError::ImmediateOutOfRange(4096))
+    );
+    assert_eq!(
+        branch(3).try_encode(),
+        Err(EncodeError::MisalignedOffset(3))
+    );
+    let jump = Instruction::JumpAndLink {
+        dst: 1,
+        offset: 1 << 20,
+    };
+    assert_eq!(
+        jump.try_encode(),
+        Err(EncodeError::ImmediateOutOfRange(1 << 20))
+    );
+    let upper = Instruction::LoadUpperImm {
+        dst: 10,
+        imm: 0x12345,
+    };
+    assert_eq!(
+        upper.try_encode(),
+        Err(EncodeError::UnalignedUpperImmediate(0x12345))
+    );
+    let register = Instruction::Arith {
+        dst: 32,
+        src1: 0,
+        src2: 0,
+        op: ArithOp::Add,
+    };
+    assert_eq!(register.try_encode(), Err(EncodeError::InvalidRegister(32)));
+}
+
+#[test]
+#[should_panic]
+fn test_encode_panics_out_of_range() {
+    Instruction::Store {
+        src: 10,
+        offset: 4096,
+        base: 2,
+        width: LoadStoreWidth::Word,
+    }
+    .encode();
+}
+
+#[test]
+fn test_unused_custom_fields_must_be_zero() {
+    let permute = Instruction::Poseidon2Permute { base: 10 }.encode();
+    assert_eq!(permute, 0x0005000b);
+    // rd, then rs2
+    assert!(Instruction::parse(permute | 1 << 7).is_err());
+    assert!(Instruction::parse(permute | 1 << 20).is_err());
+    let ecall = Instruction::EnvironmentCall.encode();
+    assert!(Instruction::parse(ecall | 1 << 7).is_err());
+    assert!(Instruction::parse(ecall | 1 << 15).is_err());
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+use vm::{
+    assembler::assemble,
+    builder::ProgramBuilder,
+    vm::{
+        execution::{ExecutionError, Executor, run_program},
+        instructions::{ArithOp, Comparison, Instruction, LoadStoreWidth},
+    },
+};
+
+const CODE: u32 = 0x1000;
+
+fn run(instructions: &[Instruction]) -> Result<(i32, i32), ExecutionError> {
+    let program = ProgramBuilder::new()
+        .code(CODE, instructions)
+        .entry_point(CODE)
+        .build()
+        .unwrap();
+    run_program(program.image, program.entry_point)
+}
+
+const RET: Instruction = Instruction::JumpAndLinkRegister {
+    base: 1,
+    dst: 0,
+    offset: 0,
+};
+
+#[test]
+fn test_unwritten_memory_reads_as_zero() {
+    let load = Instruction::Load {
+        dst: 10,
+        offset: 0x100,
+        base: 0,
+        width: LoadStoreWidth::Word,
+    };
+    assert_eq!(run(&[load, RET]).unwrap(), (0, 0));
+}
+
+#[test]
+fn test_jump_outside_image_is_an_error() {
+    let jump = Instruction::JumpAndLink {
+        dst: 0,
+        offset: 0x100,
+    };
+    assert!(matches!(
+        run(&[jump]),
+        Err(ExecutionError::InvalidPc(0x1100))
+    ));
+    // Running off the end of the code
+    let nop = Instruction::ArithImm {
+        dst: 0,
+        src: 0,
+        imm: 0,
+        op: ArithOp::Add,
+    };
+    assert!(matches!(
+        run(&[nop]),
+        Err(ExecutionError::InvalidPc(0x1004))
+    ));
+}
+
+#[test]
+fn test_branch_from_last_word() {
+    // The pc after the branch wraps to zero before the offset is applied
+    let branch = Instruction::Branch {
+        src1: 0,
+        src2: 0,
+        cond: Comparison::Equal,
+        offset: -4,
+    };
+    let image = BTreeMap::from([(0xfffffff8, RET.encode()), (0xfffffffc, branch.encode())]);
+    assert_eq!(run_program(image, 0xfffffffc).unwrap(), (0, 0));
+}
+
+#[test]
+fn test_misaligned_pc_is_an_error() {
+    let base = Instruction::LoadUpperImm { dst: 5, imm: CODE };
+    // Only the lowest bit of a jalr target is cleared
+    let jump = Instruction::JumpAndLinkRegister {
+        base: 5,
+        dst: 0,
+        offset: 7,
+    };
+    assert!(matches!(
+        run(&[base, jump]),
+        Err(ExecutionError::InvalidPc(0x1006))
+    ));
+}
+
+#[test]
+fn test_multiply_and_divide() {
+    let min = i32::MIN as u32;
+    for (op, a, b, expected) in [
+        (ArithOp::Mul, 7, -3i32 as u32, -21i32 as u32),
+        (ArithOp::MulHigh, min, min, 0x40000000),
+        (ArithOp::MulHigh, -1i32 as u32, 5, u32::MAX),
+        (ArithOp::MulHighSU, -1i32 as u32, u32::MAX, u32::MAX),
+        (ArithOp::MulHighU, u32::MAX, u32::MAX, 0xfffffffe),
+        (ArithOp::Div, -7i32 as u32, 2, -3i32 as u32),
+        (ArithOp::DivU, -7i32 as u32, 2, 0x7ffffffc),
+        (ArithOp::Rem, -7i32 as u32, 2, -1i32 as u32),
+        (ArithOp::RemU, 7, 3, 1),
+        // Division by zero and overflow are defined and do not trap
+        (ArithOp::Div, 5, 0, u32::MAX),
+        (ArithOp::DivU, 5, 0, u32::MAX),
+        (ArithOp::Rem, 5, 0, 5),
+        (ArithOp::RemU, 5, 0, 5),
+        (ArithOp::Div, min, -1i32 as u32, min),
+        (ArithOp::Rem, min, -1i32 as u32, 0),
+    ] {
+        let instruction = Instruction::Arith {
+            dst: 10,
+            src1: 11,
+            src2: 12,
+            op,
+        };
+        let program = ProgramBuilder::new()
+            .code(CODE, &[instruction, RET])
+            .entry_point(CODE)
+            .build()
+            .unwrap();
+        let mut executor = Executor::new(program.image, program.entry_point);
+        executor.registers.write(11, a);
+        executor.registers.write(12, b);
+        executor.run().unwrap();
+        assert_eq!(executor.registers.read(10), expected, "{op:?} {a} {b}");
+    }
+}
+
+fn load(dst: u32, offset: i32, width: LoadStoreWidth) -> Instruction {
+    Instruction::Load {
+        dst,
+        offset,
+        base: 5,
+        width,
+    }
+}
+
+fn store(src: u32, offset: i32, width: LoadStoreWidth) -> Instruction {
+    Instruction::Store {
+        src,
+        offset,
+        base: 5,
+        width,
+    }
+}
+
+#[test]
+fn test_sub_word_loads_and_stores() {
+    let program = ProgramBuilder::new()
+        .code(
+            CODE,
+            &[
+                Instruction::LoadUpperImm {
+                    dst: 5,
+                    imm: 0x2000,
+                },
+                load(10, 1, LoadStoreWidth::Byte),
+                load(11, 2, LoadStoreWidth::Half),
+                load(12, 3, LoadStoreWidth::ByteUnsigned),
+                load(13, 2, LoadStoreWidth::HalfUnsigned),
+                // The other bytes of the word are kept
+                store(12, 1, LoadStoreWidth::Byte),
+                store(10, 2, LoadStoreWidth::Half),
+                RET,
+            ],
+        )
+        // Little endian, so the bytes are 01 7f ff 80
+        .data(0x2000, &[0x80ff7f01])
+        .entry_point(CODE)
+        .build()
+        .unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    executor.run().unwrap();
+    let registers: Vec<_> = (10..14).map(|i| executor.registers.read(i)).collect();
+    assert_eq!(registers, [0x7f, 0xffff80ff, 0x80, 0x80ff]);
+    assert_eq!(executor.memory.read_word(0x2000), Some(0x007f8001));
+}
+
+#[test]
+fn test_misaligned_access_is_an_error() {
+    let base = Instruction::LoadUpperImm {
+        dst: 5,
+        imm: 0x2000,
+    };
+    for (access, addr) in [
+        (load(10, 1, LoadStoreWidth::Half), 0x2001),
+        (load(10, 3, LoadStoreWidth::HalfUnsigned), 0x2003),
+        (load(10, 2, LoadStoreWidth::Word), 0x2002),
+        (store(10, 1, LoadStoreWidth::Half), 0x2001),
+        (store(10, 6, LoadStoreWidth::Word), 0x2006),
+    ] {
+        let result = run(&[base, access, RET]);
+        assert!(
+            matches!(result, Err(ExecutionError::MisalignedAccess(a)) if a == addr),
+            "{access}: {result:?}"
+        );
+    }
+}
+
+#[test]
+fn test_store_into_code_invalidates_decoded_instruction() {
+    let patched = Instruction::ArithImm {
+        dst: 10,
+        src: 10,
+        imm: 10,
+        op: ArithOp::Add,
+    }
+    .encode();
+    let upper = patched.wrapping_add(0x800) & 0xfffff000;
+    let code = [
+        Instruction::LoadUpperImm { dst: 6, imm: CODE },
+        Instruction::LoadUpperImm { dst: 5, imm: upper },
+        Instruction::ArithImm {
+            dst: 5,
+            src: 5,
+            imm: patched.wrapping_sub(upper) as i32,
+            op: ArithOp::Add,
+        },
+        Instruction::JumpAndLink { dst: 0, offset: 4 },
+        // 0x1010, runs twice and is replaced by `addi a0, a0, 10` in between
+        Instruction::ArithImm {
+            dst: 10,
+            src: 10,
+            imm: 1,
+            op: ArithOp::Add,
+        },
+        Instruction::Branch {
+            src1: 11,
+            src2: 0,
+            cond: Comparison::NotEqual,
+            offset: 16,
+        },
+        Instruction::ArithImm {
+            dst: 11,
+            src: 0,
+            imm: 1,
+            op: ArithOp::Add,
+        },
+        Instruction::Store {
+            src: 5,
+            offset: 0x10,
+            base: 6,
+            width: LoadStoreWidth::Word,
+        },
+        Instruction::JumpAndLink {
+            dst: 0,
+            offset: -16,
+        },
+        RET,
+    ];
+    let program = ProgramBuilder::new()
+        .code(CODE, &code)
+        .entry_point(CODE)
+        .build()
+        .unwrap();
+    let cached = Executor::new(program.image.clone(), CODE);
+    let uncached = Executor::new(program.image.clone(), CODE).without_decode_cache();
+    for mut executor in [cached, uncached] {
+        while !executor.is_halted() {
+            executor.step().unwrap();
+        }
+        assert_eq!(executor.registers.read(10), 11);
+        assert_eq!(executor.memory.read_word(CODE + 0x10), Some(patched));
+    }
+    // The patched instruction starts a basic block that was run before the store
+    let mut blocks = Executor::new(program.image, CODE);
+    blocks.run().unwrap();
+    assert_eq!(blocks.registers.read(10), 11);
+    assert_eq!(blocks.cycles, 12);
+}
+
+#[test]
+fn test_resume_steps_over_ebreak() {
+    let program = ProgramBuilder::new()
+        .code(
+            CODE,
+            &[
+                Instruction::EnvironmentBreak,
+                Instruction::ArithImm {
+                    dst: 10,
+                    src: 10,
+                    imm: 1,
+                    op: ArithOp::Add,
+                },
+                RET,
+            ],
+        )
+        .entry_point(CODE)
+        .build()
+        .unwrap();
+    let mut executor = Executor::new(program.image, CODE);
+    assert!(matches!(
+        executor.run(),
+        Err(ExecutionError::Breakpoint(CODE))
+    ));
+    // Setting the pc, even to the ebreak, executes it again
+    executor.set_pc(CODE);
+    assert!(matches!(
+        executor.step(),
+        Err(ExecutionError::Breakpoint(CODE))
+    ));
+    // Otherwise stepping only moves past it
+    executor.step().unwrap();
+    assert_eq!((executor.pc, executor.cycles), (CODE + 4, 0));
+    executor.run().unwrap();
+    assert_eq!(executor.registers.read(10), 1);
+}
+
+// UnsupportedInstruction is left out, only hand-built instructions can raise it
+#[test]
+fn test_errors_leave_the_pc_at_the_fault() {
+    let programs = [
+        ("li t0, 2\nfault:\n    lw a0, 0(t0)", "Misaligned"),
+        ("li a7, 99\nfault:\n    ecall", "Unknown syscall"),
+        (
+            "li a0, 2\n    li a7, 1\nfault:\n    ecall",
+            "Syscall buffer",
+        ),
+        (
+            "la a0, data\n    li a7, 1\nfault:\n    ecall",
+            "not a canonical field element",
+        ),
+        ("la a0, data\n    li a7, 3\nfault:\n    ecall", "curve"),
+        ("li a0, 7\nfault:\n    ebreak", "ebreak"),
+    ];
+    for (code, error) in programs {
+        let source = format!("main:\n    {code}\n    ret\ndata:\n    .word -1, 1, 2, 3");
+        let program = assemble(&source).unwrap();
+        let fault = program.symbol_address("fault").unwrap();
+        let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+        let stepped_error = loop {
+            if let Err(error) = stepped.step() {
+                break error;
+            }
+        };
+        let mut run = Executor::new(program.image, program.entry_point);
+        let run_error = run.run().unwrap_err();
+        assert!(stepped_error.to_string().contains(error), "{stepped_error}");
+        assert_eq!(stepped_error.to_string(), run_error.to_string());
+        for executor in [&stepped, &run] {
+            assert_eq!(executor.pc, fault, "{code}");
+            assert_eq!(executor.cycles, ((fault - program.entry_point) / 4) as u64);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    net::TcpStream,
+    process::{Command, Stdio},
+};
+
+use common::{DEBUGGEE, MISALIGNED_LOAD, load};
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    gdb::{GdbStub, frame},
+    vm::execution::Executor,
+};
+
+fn new_stub() -> (GdbStub, Elf) {
+    let (executor, program) = load(DEBUGGEE);
+    (GdbStub::new(executor), program)
+}
+
+fn handle(stub: &mut GdbStub, packet: &str) -> String {
+    stub.handle(packet).unwrap()
+}
+
+#[test]
+fn test_registers_and_memory() {
+    let (mut stub, program) = new_stub();
+    let registers = handle(&mut stub, "g");
+    assert_eq!(registers.len(), 33 * 8);
+    // sp, then pc in memory order
+    assert_eq!(&registers[2 * 8..3 * 8], "f0ffffff");
+    assert_eq!(&registers[32 * 8..], "00000100");
+
+    assert_eq!(handle(&mut stub, "Pa=2a000000"), "OK");
+    assert_eq!(handle(&mut stub, "pa"), "2a000000");
+    assert_eq!(stub.executor.registers.read(10), 42);
+    assert_eq!(handle(&mut stub, "P20=04000100"), "OK");
+    assert_eq!(stub.executor.pc, 0x10004);
+    assert_eq!(handle(&mut stub, "p21"), "E01");
+
+    let registers = handle(&mut stub, "g");
+    assert_eq!(handle(&mut stub, &format!("G{registers}")), "OK");
+    assert_eq!(handle(&mut stub, "g"), registers);
+    assert_eq!(handle(&mut stub, "G00"), "E01");
+
+    let value = program.symbol_address("value").unwrap();
+    assert_eq!(handle(&mut stub, &format!("m{value:x},4")), "2a000000");
+    // Unaligned writes only change the bytes they cover
+    assert_eq!(handle(&mut stub, &format!("M{:x},2:2b01", value + 1)), "OK");
+    assert_eq!(handle(&mut stub, &format!("m{value:x},6")), "2a2b01000000");
+    assert_eq!(stub.executor.memory.read_word(value), Some(0x00012b2a));
+    assert_eq!(handle(&mut stub, &format!("M{value:x},2:2b")), "E01");
+}
+
+#[test]
+fn test_breakpoints_and_stepping() {
+    let (mut stub, _) = new_stub();
+    assert_eq!(handle(&mut stub, "?"), "S05");
+    assert_eq!(handle(&mut stub, "Z0,10008,4"), "OK");
+    assert_eq!(handle(&mut stub, "c"), "S05");
+    assert_eq!(stub.executor.pc, 0x10008);
+    assert_eq!(handle(&mut stub, "c"), "S05");
+    assert_eq!(stub.executor.registers.read(10), 5);
+    assert_eq!(handle(&mut stub, "s"), "S05");
+    assert_eq!(stub.executor.pc, 0x1000c);
+    assert_eq!(handle(&mut stub, "z0,10008,4"), "OK");
+    // Access watchpoints are not supported
+    assert_eq!(handle(&mut stub, "Z5,10028,4"), "");
+
+    // Stops at the ebreak, then steps over it
+    assert_eq!(handle(&mut stub, "c"), "S05");
+    assert_eq!(stub.executor.pc, 0x10014);
+    assert_eq!(handle(&mut stub, "s"), "S05");
+    assert_eq!(stub.executor.pc, 0x10018);
+    // The exit status is a0
+    assert_eq!(handle(&mut stub, "c"), "W0f");
+    assert_eq!(handle(&mut stub, "?"), "W0f");
+
+    // Continuing from an address
+    let (mut skipping, _) = new_stub();
+    assert_eq!(handle(&mut skipping, "c10018"), "W00");
+    assert_eq!(skipping.executor.registers.read(11), 42);
+}
+
+#[test]
+fn test_faults_are_not_skipped() {
+    let mut stub = GdbStub::new(load(MISALIGNED_LOAD).0);
+    // A misaligned load stops with SIGILL, and resuming runs into it again
+    for packet in ["c", "s", "c"] {
+        assert_eq!(handle(&mut stub, packet), "S04");
+        assert_eq!(stub.executor.pc, 0x10004);
+        assert_eq!(stub.executor.cycles, 1);
+    }
+    assert_eq!(handle(&mut stub, "P5=08000100"), "OK");
+    assert_eq!(handle(&mut stub, "c"), "W67");
+}
+
+#[test]
+fn test_watchpoints() {
+    let (mut stub, _) = new_stub();
+    // Covers the words of the range
+    assert_eq!(handle(&mut stub, "Z3,10026,4"), "OK");
+    assert_eq!(stub.executor.watchpoints.len(), 2);
+    assert_eq!(handle(&mut stub, "c"), "S05");
+    assert_eq!(handle(&mut stub, "c"), "T05rwatch:10028;");
+    assert_eq!(handle(&mut stub, "p20"), "24000100");
+    assert_eq!(handle(&mut stub, "z3,10026,4"), "OK");
+    assert!(stub.executor.watchpoints.is_empty());
+    assert_eq!(handle(&mut stub, "c"), "W0f");
+}
+
+#[test]
+fn test_target_description() {
+    let (mut stub, _) = new_stub();
+    assert!(
+        handle(&mut stub, "qSupported:multiprocess+;swbreak+").contains("qXfer:features:read+")
+    );
+    let mut description = String::new();
+    loop {
+        let offset = description.len();
+        let chunk = handle(
+            &mut stub,
+            &format!("qXfer:features:read:target.xml:{offset:x},40"),
+        );
+        description.push_str(&chunk[1..]);
+        if chunk.starts_with('l') {
+            break;
+        }
+        assert!(chunk.starts_with('m'));
+        assert_eq!(chunk.len(), 1 + 0x40);
+    }
+    assert!(description.contains("<architecture>riscv:rv32</architecture>"));
+    assert_eq!(description.matches("<reg ").count(), 33);
+    assert!(
+        description.contains("<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"32\"/>")
+    );
+}
+
+#[test]
+fn test_serve_acknowledges_packets() {
+    let (mut stub, _) = new_stub();
+    let mut input = Vec::new();
+    input.extend(b"+");
+    input.extend(frame("pa"));
+    // A corrupted packet is rejected, then the last response is requested again
+    input.extend(b"$pb#00-");
+    input.extend(frame("m10000,4"));
+    input.extend(frame("k"));
+    input.extend(frame("?"));
+    let mut output = Vec::new();
+    stub.serve(Cursor::new(input), &mut output).unwrap();
+
+    let mut expected = Vec::new();
+    expected.extend(b"+");
+    expected.extend(frame("00000000"));
+    expected.extend(b"-");
+    expected.extend(frame("00000000"));
+    expected.extend(b"+");
+    expected.extend(frame("13050000"));
+    // Nothing after the kill
+    expected.extend(b"+");
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        String::from_utf8(expected).unwrap()
+    );
+}
+
+#[test]
+fn test_serve_interrupts_running_program() {
+    let program = assemble("main:\n    j main").unwrap();
+    let mut stub = GdbStub::new(Executor::new(program.image, program.entry_point));
+    let mut input = frame("c");
+    input.push(0x03);
+    input.extend(frame("k"));
+    let mut output = Vec::new();
+    stub.serve(Cursor::new(input), &mut output).unwrap();
+
+    let mut expected = b"+".to_vec();
+    expected.extend(frame("S02"));
+    expected.extend(b"+");
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        String::from_utf8(expected).unwrap()
+    );
+    assert!(stub.executor.cycles > 0);
+}
+
+#[test]
+fn test_gdb_server_binary() {
+    let path = std::env::temp_dir().join(format!("gdb-server-{}.s", std::process::id()));
+    std::fs::write(&path, DEBUGGEE).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gdb-server"))
+        .arg(&path)
+        .arg("0")
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let addr = line
+        .strip_prefix("Listening on ")
+        .and_then(|line| line.split(',').next())
+        .unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut exchange = |packet: &str, response: &str| {
+        stream.write_all(&frame(packet)).unwrap();
+        let mut expected = b"+".to_vec();
+        expected.extend(frame(response));
+        let mut received = vec![0; expected.len()];
+        stream.read_exact(&mut received).unwrap();
+        assert_eq!(received, expected);
+    };
+    exchange("Z0,10014,4", "OK");
+    exchange("c", "S05");
+    exchange("p20", "14000100");
+    exchange("D", "OK");
+    assert!(child.wait().unwrap().success());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::load;
+use vm::vm::{execution::Executor, history::History, observer::Silent, registers::Registers};
+
+const LOOP: &str = "
+main:
+    la t0, counter
+    li t1, 10
+loop:
+    lw t2, 0(t0)
+    addi t2, t2, 3
+    sw t2, 0(t0)
+    addi t1, t1, -1
+    bnez t1, loop
+    lw a0, 0(t0)
+    ret
+counter:
+    .word 0
+";
+
+fn state(executor: &Executor) -> (u32, Registers, BTreeMap<u32, u32>, u64) {
+    (
+        executor.pc,
+        executor.registers.clone(),
+        executor.memory.words().clone(),
+        executor.cycles,
+    )
+}
+
+#[test]
+fn test_rewind_rebuilds_every_state() {
+    let poseidon = std::fs::read_to_string("./programs/asm/poseidon2_permute.s").unwrap();
+    for source in [LOOP, &poseidon] {
+        let (mut executor, _) = load(source);
+        let mut history = History::new(&executor, 4);
+        let mut states = vec![state(&executor)];
+        while !executor.is_halted() {
+            history.step(&mut executor, &mut Silent).unwrap();
+            states.push(state(&executor));
+        }
+        let end = state(&executor);
+        for cycle in (0..states.len()).rev() {
+            history.rewind(&mut executor, cycle as u64);
+            assert_eq!(state(&executor), states[cycle]);
+        }
+        // Running again records the same steps
+        history.run(&mut executor, &mut Silent).unwrap();
+        assert_eq!(state(&executor), end);
+        let middle = states.len() / 2;
+        history.rewind(&mut executor, middle as u64);
+        assert_eq!(state(&executor), states[middle]);
+    }
+}
+
+#[test]
+fn test_run_stops_at_breakpoints_on_snapshots() {
+    let (mut executor, program) = load(LOOP);
+    let loop_addr = program.symbol_address("loop").unwrap();
+    executor.breakpoints.insert(loop_addr);
+    // Iterations start every 5 cycles from cycle 3, where the snapshots are taken too
+    for _ in 0..3 {
+        executor.step().unwrap();
+    }
+    let mut history = History::new(&executor, 5);
+    let mut stops = Vec::new();
+    while !executor.is_halted() {
+        history.run(&mut executor, &mut Silent).unwrap();
+        stops.push(executor.cycles);
+    }
+    assert_eq!(stops, [8, 13, 18, 23, 28, 33, 38, 43, 48, 55]);
+    assert_eq!(executor.registers.read(10), 30);
+}
+
+#[test]
+fn test_last_write_and_last_visit() {
+    let (mut executor, program) = load(LOOP);
+    let counter = program.symbol_address("counter").unwrap();
+    let loop_addr = program.symbol_address("loop").unwrap();
+    let mut history = History::new(&executor, 4);
+    history.run(&mut executor, &mut Silent).unwrap();
+
+    let write = history.last_write(counter).unwrap();
+    assert_eq!(write.cycle, 50);
+    assert_eq!(write.pc, loop_addr + 8);
+    assert_eq!(write.instruction.to_string(), "sw t2, 0(t0)");
+    assert_eq!((write.old_value, write.value), (27, 30));
+    assert_eq!(history.last_write(counter + 4), None);
+
+    assert_eq!(
+        history.last_visit(executor.cycles, |pc| pc == loop_addr),
+        Some(48)
+    );
+    assert_eq!(history.last_visit(48, |pc| pc == loop_addr), Some(43));
+    assert_eq!(history.last_visit(3, |pc| pc == loop_addr), None);
+
+    // Going back forgets the later writes
+    history.rewind(&mut executor, 20);
+    assert_eq!(history.last_write(counter).unwrap().cycle, 15);
+    assert_eq!(executor.memory.read_word(counter), Some(9));
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "jit")]
+
+mod common;
+
+use common::{assert_same_state, step_to_end};
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::{
+        execution::{ExecutionError, Executor, JIT_HOT_THRESHOLD},
+        statistics::InstructionMix,
+    },
+};
+
+// Enough for every riscv-test to report its outcome and reach the final spin loop
+const MAX_CYCLES: u64 = 50_000;
+
+// Runs `program` stepped and with every block translated on first use
+fn run_both(program: &Elf, name: &str) -> Executor {
+    let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+    let mut jit = Executor::new(program.image.clone(), program.entry_point).with_jit(1);
+    stepped.cycle_limit = MAX_CYCLES;
+    jit.cycle_limit = MAX_CYCLES;
+    let stepped_result = step_to_end(&mut stepped).map_err(|error| error.to_string());
+    let jit_result = jit.run().map_err(|error| error.to_string());
+    assert_eq!(stepped_result, jit_result, "{name}");
+    assert_same_state(&stepped, &jit, name);
+    jit
+}
+
+#[test]
+fn test_jit_matches_interpreter_on_artifacts() {
+    let mut translated = 0;
+    for directory in std::fs::read_dir("./program_artifacts").unwrap() {
+        for entry in std::fs::read_dir(directory.unwrap().path()).unwrap() {
+            let path = entry.unwrap().path();
+            let program = Elf::load(&std::fs::read(&path).unwrap()).unwrap();
+            translated += run_both(&program, &path.to_string_lossy()).translated_blocks();
+        }
+    }
+    if cfg!(target_arch = "x86_64") {
+        assert!(translated > 0);
+    }
+}
+
+const LOOP: &str = "
+main:
+    li a0, 0
+    li t0, 1000
+    la t1, buffer
+loop:
+    lw t2, 0(t1)
+    add t2, t2, t0
+    sw t2, 0(t1)
+    addi a0, a0, 3
+    addi t0, t0, -1
+    bnez t0, loop
+    ret
+buffer:
+    .word 0
+";
+
+#[test]
+fn test_jit_hot_loop() {
+    let program = assemble(LOOP).unwrap();
+    let buffer = program.symbol_address("buffer").unwrap();
+    let mut executor =
+        Executor::new(program.image, program.entry_point).with_jit(JIT_HOT_THRESHOLD);
+    executor.run().unwrap();
+    assert_eq!(executor.registers.read(10), 3000);
+    assert_eq!(executor.memory.read_word(buffer), Some(1000 * 1001 / 2));
+    assert_eq!(executor.cycles, 4 + 1000 * 6 + 1);
+    if cfg!(target_arch = "x86_64") {
+        assert_eq!(executor.translated_blocks(), 1);
+    }
+}
+
+#[test]
+fn test_jit_with_statistics() {
+    let program = assemble(LOOP).unwrap();
+    let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+    let mut mix = InstructionMix::default();
+    stepped.run_with(&mut mix).unwrap();
+    // Blocks are interpreted to be counted
+    let mut executor = Executor::new(program.image, program.entry_point)
+        .with_jit(1)
+        .with_statistics();
+    executor.run().unwrap();
+    assert_eq!(executor.statistics(), Some(&mix));
+    assert_eq!(executor.translated_blocks(), 0);
+    assert_same_state(&stepped, &executor, "statistics");
+}
+
+#[test]
+fn test_jit_stops_at_cycle_limit() {
+    let program = assemble(LOOP).unwrap();
+    let mut stepped = Executor::new(program.image.clone(), program.entry_point);
+    let mut jit = Executor::new(program.image, program.entry_point).with_jit(1);
+    for limit in [1, 100, 1001, 5000] {
+        stepped.cycle_limit = limit;
+        jit.cycle_limit = limit;
+        step_to_end(&mut stepped).unwrap();
+        jit.run().unwrap();
+        assert_same_state(&stepped, &jit, &format!("limit {limit}"));
+    }
+}
+
+#[test]
+fn test_jit_store_into_translated_code() {
+    // The first pass through `patch` runs translated code, which is then overwritten
+    let source = "
+main:
+    la t1, target
+    li t0, 0x00a50513   # addi a0, a0, 10
+    li t2, 3
+patch:
+    addi t2, t2, -1
+    beqz t2, done
+target:
+    addi a0, a0, 1
+    sw t0, 0(t1)
+    j patch
+done:
+    ret
+";
+    let program = assemble(source).unwrap();
+    let jit = run_both(&program, "patch");
+    assert!(jit.is_halted());
+    assert_eq!(jit.registers.read(10), 11);
+}
+
+#[test]
+fn test_jit_store_into_current_block() {
+    let source = "
+main:
+    la t1, target
+    li t0, 0x00a50513   # addi a0, a0, 10
+    sw t0, 0(t1)
+    addi a0, a0, 1
+target:
+    addi a0, a0, 1
+    ret
+";
+    let program = assemble(source).unwrap();
+    let jit = run_both(&program, "store into current block");
+    assert_eq!(jit.registers.read(10), 11);
+}
+
+#[test]
+fn test_jit_misaligned_access() {
+    // The second pass misaligns the access, first inside a block and then at its start
+    for access in ["lw t2, 0(t1)", "sw a0, 0(t1)"] {
+        let source = format!(
+            "
+main:
+    la t1, buffer
+loop:
+    addi a0, a0, 1
+    {access}
+    addi t1, t1, 2
+    j loop
+buffer:
+    .word 0, 0
+"
+        );
+        let program = assemble(&source).unwrap();
+        let mut jit = Executor::new(program.image.clone(), program.entry_point).with_jit(1);
+        assert!(matches!(
+            jit.run(),
+            Err(ExecutionError::MisalignedAccess(addr)) if addr % 4 == 2
+        ));
+        run_both(&program, access);
+    }
+}
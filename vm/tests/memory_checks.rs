@@ -0,0 +1,129 @@
+mod common;
+
+use common::load;
+use vm::vm::{
+    execution::{ExecutionError, Watch},
+    observer::{UninitializedRead, UninitializedReads},
+};
+
+const PROGRAM: &str = "
+main:
+    la t0, value
+    lw t1, 0(t0)
+    lw t2, 4(t0)
+    sw t1, 8(t0)
+    lw a0, 8(t0)
+    ret
+value:
+    .word 42
+";
+
+#[test]
+fn test_uninitialized_reads_are_reported_with_the_pc() {
+    let (mut executor, program) = load(PROGRAM);
+    let value = program.symbol_address("value").unwrap();
+    let mut reads = UninitializedReads::default();
+    executor.run_with(&mut reads).unwrap();
+    // The image and the store initialize the other words
+    assert_eq!(
+        reads.reads,
+        [UninitializedRead {
+            pc: program.entry_point + 12,
+            addr: value + 4,
+        }]
+    );
+    assert_eq!(
+        reads.reads[0].to_string(),
+        format!(
+            "Read of uninitialized 0x{:08x} at 0x{:08x}",
+            value + 4,
+            program.entry_point + 12
+        )
+    );
+    assert_eq!(executor.registers.read(10), 42);
+}
+
+#[test]
+fn test_uninitialized_bytes_are_tracked() {
+    let (mut executor, program) = load(
+        "
+main:
+    li t0, 0x20000
+    sb zero, 1(t0)
+    lbu t1, 1(t0)
+byte:
+    lbu t1, 0(t0)
+    sh zero, 2(t0)
+    lh t1, 2(t0)
+word:
+    lw t1, 0(t0)
+    sb zero, 0(t0)
+    lw a0, 0(t0)
+    ret
+",
+    );
+    let mut reads = UninitializedReads::default();
+    executor.run_with(&mut reads).unwrap();
+    let read = |label| UninitializedRead {
+        pc: program.symbol_address(label).unwrap(),
+        addr: 0x20000,
+    };
+    assert_eq!(reads.reads, [read("byte"), read("word")]);
+}
+
+#[test]
+fn test_watchpoints_stop_after_the_access() {
+    let (mut executor, program) = load(PROGRAM);
+    let value = program.symbol_address("value").unwrap();
+    executor.watchpoints.insert(value + 8, Watch::Write);
+    let error = executor.run().unwrap_err();
+    assert!(matches!(
+        error,
+        ExecutionError::Watchpoint { pc, addr, access: Watch::Write }
+            if pc == program.entry_point + 16 && addr == value + 8
+    ));
+    // The store happened and the pc is past it
+    assert_eq!(executor.memory.read_word(value + 8), Some(42));
+    assert_eq!(executor.pc, program.entry_point + 20);
+    assert_eq!(executor.cycles, 5);
+    executor.run().unwrap();
+    assert!(executor.is_halted());
+
+    let (mut executor, _) = load(PROGRAM);
+    executor.watchpoints.insert(value + 8, Watch::Read);
+    let error = executor.run().unwrap_err();
+    assert!(matches!(
+        error,
+        ExecutionError::Watchpoint {
+            access: Watch::Read,
+            ..
+        }
+    ));
+    assert_eq!(executor.pc, program.entry_point + 24);
+}
+
+#[test]
+fn test_access_watchpoints_stop_at_every_step() {
+    let (mut executor, program) = load(PROGRAM);
+    let value = program.symbol_address("value").unwrap();
+    executor.watchpoints.insert(value, Watch::Access);
+    executor.watchpoints.insert(value + 8, Watch::Access);
+    let mut stops = Vec::new();
+    while !executor.is_halted() {
+        match executor.step() {
+            Ok(()) => {}
+            Err(ExecutionError::Watchpoint { pc, access, .. }) => stops.push((pc, access)),
+            Err(error) => panic!("{error}"),
+        }
+    }
+    let entry = program.entry_point;
+    assert_eq!(
+        stops,
+        [
+            (entry + 8, Watch::Read),
+            (entry + 16, Watch::Write),
+            (entry + 20, Watch::Read),
+        ]
+    );
+    assert_eq!(executor.registers.read(10), 42);
+}
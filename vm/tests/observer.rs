@@ -0,0 +1,117 @@
+mod common;
+
+use common::STORE_AND_LOAD;
+use vm::{
+    assembler::assemble,
+    vm::{
+        execution::Executor,
+        instructions::Instruction,
+        observer::{Event, Log, StructuredTrace},
+        registers::A0,
+    },
+};
+
+#[test]
+fn test_log_shows_instructions_and_effects() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let value = program.symbol_address("value").unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    let mut log = Log::new(Vec::new());
+    executor.run_with(&mut log).unwrap();
+    let output = String::from_utf8(log.finish().unwrap()).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(
+        lines[6..],
+        [
+            "0x0001000c  sw t1, 0(t0)".to_string(),
+            format!("            [0x{value:08x}] = 0x00000007 (was 0x00000005)"),
+            "0x00010010  lw a0, 0(t0)".to_string(),
+            "            a0 = 0x00000007".to_string(),
+            "0x00010014  ret".to_string(),
+            "halted after 6 cycles".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_structured_trace_records_events_in_order() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let value = program.symbol_address("value").unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    let mut trace = StructuredTrace::default();
+    executor.run_with(&mut trace).unwrap();
+    let memory_events: Vec<_> = trace
+        .events
+        .iter()
+        .filter(|event| matches!(event, Event::MemoryRead { .. } | Event::MemoryWrite { .. }))
+        .collect();
+    assert_eq!(
+        memory_events,
+        [
+            &Event::MemoryWrite {
+                addr: value,
+                old_value: 5,
+                value: 7,
+            },
+            &Event::MemoryRead {
+                addr: value,
+                value: 7,
+            },
+        ]
+    );
+    let instructions = trace
+        .events
+        .iter()
+        .filter(|event| matches!(event, Event::Instruction { .. }))
+        .count();
+    assert_eq!(instructions, 6);
+    assert!(trace.events.contains(&Event::RegisterWrite {
+        register: A0,
+        value: 7,
+    }));
+    assert_eq!(trace.events.last(), Some(&Event::Halt { cycles: 6 }));
+}
+
+#[test]
+fn test_syscalls_report_their_memory_accesses() {
+    let source = std::fs::read_to_string("./programs/asm/poseidon2_permute.s").unwrap();
+    let program = assemble(&source).unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    let mut trace = StructuredTrace::default();
+    executor.run_with(&mut trace).unwrap();
+    let syscall = trace
+        .events
+        .iter()
+        .position(|event| *event == Event::Syscall { number: 1 })
+        .unwrap();
+    let reads = trace
+        .events
+        .iter()
+        .filter(|event| matches!(event, Event::MemoryRead { .. }));
+    let writes = trace
+        .events
+        .iter()
+        .filter(|event| matches!(event, Event::MemoryWrite { .. }));
+    // The permutation reads and writes the 16 words of the state, then `lw` reads one
+    assert_eq!(reads.count(), 17);
+    assert_eq!(writes.count(), 16);
+    assert!(matches!(
+        trace.events[syscall - 1],
+        Event::Instruction {
+            instruction: Instruction::EnvironmentCall,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_observed_run_matches_run() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let mut observed = Executor::new(program.image.clone(), program.entry_point);
+    let mut run = Executor::new(program.image, program.entry_point);
+    observed.run_with(&mut StructuredTrace::default()).unwrap();
+    run.run().unwrap();
+    assert_eq!(observed.registers, run.registers);
+    assert_eq!(observed.cycles, run.cycles);
+    assert_eq!(observed.memory.words(), run.memory.words());
+}
@@ -0,0 +1,159 @@
+use std::process::Command;
+
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::{
+        execution::Executor,
+        profiler::{FunctionProfile, Profiler, function_name},
+    },
+};
+
+// 3! by recursion, then a leaf function
+const PROGRAM: &str = "
+main:
+    addi sp, sp, -4
+    sw ra, 0(sp)
+    li a0, 3
+    call fact
+    call leaf
+    lw ra, 0(sp)
+    addi sp, sp, 4
+    ret
+fact:
+    addi sp, sp, -8
+    sw ra, 0(sp)
+    sw a0, 4(sp)
+    li t0, 1
+    ble a0, t0, fact_done
+    addi a0, a0, -1
+    call fact
+    lw t0, 4(sp)
+    add a0, a0, t0
+fact_done:
+    lw ra, 0(sp)
+    addi sp, sp, 8
+    ret
+leaf:
+    nop
+    nop
+    ret
+";
+
+fn profile(program: &Elf) -> Profiler {
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut profiler = Profiler::new(program.entry_point);
+    executor.run_with(&mut profiler).unwrap();
+    assert_eq!(profiler.cycles(), executor.cycles);
+    profiler
+}
+
+#[test]
+fn test_cycles_by_call_path() {
+    let program = assemble(PROGRAM).unwrap();
+    let profiler = profile(&program);
+    assert_eq!(
+        profiler.folded(|addr| function_name(&program, addr)),
+        "main 10\n\
+         main;fact 13\n\
+         main;fact;fact 13\n\
+         main;fact;fact;fact 8\n\
+         main;leaf 3\n"
+    );
+
+    let fact = program.symbol_address("fact").unwrap();
+    let functions = profiler.functions();
+    // Recursive calls count once in the total
+    assert_eq!(
+        functions[0],
+        FunctionProfile {
+            function: fact,
+            self_cycles: 34,
+            total_cycles: 34,
+            calls: 3,
+        }
+    );
+    assert_eq!(functions[1].total_cycles, 47);
+
+    let top = profiler.top(2, |addr| function_name(&program, addr));
+    let lines: Vec<_> = top.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("calls  function"));
+    assert_eq!(
+        lines[1],
+        "          34  72.34%           34  72.34%          3  fact"
+    );
+}
+
+#[test]
+fn test_tail_calls_and_unknown_functions() {
+    let program = assemble(
+        "
+main:
+    mv s0, ra
+    la t0, indirect
+    jalr t0
+    mv ra, s0
+    ret
+indirect:
+    j helper
+    nop
+helper:
+    nop
+    ret
+",
+    )
+    .unwrap();
+    let profiler = profile(&program);
+    // The tail call stays in the function that made it, and returns to main
+    let folded = profiler.folded(|addr| format!("0x{addr:x}"));
+    assert_eq!(folded, "0x10000 6\n0x10000;0x10018 3\n");
+
+    let unnamed = Elf {
+        symbols: Default::default(),
+        ..program
+    };
+    assert_eq!(function_name(&unnamed, 0x10018), "0x00010018");
+}
+
+#[test]
+fn test_call_stack_at_error() {
+    let program = assemble(
+        PROGRAM
+            .replace("leaf:\n    nop", "leaf:\n    ebreak")
+            .as_str(),
+    )
+    .unwrap();
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut profiler = Profiler::new(program.entry_point);
+    assert!(executor.run_with(&mut profiler).is_err());
+    let stack: Vec<_> = profiler
+        .call_stack()
+        .map(|addr| function_name(&program, addr))
+        .collect();
+    assert_eq!(stack, ["leaf", "main"]);
+}
+
+#[test]
+fn test_profile_binary() {
+    let path = std::env::temp_dir().join(format!("profile-{}.s", std::process::id()));
+    std::fs::write(&path, PROGRAM).unwrap();
+    let folded = Command::new(env!("CARGO_BIN_EXE_profile"))
+        .arg(&path)
+        .arg("folded")
+        .output()
+        .unwrap();
+    let top = Command::new(env!("CARGO_BIN_EXE_profile"))
+        .arg(&path)
+        .args(["top", "1"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(folded.status.success());
+    assert!(
+        String::from_utf8(folded.stdout)
+            .unwrap()
+            .contains("main;fact;fact 13\n")
+    );
+    assert_eq!(String::from_utf8(top.stdout).unwrap().lines().count(), 2);
+}
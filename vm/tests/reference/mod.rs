@@ -0,0 +1,217 @@
+// Reference model of RV32IM, transcribed from the unprivileged spec as directly as possible.
+// It shares no code with the VM, including the decoder, so that both can be checked
+// against each other. Speed and error reporting are not goals.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq)]
+pub enum Trap {
+    IllegalInstruction(u32),
+    // Valid instructions the model does not execute, e.g. ecall
+    Unsupported(u32),
+    MissingMemory(u32),
+}
+
+pub struct ReferenceMachine {
+    pub pc: u32,
+    pub x: [u32; 32],
+    // Byte addressed, so that sub-word accesses follow the spec literally
+    pub memory: BTreeMap<u32, u8>,
+}
+
+fn bits(word: u32, hi: u32, lo: u32) -> u32 {
+    (word >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+fn sign_extend(value: u32, width: u32) -> u32 {
+    let shift = 32 - width;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+impl ReferenceMachine {
+    pub fn new(image: &BTreeMap<u32, u32>, pc: u32) -> ReferenceMachine {
+        let mut memory = BTreeMap::new();
+        for (addr, word) in image {
+            for (i, byte) in word.to_le_bytes().into_iter().enumerate() {
+                memory.insert(addr + i as u32, byte);
+            }
+        }
+        ReferenceMachine {
+            pc,
+            x: [0; 32],
+            memory,
+        }
+    }
+
+    pub fn load(&self, addr: u32, size: u32) -> Result<u32, Trap> {
+        let mut value = 0;
+        for i in 0..size {
+            let byte_addr = addr.wrapping_add(i);
+            let byte = self
+                .memory
+                .get(&byte_addr)
+                .ok_or(Trap::MissingMemory(byte_addr))?;
+            value |= (*byte as u32) << (8 * i);
+        }
+        Ok(value)
+    }
+
+    fn store(&mut self, addr: u32, size: u32, value: u32) {
+        for i in 0..size {
+            self.memory
+                .insert(addr.wrapping_add(i), (value >> (8 * i)) as u8);
+        }
+    }
+
+    fn set(&mut self, rd: u32, value: u32) {
+        if rd != 0 {
+            self.x[rd as usize] = value;
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), Trap> {
+        let inst = self.load(self.pc, 4)?;
+        let opcode = bits(inst, 6, 0);
+        let rd = bits(inst, 11, 7);
+        let funct3 = bits(inst, 14, 12);
+        let rs1 = bits(inst, 19, 15);
+        let rs2 = bits(inst, 24, 20);
+        let funct7 = bits(inst, 31, 25);
+        let imm_i = sign_extend(bits(inst, 31, 20), 12);
+        let imm_s = sign_extend(bits(inst, 31, 25) << 5 | bits(inst, 11, 7), 12);
+        let imm_b = sign_extend(
+            bits(inst, 31, 31) << 12
+                | bits(inst, 7, 7) << 11
+                | bits(inst, 30, 25) << 5
+                | bits(inst, 11, 8) << 1,
+            13,
+        );
+        let imm_u = bits(inst, 31, 12) << 12;
+        let imm_j = sign_extend(
+            bits(inst, 31, 31) << 20
+                | bits(inst, 19, 12) << 12
+                | bits(inst, 20, 20) << 11
+                | bits(inst, 30, 21) << 1,
+            21,
+        );
+        let a = self.x[rs1 as usize];
+        let b = self.x[rs2 as usize];
+        let illegal = Trap::IllegalInstruction(inst);
+        let mut next_pc = self.pc.wrapping_add(4);
+
+        match opcode {
+            // LUI
+            0b0110111 => self.set(rd, imm_u),
+            // AUIPC
+            0b0010111 => self.set(rd, self.pc.wrapping_add(imm_u)),
+            // JAL
+            0b1101111 => {
+                self.set(rd, next_pc);
+                next_pc = self.pc.wrapping_add(imm_j);
+            }
+            // JALR
+            0b1100111 if funct3 == 0 => {
+                let target = a.wrapping_add(imm_i) & !1;
+                self.set(rd, next_pc);
+                next_pc = target;
+            }
+            // BRANCH
+            0b1100011 => {
+                let taken = match funct3 {
+                    0b000 => a == b,
+                    0b001 => a != b,
+                    0b100 => (a as i32) < (b as i32),
+                    0b101 => (a as i32) >= (b as i32),
+                    0b110 => a < b,
+                    0b111 => a >= b,
+                    _ => return Err(illegal),
+                };
+                if taken {
+                    next_pc = self.pc.wrapping_add(imm_b);
+                }
+            }
+            // LOAD
+            0b0000011 => {
+                let addr = a.wrapping_add(imm_i);
+                let value = match funct3 {
+                    0b000 => sign_extend(self.load(addr, 1)?, 8),
+                    0b001 => sign_extend(self.load(addr, 2)?, 16),
+                    0b010 => self.load(addr, 4)?,
+                    0b100 => self.load(addr, 1)?,
+                    0b101 => self.load(addr, 2)?,
+                    _ => return Err(illegal),
+                };
+                self.set(rd, value);
+            }
+            // STORE
+            0b0100011 => {
+                let addr = a.wrapping_add(imm_s);
+                match funct3 {
+                    0b000 => self.store(addr, 1, b),
+                    0b001 => self.store(addr, 2, b),
+                    0b010 => self.store(addr, 4, b),
+                    _ => return Err(illegal),
+                }
+            }
+            // OP-IMM
+            0b0010011 => {
+                let shamt = bits(inst, 24, 20);
+                let value = match (funct3, funct7) {
+                    (0b000, _) => a.wrapping_add(imm_i),
+                    (0b010, _) => ((a as i32) < (imm_i as i32)) as u32,
+                    (0b011, _) => (a < imm_i) as u32,
+                    (0b100, _) => a ^ imm_i,
+                    (0b110, _) => a | imm_i,
+                    (0b111, _) => a & imm_i,
+                    (0b001, 0b0000000) => a << shamt,
+                    (0b101, 0b0000000) => a >> shamt,
+                    (0b101, 0b0100000) => ((a as i32) >> shamt) as u32,
+                    _ => return Err(illegal),
+                };
+                self.set(rd, value);
+            }
+            // OP
+            0b0110011 => {
+                let shamt = b & 0x1f;
+                let value = match (funct7, funct3) {
+                    (0b0000000, 0b000) => a.wrapping_add(b),
+                    (0b0100000, 0b000) => a.wrapping_sub(b),
+                    (0b0000000, 0b001) => a << shamt,
+                    (0b0000000, 0b010) => ((a as i32) < (b as i32)) as u32,
+                    (0b0000000, 0b011) => (a < b) as u32,
+                    (0b0000000, 0b100) => a ^ b,
+                    (0b0000000, 0b101) => a >> shamt,
+                    (0b0100000, 0b101) => ((a as i32) >> shamt) as u32,
+                    (0b0000000, 0b110) => a | b,
+                    (0b0000000, 0b111) => a & b,
+                    // M extension
+                    (0b0000001, 0b000) => a.wrapping_mul(b),
+                    (0b0000001, 0b001) => ((a as i32 as i64 * b as i32 as i64) >> 32) as u32,
+                    (0b0000001, 0b010) => ((a as i32 as i64 * b as i64) >> 32) as u32,
+                    (0b0000001, 0b011) => ((a as u64 * b as u64) >> 32) as u32,
+                    (0b0000001, 0b100) => match (a as i32, b as i32) {
+                        (_, 0) => u32::MAX,
+                        (i32::MIN, -1) => a,
+                        (a, b) => (a / b) as u32,
+                    },
+                    (0b0000001, 0b101) => a.checked_div(b).unwrap_or(u32::MAX),
+                    (0b0000001, 0b110) => match (a as i32, b as i32) {
+                        (_, 0) => a,
+                        (i32::MIN, -1) => 0,
+                        (a, b) => (a % b) as u32,
+                    },
+                    (0b0000001, 0b111) => a.checked_rem(b).unwrap_or(a),
+                    _ => return Err(illegal),
+                };
+                self.set(rd, value);
+            }
+            // MISC-MEM: fences have no effect on a single hart without caches
+            0b0001111 => {}
+            // SYSTEM
+            0b1110011 => return Err(Trap::Unsupported(inst)),
+            _ => return Err(illegal),
+        }
+        self.pc = next_pc;
+        Ok(())
+    }
+}
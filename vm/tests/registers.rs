@@ -0,0 +1,46 @@
+use vm::vm::registers::{REGISTER_NAMES, Registers, register_index};
+
+#[test]
+fn test_x0_is_hardwired() {
+    let mut registers = Registers::default();
+    registers.write(0, 42);
+    registers.write(1, 42);
+    assert_eq!(registers.read(0), 0);
+    assert_eq!(registers.read(1), 42);
+}
+
+#[test]
+fn test_register_lookup_by_name() {
+    for (index, name) in REGISTER_NAMES.iter().enumerate() {
+        assert_eq!(register_index(name), Some(index as u32));
+        assert_eq!(register_index(&format!("x{index}")), Some(index as u32));
+    }
+    assert_eq!(register_index("fp"), Some(8));
+    assert_eq!(register_index("x32"), None);
+    assert_eq!(register_index("x+1"), None);
+    assert_eq!(register_index("a8"), None);
+    let mut registers = Registers::default();
+    registers.write(10, 7);
+    assert_eq!(registers.read_by_name("a0"), Some(7));
+    assert_eq!(registers.read_by_name("x10"), Some(7));
+    assert_eq!(registers.read_by_name("pc"), None);
+}
+
+#[test]
+fn test_registers_display() {
+    let mut registers = Registers::default();
+    for index in 0..32 {
+        registers.write(index, index * 0x1111);
+    }
+    let expected = "\
+x0  zero 0x00000000  x8  s0   0x00008888  x16 a6   0x00011110  x24 s8   0x00019998
+x1  ra   0x00001111  x9  s1   0x00009999  x17 a7   0x00012221  x25 s9   0x0001aaa9
+x2  sp   0x00002222  x10 a0   0x0000aaaa  x18 s2   0x00013332  x26 s10  0x0001bbba
+x3  gp   0x00003333  x11 a1   0x0000bbbb  x19 s3   0x00014443  x27 s11  0x0001cccb
+x4  tp   0x00004444  x12 a2   0x0000cccc  x20 s4   0x00015554  x28 t3   0x0001dddc
+x5  t0   0x00005555  x13 a3   0x0000dddd  x21 s5   0x00016665  x29 t4   0x0001eeed
+x6  t1   0x00006666  x14 a4   0x0000eeee  x22 s6   0x00017776  x30 t5   0x0001fffe
+x7  t2   0x00007777  x15 a5   0x0000ffff  x23 s7   0x00018887  x31 t6   0x0002110f
+";
+    assert_eq!(registers.to_string(), expected);
+}
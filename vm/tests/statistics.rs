@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, process::Command};
+
+use vm::{
+    assembler::assemble,
+    vm::{
+        execution::{Executor, run_program, run_program_with_statistics},
+        instructions::{ArithOp, Comparison, LoadStoreWidth},
+        statistics::InstructionMix,
+        syscalls::POSEIDON2_PERMUTE,
+    },
+};
+
+const PROGRAM: &str = "
+main:
+    li t0, 3
+loop:
+    addi t0, t0, -1
+    bnez t0, loop
+    la a0, state
+    sw t0, 64(a0)
+    addi a7, zero, 1
+    ecall
+    lw a0, 0(a0)
+    ret
+state:
+    .word 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+";
+
+fn mix() -> InstructionMix {
+    let program = assemble(PROGRAM).unwrap();
+    let mut executor = Executor::new(program.image, program.entry_point);
+    let mut mix = InstructionMix::default();
+    executor.run_with(&mut mix).unwrap();
+    assert_eq!(mix.cycles(), executor.cycles);
+    mix
+}
+
+#[test]
+fn test_instruction_mix() {
+    let mix = mix();
+    assert_eq!(
+        mix.instructions,
+        BTreeMap::from([
+            ("AddUpperImmToPc", 1),
+            ("ArithImm", 6),
+            ("Branch", 3),
+            ("EnvironmentCall", 1),
+            ("JumpAndLinkRegister", 1),
+            ("Load", 1),
+            ("Store", 1),
+        ])
+    );
+    assert_eq!(mix.arith_ops, BTreeMap::from([(ArithOp::Add, 6)]));
+    assert_eq!(mix.comparisons, BTreeMap::from([(Comparison::NotEqual, 3)]));
+    assert_eq!(mix.loads, BTreeMap::from([(LoadStoreWidth::Word, 1)]));
+    assert_eq!(mix.stores, BTreeMap::from([(LoadStoreWidth::Word, 1)]));
+    assert_eq!(mix.syscalls, BTreeMap::from([(POSEIDON2_PERMUTE, 1)]));
+    assert_eq!((mix.branches_taken, mix.branches_not_taken), (2, 1));
+    // The permutation reads and writes the 16 words of its state
+    assert_eq!((mix.memory_reads, mix.memory_writes), (17, 17));
+}
+
+#[test]
+fn test_executor_statistics() {
+    let program = assemble(PROGRAM).unwrap();
+    // Run in whole blocks
+    let mut executor = Executor::new(program.image.clone(), program.entry_point).with_statistics();
+    executor.run().unwrap();
+    assert_eq!(executor.statistics(), Some(&mix()));
+    assert!(
+        Executor::new(program.image.clone(), program.entry_point)
+            .statistics()
+            .is_none()
+    );
+    let result = run_program(program.image.clone(), program.entry_point).unwrap();
+    let (result_with_statistics, statistics) =
+        run_program_with_statistics(program.image, program.entry_point).unwrap();
+    assert_eq!(result_with_statistics, result);
+    assert_eq!(statistics, mix());
+}
+
+#[test]
+fn test_branches_to_the_next_instruction() {
+    let program = assemble(
+        "
+main:
+    beq zero, zero, next
+next:
+    bne zero, zero, last
+last:
+    ret
+",
+    )
+    .unwrap();
+    let (_, statistics) = run_program_with_statistics(program.image, program.entry_point).unwrap();
+    assert_eq!(
+        (statistics.branches_taken, statistics.branches_not_taken),
+        (1, 1)
+    );
+}
+
+#[test]
+fn test_stopped_instructions_are_not_counted() {
+    let program =
+        assemble("main:\n    li t0, 1\n    bnez t0, stop\nstop:\n    ebreak\n    ret").unwrap();
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut mix = InstructionMix::default();
+    assert!(executor.run_with(&mut mix).is_err());
+    assert_eq!(mix.cycles(), executor.cycles);
+    assert_eq!(mix.instructions.get("EnvironmentBreak"), None);
+    assert_eq!((mix.branches_taken, mix.branches_not_taken), (1, 0));
+
+    let mut executor = Executor::new(program.image, program.entry_point).with_statistics();
+    assert!(executor.run().is_err());
+    assert_eq!(executor.statistics(), Some(&mix));
+}
+
+#[test]
+fn test_report() {
+    let report = mix().to_string();
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(lines[0], "Instructions: 14");
+    // Most frequent first, then by name
+    assert_eq!(lines[1], "  ArithImm                            6  42.86%");
+    assert_eq!(lines[3], "  AddUpperImmToPc                     1   7.14%");
+    assert!(report.contains("Branches: 2 taken (66.67%), 1 not taken\n"));
+    assert!(report.contains("Memory: 17 reads, 17 writes\n"));
+    assert!(report.ends_with("Syscalls:\n  0x01                                1 100.00%\n"));
+
+    // Nothing ran
+    let empty = InstructionMix::default().to_string();
+    assert!(empty.starts_with("Instructions: 0\nArithmetic operations:\n"));
+    assert!(empty.contains("Branches: 0 taken (0.00%), 0 not taken\n"));
+}
+
+#[test]
+fn test_mix_report_binary() {
+    let path = std::env::temp_dir().join(format!("mix-{}.s", std::process::id()));
+    std::fs::write(&path, PROGRAM).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_profile"))
+        .arg(&path)
+        .arg("mix")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), mix().to_string());
+}
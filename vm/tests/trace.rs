@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::{execution::Executor, instructions::Instruction, trace::ExecutionTrace},
+};
+
+fn record(program: &Elf) -> (Executor, ExecutionTrace) {
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut trace = ExecutionTrace::default();
+    executor.run_with(&mut trace).unwrap();
+    (executor, trace)
+}
+
+#[test]
+fn test_trace_records_operands_and_accesses() {
+    let source = "
+main:
+    la t0, value
+    li t1, 7
+    sw t1, 0(t0)
+    lw a0, 0(t0)
+    add a1, a0, t1
+    ret
+value:
+    .word 5
+";
+    let program = assemble(source).unwrap();
+    let value = program.symbol_address("value").unwrap();
+    let (_, trace) = record(&program);
+    assert_eq!(trace.len(), 7);
+    let steps = &trace.steps;
+    // sw t1, 0(t0)
+    assert!(matches!(steps.instruction[3], Instruction::Store { .. }));
+    assert_eq!((steps.rs1_value[3], steps.rs2_value[3]), (value, 7));
+    assert_eq!(steps.rd_value[3], 0);
+    // add a1, a0, t1
+    assert_eq!(steps.pc[5], steps.pc[0] + 20);
+    assert_eq!((steps.rs1_value[5], steps.rs2_value[5]), (7, 7));
+    assert_eq!(steps.rd_value[5], 14);
+
+    let memory = &trace.memory;
+    assert_eq!(memory.timestamp, [1, 2]);
+    assert_eq!(memory.cycle, [3, 4]);
+    assert_eq!(memory.addr, [value, value]);
+    assert_eq!(memory.value, [7, 7]);
+    assert_eq!(memory.old_value, [5, 7]);
+    assert_eq!(memory.is_write, [true, false]);
+}
+
+#[test]
+fn test_trace_is_consistent_with_execution() {
+    for entry in std::fs::read_dir("./program_artifacts/asm").unwrap() {
+        let path = entry.unwrap().path();
+        let program = Elf::load(&std::fs::read(&path).unwrap()).unwrap();
+        let mut executor = Executor::new(program.image.clone(), program.entry_point);
+        let mut trace = ExecutionTrace::default();
+        if executor.run_with(&mut trace).is_err() {
+            continue;
+        }
+        assert_eq!(trace.len() as u64, executor.cycles, "{path:?}");
+        let steps = &trace.steps;
+        for column in [&steps.rs1_value, &steps.rs2_value, &steps.rd_value] {
+            assert_eq!(column.len(), trace.len());
+        }
+
+        // Replaying the accesses from the initial image must agree with every old value,
+        // and end with the final memory
+        let mut memory: BTreeMap<u32, u32> = program.image.clone();
+        let columns = &trace.memory;
+        for row in 0..columns.addr.len() {
+            let addr = columns.addr[row];
+            let current = memory.get(&addr).copied().unwrap_or(0);
+            assert_eq!(columns.old_value[row], current, "{path:?}");
+            assert_eq!(columns.timestamp[row], row as u64 + 1);
+            if columns.is_write[row] {
+                memory.insert(addr, columns.value[row]);
+            } else {
+                assert_eq!(columns.value[row], current, "{path:?}");
+            }
+        }
+        assert_eq!(&memory, executor.memory.words(), "{path:?}");
+    }
+}
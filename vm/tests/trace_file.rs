@@ -0,0 +1,124 @@
+mod common;
+
+use std::process::Command;
+
+use common::STORE_AND_LOAD;
+use vm::{
+    assembler::assemble,
+    elf::Elf,
+    vm::{
+        execution::Executor,
+        trace::ExecutionTrace,
+        trace_file::{MAGIC, Record, TraceFileError, TraceReader, TraceWriter, read_trace},
+    },
+};
+
+fn write_trace(program: &Elf) -> Vec<u8> {
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut writer = TraceWriter::new(Vec::new()).unwrap();
+    executor.run_with(&mut writer).unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_trace_file_round_trip() {
+    let source = std::fs::read_to_string("./programs/asm/poseidon2_permute.s").unwrap();
+    let program = assemble(&source).unwrap();
+    let mut executor = Executor::new(program.image.clone(), program.entry_point);
+    let mut in_memory = ExecutionTrace::default();
+    executor.run_with(&mut in_memory).unwrap();
+
+    let read = read_trace(write_trace(&program).as_slice()).unwrap();
+    assert_eq!(read.steps, in_memory.steps);
+    assert_eq!(read.memory, in_memory.memory);
+}
+
+#[test]
+fn test_trace_reader_streams_records_in_order() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let bytes = write_trace(&program);
+    let records: Vec<_> = TraceReader::new(bytes.as_slice())
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 6 + 2);
+    // The store's access comes before its step
+    assert!(matches!(records[3], Record::MemoryAccess(access) if access.is_write));
+    assert!(matches!(records[4], Record::Step(step) if step.rs2_value == 7));
+}
+
+#[test]
+fn test_trace_reader_rejects_invalid_files() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let bytes = write_trace(&program);
+    let read_all = |bytes: &[u8]| -> Result<Vec<Record>, TraceFileError> {
+        TraceReader::new(bytes)?.collect()
+    };
+    assert!(read_all(&bytes).is_ok());
+    assert!(matches!(read_all(&[]), Err(TraceFileError::NotATrace)));
+    assert!(matches!(
+        read_all(b"NOTATRACE!"),
+        Err(TraceFileError::NotATrace)
+    ));
+
+    let mut newer = bytes.clone();
+    newer[MAGIC.len()] = 2;
+    assert!(matches!(
+        read_all(&newer),
+        Err(TraceFileError::UnsupportedVersion(2))
+    ));
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(matches!(
+        read_all(truncated),
+        Err(TraceFileError::Truncated)
+    ));
+
+    // The length of the first record, a step
+    let mut wrong_length = bytes.clone();
+    wrong_length[MAGIC.len() + 2] += 1;
+    assert!(matches!(
+        read_all(&wrong_length),
+        Err(TraceFileError::InvalidRecord { kind: 0, .. })
+    ));
+}
+
+#[test]
+fn test_dump_trace() {
+    let program = assemble(STORE_AND_LOAD).unwrap();
+    let value = program.symbol_address("value").unwrap();
+    let path = std::env::temp_dir().join(format!("dump-trace-{}.trace", std::process::id()));
+    std::fs::write(&path, write_trace(&program)).unwrap();
+    let dump = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dump-trace"))
+            .arg(&path)
+            .arg(format)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let csv = dump("csv");
+    let jsonl = dump("jsonl");
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 1 + 8);
+    assert!(lines[0].starts_with("record,cycle,pc,instruction,"));
+    assert_eq!(
+        lines[4],
+        format!("memory,3,,,,,,1,0x{value:08x},0x00000007,0x00000005,true")
+    );
+    assert!(lines[5].starts_with("step,3,") && lines[5].contains(",\"sw t1, 0(t0)\","));
+
+    let lines: Vec<_> = jsonl.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(
+        lines[6],
+        format!(
+            "{{\"record\":\"step\",\"cycle\":4,\"pc\":{},\"instruction\":\"lw a0, 0(t0)\",\
+             \"rs1_value\":{value},\"rs2_value\":0,\"rd_value\":7}}",
+            program.entry_point + 16
+        )
+    );
+}